@@ -0,0 +1,135 @@
+//! Attributes guarding axum handlers with the IAM of Ark.
+//!
+//! ```ignore
+//! #[require_permission("ban.user")]
+//! async fn ban(Path(user_id): Path<String>) -> CustomJsonResponse<String> {
+//!     // only reached by users holding ban.user.
+//! }
+//! ```
+//!
+//! Each attribute adds a `Guarded` extractor in front of the arguments of the
+//! handler, it authenticates the request (401 otherwise) and checks the
+//! requirement against the role and permission caches (403 otherwise).
+//! Stacked attributes must all be met, handlers can be free functions or
+//! associated functions of an `impl` block.
+//!
+//! The expansion names the guard module through `::ark::__guard`, the crate
+//! using the attributes re-exports it there.
+
+use proc_macro::TokenStream;
+use proc_macro2::Literal;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Error, FnArg, ItemFn, LitStr};
+
+/// Only lets users holding a permission, directly or through a role, reach the handler.
+///
+/// The key is checked when compiling, it is made of segments of ascii letters,
+/// digits, `_` or `-` separated by dots ex: `ban.user`.
+///
+/// # Examples
+/// ```ignore
+/// #[require_permission("ban.user")]
+/// async fn ban(Path(user_id): Path<String>) -> CustomJsonResponse<String> { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn require_permission(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let permission_key = parse_macro_input!(attr as LitStr);
+    let handler = parse_macro_input!(item as ItemFn);
+    if let Err(reason) = validate_permission_key(&permission_key.value()) {
+        return Error::new(permission_key.span(), reason)
+            .to_compile_error()
+            .into();
+    }
+    let key = type_str(&permission_key.value());
+    guard(handler, quote!(::ark::__guard::PermissionOf<#key>))
+}
+
+/// Only lets users holding a role, by name or id, reach the handler.
+///
+/// # Examples
+/// ```ignore
+/// #[require_role("Admin")]
+/// async fn admin() -> CustomJsonResponse<String> { ... }
+/// ```
+#[proc_macro_attribute]
+pub fn require_role(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let role = parse_macro_input!(attr as LitStr);
+    let handler = parse_macro_input!(item as ItemFn);
+    if role.value().trim().is_empty() {
+        return Error::new(role.span(), "the role cannot be empty")
+            .to_compile_error()
+            .into();
+    }
+    let key = type_str(&role.value());
+    guard(handler, quote!(::ark::__guard::RoleOf<#key>))
+}
+
+/// Puts the `Guarded` extractor of the requirement first in the arguments of
+/// the handler, after `self` if there is one. Nothing is emitted next to the
+/// handler so it can sit in an `impl` block.
+///
+/// # Arguments
+/// * `handler` - The handler being guarded.
+/// * `requirement` - The type implementing `GuardRequirement`.
+fn guard(mut handler: ItemFn, requirement: proc_macro2::TokenStream) -> TokenStream {
+    if handler.sig.asyncness.is_none() {
+        return Error::new(
+            handler.sig.fn_token.span,
+            "guarded handlers must be async functions",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let position = match handler.sig.inputs.first() {
+        Some(FnArg::Receiver(_)) => 1,
+        _ => 0,
+    };
+    handler.sig.inputs.insert(
+        position,
+        parse_quote!(_: ::ark::__guard::Guarded<#requirement>),
+    );
+    quote!(#handler).into()
+}
+
+/// Spells a literal out as a `TypeStr`, ex: `ban` becomes
+/// `Chars<'b', Chars<'a', Chars<'n', End>>>`.
+fn type_str(value: &str) -> proc_macro2::TokenStream {
+    value
+        .chars()
+        .rev()
+        .fold(quote!(::ark::__guard::End), |rest, c| {
+            let c = Literal::character(c);
+            quote!(::ark::__guard::Chars<#c, #rest>)
+        })
+}
+
+/// Checks the syntax of a permission key.
+///
+/// # Examples
+/// ```ignore
+/// assert!(validate_permission_key("ban.user").is_ok());
+/// assert!(validate_permission_key("ban..user").is_err());
+/// ```
+fn validate_permission_key(permission_key: &str) -> Result<(), String> {
+    if permission_key.is_empty() {
+        return Err(String::from("the permission key cannot be empty"));
+    }
+    for segment in permission_key.split('.') {
+        if segment.is_empty() {
+            return Err(format!(
+                "`{}` has an empty segment, segments are separated by a single dot",
+                permission_key
+            ));
+        }
+        if let Some(c) = segment
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || *c == '_' || *c == '-'))
+        {
+            return Err(format!(
+                "`{}` contains `{}`, segments are made of ascii letters, digits, `_` or `-`",
+                permission_key, c
+            ));
+        }
+    }
+    Ok(())
+}
@@ -1,43 +1,93 @@
--- This table stores information about various identity roles.
-CREATE TABLE iam_roles (
+-- This table stores the organizations (communities) hosted on the deployment,
+-- each one defines its own roles and permissions.
+CREATE TABLE iam_organizations (
     id VARCHAR(255),
-    role_name VARCHAR(255) NOT NULL UNIQUE,
+    org_name VARCHAR(255) NOT NULL UNIQUE,
+    created_at BIGINT NOT NULL DEFAULT (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT,
     PRIMARY KEY(id)
 );
 
--- This table stores the basic information about each permission.
+-- This table stores information about various identity roles. A role without
+-- an org_id is a system role, usable in every organization. Names are unique
+-- per organization.
+CREATE TABLE iam_roles (
+    id VARCHAR(255),
+    role_name VARCHAR(255) NOT NULL,
+    max_sessions INTEGER DEFAULT NULL CHECK (max_sessions >= 1),
+    session_limit_action VARCHAR(16) DEFAULT NULL,
+    org_id VARCHAR(255) DEFAULT NULL REFERENCES iam_organizations(id) ON DELETE CASCADE,
+    PRIMARY KEY(id),
+    UNIQUE (org_id, role_name)
+);
+CREATE UNIQUE INDEX iam_roles_system_role_name ON iam_roles (role_name) WHERE org_id IS NULL;
+
+-- This table stores the basic information about each permission. A permission
+-- without an org_id is a system permission, names and keys are unique per
+-- organization.
 CREATE TABLE iam_permissions (
     id VARCHAR(255),
-    permission_name VARCHAR(255) NOT NULL UNIQUE,
-    permission_key VARCHAR(255) NOT NULL UNIQUE,
-    PRIMARY KEY (id)
+    permission_name VARCHAR(255) NOT NULL,
+    permission_key VARCHAR(255) NOT NULL,
+    org_id VARCHAR(255) DEFAULT NULL REFERENCES iam_organizations(id) ON DELETE CASCADE,
+    PRIMARY KEY (id),
+    UNIQUE (org_id, permission_name),
+    UNIQUE (org_id, permission_key)
 );
+CREATE UNIQUE INDEX iam_permissions_system_permission_name ON iam_permissions (permission_name) WHERE org_id IS NULL;
+CREATE UNIQUE INDEX iam_permissions_system_permission_key ON iam_permissions (permission_key) WHERE org_id IS NULL;
 -- This table stores information about the user
 CREATE TABLE iam_users (
     id VARCHAR(255),
     username VARCHAR(255) UNIQUE,
     email VARCHAR(255) UNIQUE,
     verified BOOLEAN NOT NULL DEFAULT FALSE,
+    avatar_url VARCHAR(512) DEFAULT NULL,
     security_token VARCHAR(255) DEFAULT NULL,
     security_stamp VARCHAR(255) DEFAULT NULL,
     created_at BIGINT NOT NULL DEFAULT (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT,
     updated_at BIGINT NOT NULL DEFAULT (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT,
     PRIMARY KEY(id)
 );
+
+-- This table links users with the organizations they are members of, only
+-- members can be granted the roles and permissions of an organization.
+CREATE TABLE iam_organization_member (
+    org_id VARCHAR(255) NOT NULL REFERENCES iam_organizations(id) ON DELETE CASCADE,
+    user_id VARCHAR(255) NOT NULL REFERENCES iam_users(id) ON DELETE CASCADE,
+    joined_at BIGINT NOT NULL DEFAULT (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT,
+    PRIMARY KEY (org_id, user_id)
+);
+CREATE INDEX iam_organization_member_user_id ON iam_organization_member (user_id);
+
 -- This table links roles with permissions, enabling a many-to-many 
 -- relationship where a role can have multiple permissions, and a 
--- permission can belong to multiple roles.
+-- permission can belong to multiple roles. A denied link takes the
+-- permission away even when something else grants it.
 CREATE TABLE iam_role_permission (
     role_id VARCHAR(255) REFERENCES iam_roles(id) ON DELETE CASCADE,
     permission_id VARCHAR(255) REFERENCES iam_permissions(id) ON DELETE CASCADE,
+    denied BOOLEAN NOT NULL DEFAULT FALSE,
     PRIMARY KEY (role_id, permission_id)
 );
 
+-- This table links roles with the roles they inherit from, a role holds the
+-- permissions of its parents and of their ancestors. Cycles are refused when
+-- linking, deleting either role drops the link.
+CREATE TABLE iam_role_parent (
+    role_id VARCHAR(255) REFERENCES iam_roles(id) ON DELETE CASCADE,
+    parent_id VARCHAR(255) REFERENCES iam_roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (role_id, parent_id)
+);
+
 -- This table links identities with permissions, enabling a many-to-many 
 -- relationship where an identity can have specific permissions for themselves.
+-- A denied link takes the permission away even when one of their roles grants it.
 CREATE TABLE iam_user_permission (
     user_id VARCHAR(255) REFERENCES iam_users(id) ON DELETE CASCADE,
     permission_id VARCHAR(255) REFERENCES iam_permissions(id) ON DELETE CASCADE,
+    denied BOOLEAN NOT NULL DEFAULT FALSE,
+    -- when the grant lapses (milliseconds), a grant without one never does.
+    expires_at BIGINT DEFAULT NULL,
     PRIMARY KEY (user_id, permission_id)
 );
 
@@ -45,16 +95,72 @@ CREATE TABLE iam_user_permission (
 CREATE TABLE iam_user_role (
     user_id VARCHAR(255),
     role_id VARCHAR(255) NOT NULL REFERENCES iam_roles(id) ON DELETE CASCADE,
+    -- when the grant lapses (milliseconds), a grant without one never does.
+    expires_at BIGINT DEFAULT NULL,
     PRIMARY KEY (user_id, role_id)
 );
 
--- No changes needed for iam_user_oauth as it deals with OAuth identities and 
--- not directly with roles or permissions. However, ensuring that user deletion 
--- cascades to this table.
+-- This table links users with roles held on a single resource, a user can be
+-- a Moderator of one channel only. Roles of iam_user_role apply on every
+-- resource.
+CREATE TABLE iam_user_scoped_role (
+    user_id VARCHAR(255) NOT NULL REFERENCES iam_users(id) ON DELETE CASCADE,
+    role_id VARCHAR(255) NOT NULL REFERENCES iam_roles(id) ON DELETE CASCADE,
+    resource_type VARCHAR(255) NOT NULL,
+    resource_id VARCHAR(255) NOT NULL,
+    PRIMARY KEY (user_id, role_id, resource_type, resource_id)
+);
+
+-- This table links users with permissions held on a single resource.
+-- Permissions of iam_user_permission apply on every resource.
+CREATE TABLE iam_user_scoped_permission (
+    user_id VARCHAR(255) NOT NULL REFERENCES iam_users(id) ON DELETE CASCADE,
+    permission_id VARCHAR(255) NOT NULL REFERENCES iam_permissions(id) ON DELETE CASCADE,
+    resource_type VARCHAR(255) NOT NULL,
+    resource_id VARCHAR(255) NOT NULL,
+    PRIMARY KEY (user_id, permission_id, resource_type, resource_id)
+);
+
+-- This table links OAuth identities with users, a user can link an identity
+-- from as many providers as they want but an identity only ever belongs to
+-- one user. Ensuring that user deletion cascades to this table.
 CREATE TABLE iam_user_oauth (
     oauth_id VARCHAR(255) NOT NULL,
     oauth_provider VARCHAR(255) NOT NULL,
     user_id VARCHAR(255) NOT NULL REFERENCES iam_users(id) ON DELETE CASCADE,
-    UNIQUE(oauth_id, oauth_provider),
-    PRIMARY KEY(user_id)
+    -- the tokens the provider issued, encrypted.
+    access_token TEXT DEFAULT NULL,
+    refresh_token TEXT DEFAULT NULL,
+    token_expires_at BIGINT DEFAULT NULL,
+    PRIMARY KEY(oauth_id, oauth_provider)
+);
+CREATE INDEX iam_user_oauth_user_id ON iam_user_oauth (user_id);
+
+-- These tables store the sessions and their refresh tokens when SESSION_STORE=postgres,
+-- both are keyed by the SHA-256 digest of their token and grouped into families by
+-- session_id. Timestamps are in milliseconds, expired rows are skipped.
+CREATE TABLE iam_sessions (
+    digest VARCHAR(64),
+    user_id VARCHAR(255) NOT NULL REFERENCES iam_users(id) ON DELETE CASCADE,
+    session_id VARCHAR(255) NOT NULL,
+    security_stamp VARCHAR(255) NOT NULL,
+    metadata TEXT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    PRIMARY KEY(digest)
+);
+CREATE INDEX iam_sessions_user_id ON iam_sessions (user_id);
+
+CREATE TABLE iam_session_refresh_tokens (
+    digest VARCHAR(64),
+    user_id VARCHAR(255) NOT NULL REFERENCES iam_users(id) ON DELETE CASCADE,
+    session_id VARCHAR(255) NOT NULL,
+    session_digest VARCHAR(64) NOT NULL,
+    security_stamp VARCHAR(255) NOT NULL,
+    metadata TEXT NOT NULL,
+    -- a used refresh token is kept until it expires so that reuse is noticed.
+    used BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    PRIMARY KEY(digest)
 );
+CREATE INDEX iam_session_refresh_tokens_user_id ON iam_session_refresh_tokens (user_id);
@@ -1 +1,2 @@
-pub mod oauth_adapter;
\ No newline at end of file
+pub mod oauth_adapter;
+pub mod profile_mapper;
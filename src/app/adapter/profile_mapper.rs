@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use super::oauth_adapter::{OAuthAdapterError, OAuthProfile};
+
+/// Maps the userinfo returned by a provider onto an `OAuthProfile`.
+///
+/// Every provider names its fields differently, implement this trait and add
+/// it to `create_profile_mapper` to support one that isn't covered below.
+pub trait ProfileMapper: Send + Sync {
+    /// Maps the userinfo of the provider.
+    ///
+    /// # Arguments
+    /// * `userinfo` - The JSON returned by the userinfo endpoint of the provider.
+    ///
+    /// # Examples
+    /// ```
+    /// let profile = DiscordProfileMapper.map(&userinfo)?;
+    /// ```
+    fn map(&self, userinfo: &Value) -> Result<OAuthProfile, OAuthAdapterError>;
+}
+
+/// Maps the userinfo of Discord's `/users/@me`.
+pub struct DiscordProfileMapper;
+
+impl ProfileMapper for DiscordProfileMapper {
+    fn map(&self, userinfo: &Value) -> Result<OAuthProfile, OAuthAdapterError> {
+        let oauth_id = read_id(&userinfo["id"]).ok_or(OAuthAdapterError::UserInfoFailed)?;
+        let avatar_url = read_string(&userinfo["avatar"]).map(|avatar| {
+            format!(
+                "https://cdn.discordapp.com/avatars/{}/{}.png",
+                oauth_id, avatar
+            )
+        });
+        Ok(OAuthProfile {
+            username: read_string(&userinfo["username"]),
+            email: read_string(&userinfo["email"]),
+            verified: userinfo["verified"].as_bool(),
+            avatar_url,
+            oauth_id,
+        })
+    }
+}
+
+/// Maps the userinfo of GitHub's `/user`.
+///
+/// GitHub does not say whether the public email is verified.
+pub struct GitHubProfileMapper;
+
+impl ProfileMapper for GitHubProfileMapper {
+    fn map(&self, userinfo: &Value) -> Result<OAuthProfile, OAuthAdapterError> {
+        Ok(OAuthProfile {
+            oauth_id: read_id(&userinfo["id"]).ok_or(OAuthAdapterError::UserInfoFailed)?,
+            username: read_string(&userinfo["login"]),
+            email: read_string(&userinfo["email"]),
+            verified: None,
+            avatar_url: read_string(&userinfo["avatar_url"]),
+        })
+    }
+}
+
+/// Maps the standard OpenID Connect claims (`sub`, `preferred_username`,
+/// `email`, `email_verified` and `picture`), falling back to `id` for the
+/// identifier.
+pub struct StandardProfileMapper;
+
+impl ProfileMapper for StandardProfileMapper {
+    fn map(&self, userinfo: &Value) -> Result<OAuthProfile, OAuthAdapterError> {
+        Ok(OAuthProfile {
+            oauth_id: read_id(&userinfo["sub"])
+                .or_else(|| read_id(&userinfo["id"]))
+                .ok_or(OAuthAdapterError::UserInfoFailed)?,
+            username: read_string(&userinfo["preferred_username"]),
+            email: read_string(&userinfo["email"]),
+            verified: userinfo["email_verified"].as_bool(),
+            avatar_url: read_string(&userinfo["picture"]),
+        })
+    }
+}
+
+/// Creates the mapper of a provider.
+///
+/// The mapper is chosen through `{PROVIDER}_PROFILE_MAPPER` (discord, github
+/// or standard) and defaults to the name of the provider, anything unknown
+/// is mapped as standard claims.
+///
+/// # Arguments
+/// * `name` - The name of the mapper ex: discord.
+///
+/// # Examples
+/// ```
+/// let mapper = create_profile_mapper("discord");
+/// ```
+pub fn create_profile_mapper(name: &str) -> Arc<dyn ProfileMapper> {
+    match name.to_lowercase().as_str() {
+        "discord" => Arc::new(DiscordProfileMapper),
+        "github" => Arc::new(GitHubProfileMapper),
+        _ => Arc::new(StandardProfileMapper),
+    }
+}
+
+/// Reads an identifier that providers send either as a string or a number.
+fn read_id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+fn read_string(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+}
@@ -1,5 +1,5 @@
 use core::fmt;
-use std::{env, sync::Arc};
+use std::{env, net::SocketAddr, sync::Arc};
 
 use axum::{extract::FromRef, Extension, Router};
 use tokio::net::TcpListener;
@@ -13,9 +13,14 @@ use super::{
         redis::{RedisConfig, RedisDatabase},
     },
     platform::iam::{
+        organization::manager::OrganizationManager,
         permission::manager::PermissionManager,
         role::manager::RoleManager,
-        user::manager::UserManager,
+        session::{
+            manager::SessionManager,
+            store::{session_store_from_env, SharedSessionStore},
+        },
+        user::{manager::UserManager, route, sweeper::GrantSweeper},
     },
     service::{cache::manager::CacheManager, task::manager::TaskManager},
 };
@@ -26,6 +31,7 @@ static MODE: ServerMode = ServerMode::Development;
 
 pub static SESSION_COOKIE_NAME: &str = "pl.session";
 pub static INTEGRITY_COOKIE_NAME: &str = "pl.integrity";
+pub static REFRESH_COOKIE_NAME: &str = "pl.refresh";
 
 /// Represents a server configuration.
 ///
@@ -53,16 +59,20 @@ pub struct ArkServer {
     port: usize,
     mode: ServerMode,
     router: Router,
+    sessions: SharedSessionStore,
 }
 
 impl ArkServer {
     pub async fn default() -> Self {
+        let state = ArkState::default().await;
         Self {
             address: ADDRESS.to_string(),
             port: PORT,
             mode: MODE,
+            sessions: state.sessions.clone(),
             router: Router::new()
-                .layer(Extension(Arc::new(ArkState::default().await)))
+                .merge(route::routes())
+                .layer(Extension(Arc::new(state)))
                 .layer(CookieManagerLayer::new()),
         }
     }
@@ -82,7 +92,7 @@ impl ArkServer {
     /// ```
     pub async fn run(self, pg: PostgresDatabase, redis: RedisDatabase) {
         let tcp = TcpListener::bind(&self.get_addr()).await.unwrap();
-        println!("[ARK] mode: {}", self.mode.to_string());
+        println!("[ARK] mode: {}", self.mode);
         match self.mode {
             ServerMode::Production => {}
             ServerMode::Development => {
@@ -92,14 +102,22 @@ impl ArkServer {
                 Self::enable_tracing();
             }
         }
-        Self::register_listeners(pg, redis).await;
+        Self::register_listeners(pg, redis, self.sessions.clone()).await;
         Self::preload_necessities().await;
+        GrantSweeper::start(GrantSweeper::interval_from_env());
         println!(
             "[ARK] router initialized, now listening on port {}.",
             &self.port
         );
         //Self::load_prerequisites(pg.clone(), redis.clone()).await;
-        axum::serve(tcp, self.router).await.unwrap();
+        // the address of the client is recorded alongside their sessions.
+        axum::serve(
+            tcp,
+            self.router
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+        .unwrap();
     }
 
     /// Retrieves the full network address of the server.
@@ -155,6 +173,7 @@ impl ArkServer {
     ///
     /// * `pg` - An instance of `PostgresDatabase` representing the connection to the PostgreSQL database.
     /// * `redis` - An instance of `RedisDatabase` representing the connection to the Redis database.
+    /// * `sessions` - The session store picked by `ArkState`.
     ///
     /// # Examples
     ///
@@ -163,18 +182,23 @@ impl ArkServer {
     /// async fn main() {
     ///     let pg_database = PostgresDatabase::new(/* PostgreSQL connection parameters */);
     ///     let redis_database = RedisDatabase::new(/* Redis connection parameters */);
-    ///     register_tasks(pg_database, redis_database).await;
+    ///     register_tasks(pg_database, redis_database, sessions).await;
     /// }
     /// ```
-    async fn register_listeners(pg: PostgresDatabase, redis: RedisDatabase) {
-        TaskManager::new(pg, redis.clone()).listen();
+    async fn register_listeners(pg: PostgresDatabase, redis: RedisDatabase, sessions: SharedSessionStore) {
+        TaskManager::new(pg, sessions).listen();
         CacheManager::new(redis).listen();
     }
 
     async fn preload_necessities() {
+        OrganizationManager::preload_organization_cache().unwrap();
         PermissionManager::preload_permission_cache().unwrap();
         RoleManager::preload_role_cache().unwrap();
         UserManager::preload_user_cache().unwrap();
+        // the purge runs again on the next start when it fails.
+        if let Err(er) = SessionManager::purge_legacy_sessions() {
+            println!("[ARK] failed to purge legacy sessions: {}", er);
+        }
     }
     
 }
@@ -185,6 +209,7 @@ impl ArkServer {
 /// - `Production`: Mode indicating the server is in a live, production environment (value 0).
 /// - `Development`: Mode for development and testing purposes (value 1).
 /// - `Maintenance`: Indicates the server is in maintenance mode, possibly for updates or repairs (value 2).
+#[allow(dead_code)]
 #[derive(Clone, Copy, PartialEq)]
 enum ServerMode {
     Production,
@@ -208,6 +233,7 @@ pub struct ArkState {
     pub postgres: PostgresDatabase,
     pub auth: OAuthCollectionAdapter,
     pub redis: RedisDatabase,
+    pub sessions: SharedSessionStore,
 }
 
 impl FromRef<ArkState> for Key {
@@ -218,11 +244,15 @@ impl FromRef<ArkState> for Key {
 
 impl ArkState {
     async fn default() -> Self {
+        let postgres = PostgresDatabase::new(PostgresConfig::default()).await;
+        let redis = RedisDatabase::new(RedisConfig::default()).await;
         Self {
             key: ArkState::get_key(),
-            postgres: PostgresDatabase::new(PostgresConfig::default()).await,
-            redis: RedisDatabase::new(RedisConfig::default()).await,
-            auth: OAuthCollectionAdapter::new(),
+            // the session store is picked once, through SESSION_STORE.
+            sessions: session_store_from_env(&postgres, &redis),
+            postgres,
+            redis,
+            auth: OAuthCollectionAdapter::new().await,
         }
     }
 
@@ -243,3 +273,76 @@ impl ArkState {
 //    .permission_name("permission_nadme")
 //    .build();
 //  PermissionManager::create_permission(permission);
+
+/// What tests need to run routes and tasks without postgres or redis.
+#[cfg(test)]
+pub mod testing {
+    use std::sync::Arc;
+
+    use bb8::Pool;
+    use bb8_postgres::{tokio_postgres::NoTls, PostgresConnectionManager};
+    use bb8_redis::RedisConnectionManager;
+    use once_cell::sync::Lazy;
+    use tokio::sync::{Mutex, MutexGuard};
+    use tower_cookies::Key;
+
+    use super::ArkState;
+    use crate::app::{
+        adapter::oauth_adapter::OAuthCollectionAdapter,
+        database::{postgres::PostgresDatabase, redis::RedisDatabase},
+        platform::iam::session::store::{memory_store::MemorySessionStore, SharedSessionStore},
+        service::task::manager::TaskManager,
+    };
+
+    /// The store the task listener runs session tasks against, the listener
+    /// lives on its own runtime so it outlives the runtime of each test.
+    static SESSIONS: Lazy<SharedSessionStore> = Lazy::new(|| {
+        let sessions: SharedSessionStore = Arc::new(MemorySessionStore::default());
+        let listener = sessions.clone();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                TaskManager::new(postgres(), listener).listen();
+                std::future::pending::<()>().await;
+            });
+        });
+        sessions
+    });
+
+    /// Task responses go to whoever waits first, tests sending tasks take
+    /// turns.
+    static TASKS: Lazy<Mutex<()>> = Lazy::new(Mutex::default);
+
+    /// Starts the task listener once and waits for the turn of the test,
+    /// returns the session store of the listener.
+    pub async fn tasks() -> (MutexGuard<'static, ()>, SharedSessionStore) {
+        let turn = TASKS.lock().await;
+        (turn, SESSIONS.clone())
+    }
+
+    /// A state without providers whose databases never connect.
+    pub fn state() -> Arc<ArkState> {
+        Arc::new(ArkState {
+            key: Key::generate(),
+            postgres: postgres(),
+            auth: OAuthCollectionAdapter::default(),
+            redis: RedisDatabase {
+                pool: Pool::builder()
+                    .build_unchecked(RedisConnectionManager::new("redis://127.0.0.1/").unwrap()),
+            },
+            sessions: SESSIONS.clone(),
+        })
+    }
+
+    fn postgres() -> PostgresDatabase {
+        let manager =
+            PostgresConnectionManager::new_from_stringlike("host=127.0.0.1 user=ark", NoTls)
+                .unwrap();
+        PostgresDatabase {
+            pool: Pool::builder().build_unchecked(manager),
+        }
+    }
+}
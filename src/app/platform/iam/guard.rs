@@ -0,0 +1,398 @@
+use std::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::app::{
+    platform::{
+        iam::{
+            permission::matcher::PermissionMatcher,
+            role::{cache::RoleCache, hierarchy::RoleHierarchy},
+            session::extractor::AuthenticatedUser,
+            user::{manager::UserManager, model::User},
+        },
+        response::ErrorJsonResponse,
+    },
+    service::cache::LocalizedCache,
+};
+
+/// What a user must hold to get through a guard.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Requirement {
+    /// A permission, held directly or through a role, by its key. Wildcard
+    /// grants (`moderation.*`) count.
+    Permission(String),
+    /// At least one of the roles, by name or id. Roles inheriting from one of
+    /// them count.
+    AnyRole(Vec<String>),
+    /// Every one of the requirements.
+    All(Vec<Requirement>),
+}
+
+impl Requirement {
+    /// Whether the user meets the requirement, roles and permissions are
+    /// resolved through `RoleCache` and `PermissionCache`.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    ///
+    /// # Examples
+    /// ```
+    /// Requirement::Permission(String::from("ban.user")).is_met(&user);
+    /// ```
+    pub fn is_met(&self, user: &User) -> bool {
+        self.is_met_in(user, None)
+    }
+
+    /// Whether the user meets the requirement within an organization, `None`
+    /// standing for outside of any organization where only system roles and
+    /// permissions count.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    /// - `org_id`: the id of the organization.
+    ///
+    /// # Examples
+    /// ```
+    /// Requirement::Permission(String::from("ban.user")).is_met_in(&user, session.metadata.organization_id.as_deref());
+    /// ```
+    pub fn is_met_in(&self, user: &User, org_id: Option<&str>) -> bool {
+        self.is_met_with(user, org_id, &UserManager::permission_matcher_in_org(user, org_id))
+    }
+
+    /// Checks the requirement with the permissions of the user already compiled.
+    fn is_met_with(&self, user: &User, org_id: Option<&str>, permissions: &PermissionMatcher) -> bool {
+        match self {
+            Requirement::Permission(permission_key) => permissions.matches(permission_key),
+            Requirement::AnyRole(roles) => UserManager::roles_in_org(user, org_id)
+                .into_iter()
+                .flat_map(|role| {
+                    let role_id = role.role_id;
+                    let ancestors = RoleHierarchy::ancestors(&role_id);
+                    std::iter::once(role_id).chain(ancestors)
+                })
+                .any(|role_id| {
+                    let role_name = RoleCache::get(&role_id).ok().map(|role| role.role_name);
+                    roles
+                        .iter()
+                        .any(|role| role.eq(&role_id) || role_name.as_ref() == Some(role))
+                }),
+            Requirement::All(requirements) => requirements
+                .iter()
+                .all(|requirement| requirement.is_met_with(user, org_id, permissions)),
+        }
+    }
+}
+
+/// Only lets users holding a permission through.
+///
+/// # Examples
+/// ```
+/// Router::new()
+///     .route("/users/:id/ban", post(ban))
+///     .route_layer(RequirePermission::new("ban.user"));
+/// ```
+#[derive(Clone)]
+pub struct RequirePermission(String);
+
+impl RequirePermission {
+    pub fn new(permission_key: &str) -> Self {
+        Self(String::from(permission_key))
+    }
+}
+
+impl From<RequirePermission> for Requirement {
+    fn from(value: RequirePermission) -> Self {
+        Requirement::Permission(value.0)
+    }
+}
+
+/// Only lets users holding at least one of the roles through.
+///
+/// # Examples
+/// ```
+/// Router::new()
+///     .route("/admin", get(admin))
+///     .route_layer(RequireAnyRole::new(["Admin", "Moderator"]));
+/// ```
+#[derive(Clone)]
+pub struct RequireAnyRole(Vec<String>);
+
+impl RequireAnyRole {
+    pub fn new<const N: usize>(roles: [&str; N]) -> Self {
+        Self(roles.iter().map(|role| String::from(*role)).collect())
+    }
+}
+
+impl From<RequireAnyRole> for Requirement {
+    fn from(value: RequireAnyRole) -> Self {
+        Requirement::AnyRole(value.0)
+    }
+}
+
+/// Only lets users meeting every requirement through.
+///
+/// # Examples
+/// ```
+/// Router::new()
+///     .route("/admin/users/:id/ban", post(ban))
+///     .route_layer(RequireAll::new([
+///         RequireAnyRole::new(["Admin"]).into(),
+///         RequirePermission::new("ban.user").into(),
+///     ]));
+/// ```
+#[derive(Clone)]
+pub struct RequireAll(Vec<Requirement>);
+
+impl RequireAll {
+    pub fn new<const N: usize>(requirements: [Requirement; N]) -> Self {
+        Self(requirements.to_vec())
+    }
+}
+
+impl From<RequireAll> for Requirement {
+    fn from(value: RequireAll) -> Self {
+        Requirement::All(value.0)
+    }
+}
+
+/// Authenticates the request and checks the requirement within the organization
+/// the session is scoped to, if any. The `AuthenticatedUser`
+/// is kept in the extensions of the request so the handler does not validate
+/// the session again.
+///
+/// # Arguments
+/// - `parts`: the parts of the request.
+/// - `state`: the state of the router.
+/// - `requirement`: what the user must hold.
+///
+/// # Examples
+/// ```
+/// authorize(&mut parts, &(), &Requirement::Permission(String::from("ban.user"))).await?;
+/// ```
+async fn authorize<S: Send + Sync>(
+    parts: &mut Parts,
+    state: &S,
+    requirement: &Requirement,
+) -> Result<(), ErrorJsonResponse> {
+    let authenticated = AuthenticatedUser::from_request_parts(parts, state).await?;
+    let org_id = authenticated.session.metadata.organization_id.as_deref();
+    if !requirement.is_met_in(&authenticated.user, org_id) {
+        return Err(ErrorJsonResponse::new(
+            StatusCode::FORBIDDEN,
+            "You do not have permission to do this.",
+        ));
+    }
+    parts.extensions.insert(authenticated);
+    Ok(())
+}
+
+/// A requirement known when compiling, `#[require_permission]` and
+/// `#[require_role]` name it through `PermissionOf` and `RoleOf`.
+pub trait GuardRequirement {
+    fn requirement() -> Requirement;
+}
+
+/// A string spelled out in types one `char` at a time, ended by `End`. The
+/// guard attributes carry their literal this way instead of emitting an item
+/// next to the handler, which is not allowed inside of an `impl` block.
+///
+/// # Examples
+/// ```
+/// type Ban = Chars<'b', Chars<'a', Chars<'n', End>>>;
+/// assert_eq!(Ban::value(), "ban");
+/// ```
+pub trait TypeStr {
+    fn push_to(value: &mut String);
+
+    fn value() -> String {
+        let mut value = String::new();
+        Self::push_to(&mut value);
+        value
+    }
+}
+
+pub struct Chars<const C: char, T: TypeStr>(PhantomData<T>);
+
+pub struct End;
+
+impl TypeStr for End {
+    fn push_to(_: &mut String) {}
+}
+
+impl<const C: char, T: TypeStr> TypeStr for Chars<C, T> {
+    fn push_to(value: &mut String) {
+        value.push(C);
+        T::push_to(value);
+    }
+}
+
+/// The permission `K` spells out, see `RequirePermission`.
+pub struct PermissionOf<K: TypeStr>(PhantomData<K>);
+
+impl<K: TypeStr> GuardRequirement for PermissionOf<K> {
+    fn requirement() -> Requirement {
+        Requirement::Permission(K::value())
+    }
+}
+
+/// The role `K` spells out, by name or id, see `RequireAnyRole`.
+pub struct RoleOf<K: TypeStr>(PhantomData<K>);
+
+impl<K: TypeStr> GuardRequirement for RoleOf<K> {
+    fn requirement() -> Requirement {
+        Requirement::AnyRole(vec![K::value()])
+    }
+}
+
+/// Rejects the request unless the user meets the requirement of `R`, it is
+/// what the guard attributes of `ark-macros` put in front of a handler.
+///
+/// # Examples
+/// ```
+/// #[require_permission("ban.user")]
+/// async fn ban(Path(user_id): Path<String>) -> CustomJsonResponse<String> { ... }
+/// ```
+pub struct Guarded<R: GuardRequirement>(PhantomData<R>);
+
+#[async_trait]
+impl<S: Send + Sync, R: GuardRequirement> FromRequestParts<S> for Guarded<R> {
+    type Rejection = ErrorJsonResponse;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        authorize(parts, state, &R::requirement()).await?;
+        Ok(Self(PhantomData))
+    }
+}
+
+macro_rules! impl_guard_layer {
+    ($($guard:ty),*) => {
+        $(
+            impl<S> Layer<S> for $guard {
+                type Service = GuardService<S>;
+
+                fn layer(&self, inner: S) -> Self::Service {
+                    GuardService {
+                        inner,
+                        requirement: Arc::new(self.clone().into()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_guard_layer!(RequirePermission, RequireAnyRole, RequireAll);
+
+/// Authenticates the request and checks the requirement before handing it to
+/// the route. Requests without a valid session are rejected with a 401, users
+/// not meeting the requirement with a 403.
+#[derive(Clone)]
+pub struct GuardService<S> {
+    inner: S,
+    requirement: Arc<Requirement>,
+}
+
+impl<S> Service<Request> for GuardService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let requirement = self.requirement.clone();
+        // the ready service is the one that is called.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            if let Err(rejection) = authorize(&mut parts, &(), &requirement).await {
+                return Ok(rejection.into_response());
+            }
+            inner.call(Request::from_parts(parts, body)).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_macros::{require_permission, require_role};
+    use axum::{
+        body::Body,
+        extract::{Path, Request},
+        http::StatusCode,
+        routing::{get, post},
+        Router,
+    };
+    use tower_service::Service;
+
+    use super::{Chars, End, GuardRequirement, PermissionOf, Requirement, RoleOf, TypeStr};
+
+    #[require_permission("ban.user")]
+    async fn ban(Path(user_id): Path<String>) -> String {
+        user_id
+    }
+
+    struct AdminRoutes;
+
+    impl AdminRoutes {
+        #[require_role("Admin")]
+        #[require_permission("admin.panel")]
+        async fn panel() -> &'static str {
+            "panel"
+        }
+    }
+
+    fn routes() -> Router {
+        Router::new()
+            .route("/users/:id/ban", post(ban))
+            .route("/admin", get(AdminRoutes::panel))
+    }
+
+    #[test]
+    fn type_str_spells_the_literal() {
+        assert_eq!(End::value(), "");
+        assert_eq!(<Chars<'b', Chars<'a', Chars<'n', End>>>>::value(), "ban");
+        assert_eq!(
+            PermissionOf::<Chars<'a', Chars<'.', Chars<'*', End>>>>::requirement(),
+            Requirement::Permission(String::from("a.*"))
+        );
+        assert_eq!(
+            RoleOf::<Chars<'é', End>>::requirement(),
+            Requirement::AnyRole(vec![String::from("é")])
+        );
+    }
+
+    #[tokio::test]
+    async fn guarded_handlers_reject_anonymous_requests() {
+        let mut router = routes();
+        for (method, uri) in [("POST", "/users/1/ban"), ("GET", "/admin")] {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let response = router.call(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+}
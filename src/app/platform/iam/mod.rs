@@ -1,4 +1,6 @@
+pub mod guard;
+pub mod organization;
 pub mod permission;
 pub mod role;
 pub mod session;
-pub mod user;
\ No newline at end of file
+pub mod user;
@@ -0,0 +1,49 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use once_cell::sync::Lazy;
+
+use crate::app::service::cache::{
+    error::{CacheError, CacheResult},
+    LocalizedCache,
+};
+
+use super::model::Organization;
+
+static ORGANIZATION_CACHE: Lazy<RwLock<HashMap<String, Arc<Organization>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+pub struct OrganizationCache;
+
+impl LocalizedCache<Organization> for OrganizationCache {
+    fn add(item: Organization) {
+        let item_arc = Arc::new(item);
+        let mut cache = ORGANIZATION_CACHE.write().unwrap();
+        cache.insert(item_arc.org_id.clone(), Arc::clone(&item_arc));
+        cache.insert(item_arc.org_name.clone(), Arc::clone(&item_arc));
+    }
+
+    fn single_add(item: Organization) {
+        let item_arc = Arc::new(item);
+        let mut cache = ORGANIZATION_CACHE.write().unwrap();
+        cache.insert(item_arc.org_id.clone(), Arc::clone(&item_arc));
+    }
+
+    fn remove(id: &str) -> CacheResult<bool> {
+        let mut cache = ORGANIZATION_CACHE.write().unwrap();
+        match cache.remove(id) {
+            Some(_) => Ok(true),
+            None => Err(CacheError::ItemNotFound),
+        }
+    }
+
+    fn get(id: &str) -> CacheResult<Organization> {
+        let cache = ORGANIZATION_CACHE.read().unwrap();
+        match cache.get(id) {
+            Some(v) => Ok(v.as_ref().clone()),
+            None => Err(CacheError::ItemNotFound),
+        }
+    }
+}
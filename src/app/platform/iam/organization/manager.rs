@@ -0,0 +1,306 @@
+use crate::app::{
+    platform::iam::user::manager::UserManager,
+    service::task::{
+        error::TaskResult,
+        manager::TaskManager,
+        message::{TaskRequest, TaskStatus, TaskType},
+    },
+};
+
+use super::{
+    model::Organization,
+    task::{
+        OrganizationAddMember, OrganizationCreateTask, OrganizationDeleteTask,
+        OrganizationListMembers, OrganizationPreloadCache, OrganizationReadTask,
+        OrganizationRemoveMember, OrganizationUpdateTask,
+    },
+};
+
+pub struct OrganizationManager;
+
+impl OrganizationManager {
+    /// Create an organization.
+    ///
+    /// # Arguments
+    /// - `organization`: the organization.
+    ///
+    /// # Examples
+    /// ```
+    /// let organization = Organization::builder()
+    ///     .org_name("Acme")
+    ///     .build();
+    /// create_organization(organization);
+    /// ```
+    pub fn create_organization(organization: Organization) -> TaskResult<TaskStatus> {
+        let task_request = Self::create_organization_request(organization);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Composes an organization create request.
+    ///
+    /// # Arguments
+    /// - `organization`: the organization.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::create_organization_request(organization)
+    /// ```
+    fn create_organization_request(organization: Organization) -> TaskRequest {
+        TaskRequest::compose_request(
+            OrganizationCreateTask::from(organization),
+            TaskType::Organization,
+            "organization_create",
+        )
+    }
+
+    /// Delete an organization, its roles, permissions and memberships go away
+    /// with it, the sessions of its members are revoked.
+    ///
+    /// # Arguments
+    /// - `identifier`: Find an organization based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// delete_organization("dd2546c3-e34a-4fcb-9b12-1a96eb6873e3");
+    /// delete_organization("Acme");
+    /// ```
+    pub fn delete_organization(identifier: &str) -> TaskResult<TaskStatus> {
+        let task_request = Self::delete_organization_request(identifier);
+        let members = TaskManager::process_task_with_result::<Vec<String>>(task_request)?;
+        // sessions issued while they were members are revoked.
+        for user_id in members {
+            UserManager::rotate_security_stamp(&user_id)?;
+        }
+        Ok(TaskStatus::Completed)
+    }
+
+    /// Composes an organization delete request.
+    ///
+    /// # Arguments
+    /// - `identifier`: Find an organization based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::delete_organization_request("Acme")
+    /// ```
+    fn delete_organization_request(identifier: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            OrganizationDeleteTask {
+                identifier: String::from(identifier),
+            },
+            TaskType::Organization,
+            "organization_delete",
+        )
+    }
+
+    /// Updates specific field within an organization, only `org_name` can be.
+    ///
+    /// # Arguments
+    /// - `search_by`: Find an organization based on it's identifier.
+    /// - `update_for`: The field that needs to be updated.
+    /// - `value`: The value of the field.
+    ///
+    /// # Examples
+    /// ```
+    /// update_organization("Acme", "org_name", "Acme Corp");
+    /// ```
+    pub fn update_organization(
+        search_by: &str,
+        update_for: &str,
+        value: &str,
+    ) -> TaskResult<TaskStatus> {
+        let task_request = Self::update_organization_request(search_by, update_for, value);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Composes an organization update request.
+    ///
+    /// # Arguments
+    /// - `search_by`: Find an organization based on it's identifier.
+    /// - `update_for`: The field that needs to be updated.
+    /// - `value`: The value of the field.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::update_organization_request("Acme", "org_name", "Acme Corp")
+    /// ```
+    fn update_organization_request(search_by: &str, update_for: &str, value: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            OrganizationUpdateTask {
+                search_by: String::from(search_by),
+                update_for: String::from(update_for),
+                value: String::from(value),
+            },
+            TaskType::Organization,
+            "organization_update",
+        )
+    }
+
+    /// Read a specific organization.
+    ///
+    /// # Arguments
+    /// - `identifier`: Find an organization based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// get_organization("Acme");
+    /// ```
+    pub fn get_organization(identifier: &str) -> TaskResult<Organization> {
+        let task_request = Self::read_organization_request(identifier);
+        TaskManager::process_task_with_result::<Organization>(task_request)
+    }
+
+    /// Composes an organization read request.
+    ///
+    /// # Arguments
+    /// - `identifier`: Find an organization based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::read_organization_request("Acme")
+    /// ```
+    fn read_organization_request(identifier: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            OrganizationReadTask {
+                identifier: String::from(identifier),
+            },
+            TaskType::Organization,
+            "organization_read",
+        )
+    }
+
+    /// Make a user a member of an organization, only members can be granted
+    /// the roles and permissions of an organization or sign into it.
+    ///
+    /// # Arguments
+    /// - `org_identifier`: Find an organization based on it's identifier.
+    /// - `user_id`: the id of the user.
+    ///
+    /// # Examples
+    /// ```
+    /// add_member("Acme", "2f4afce2-ec56-429a-96b1-480c0b20943a");
+    /// ```
+    pub fn add_member(org_identifier: &str, user_id: &str) -> TaskResult<TaskStatus> {
+        let task_request = Self::add_member_request(org_identifier, user_id);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Composes an organization add member request.
+    ///
+    /// # Arguments
+    /// - `org_identifier`: Find an organization based on it's identifier.
+    /// - `user_id`: the id of the user.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::add_member_request("Acme", "2f4afce2-ec56-429a-96b1-480c0b20943a")
+    /// ```
+    fn add_member_request(org_identifier: &str, user_id: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            OrganizationAddMember {
+                org_id: String::from(org_identifier),
+                user_id: String::from(user_id),
+            },
+            TaskType::Organization,
+            "organization_add_member",
+        )
+    }
+
+    /// Remove a user from an organization along with the roles and permissions
+    /// of the organization they were granted, their sessions are revoked.
+    ///
+    /// # Arguments
+    /// - `org_identifier`: Find an organization based on it's identifier.
+    /// - `user_id`: the id of the user.
+    ///
+    /// # Examples
+    /// ```
+    /// remove_member("Acme", "2f4afce2-ec56-429a-96b1-480c0b20943a");
+    /// ```
+    pub fn remove_member(org_identifier: &str, user_id: &str) -> TaskResult<TaskStatus> {
+        let task_request = Self::remove_member_request(org_identifier, user_id);
+        let status = TaskManager::process_task(task_request)?;
+        // sessions issued while the user was a member are revoked.
+        UserManager::rotate_security_stamp(user_id)?;
+        Ok(status)
+    }
+
+    /// Composes an organization remove member request.
+    ///
+    /// # Arguments
+    /// - `org_identifier`: Find an organization based on it's identifier.
+    /// - `user_id`: the id of the user.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::remove_member_request("Acme", "2f4afce2-ec56-429a-96b1-480c0b20943a")
+    /// ```
+    fn remove_member_request(org_identifier: &str, user_id: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            OrganizationRemoveMember {
+                org_id: String::from(org_identifier),
+                user_id: String::from(user_id),
+            },
+            TaskType::Organization,
+            "organization_remove_member",
+        )
+    }
+
+    /// List the ids of the members of an organization, earliest first.
+    ///
+    /// # Arguments
+    /// - `org_identifier`: Find an organization based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// let members = list_members("Acme").unwrap();
+    /// ```
+    pub fn list_members(org_identifier: &str) -> TaskResult<Vec<String>> {
+        let task_request = Self::list_members_request(org_identifier);
+        TaskManager::process_task_with_result::<Vec<String>>(task_request)
+    }
+
+    /// Composes an organization list members request.
+    ///
+    /// # Arguments
+    /// - `org_identifier`: Find an organization based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::list_members_request("Acme")
+    /// ```
+    fn list_members_request(org_identifier: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            OrganizationListMembers {
+                org_id: String::from(org_identifier),
+            },
+            TaskType::Organization,
+            "organization_list_members",
+        )
+    }
+
+    /// Preload organization cache.
+    ///
+    /// # Examples
+    /// ```
+    /// preload_organization_cache();
+    /// ```
+    pub fn preload_organization_cache() -> TaskResult<TaskStatus> {
+        let task_request = Self::preload_organization_request();
+        TaskManager::process_task(task_request)
+    }
+
+    /// Composes an organization preload cache request.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::preload_organization_request()
+    /// ```
+    fn preload_organization_request() -> TaskRequest {
+        TaskRequest::compose_request(
+            OrganizationPreloadCache {},
+            TaskType::Organization,
+            "organization_preload_cache",
+        )
+    }
+}
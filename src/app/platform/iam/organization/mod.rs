@@ -0,0 +1,4 @@
+pub mod cache;
+pub mod manager;
+pub mod model;
+pub mod task;
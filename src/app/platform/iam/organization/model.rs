@@ -0,0 +1,110 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::task::OrganizationCreateTask;
+
+/// A community hosted on the deployment.
+///
+/// Roles and permissions either belong to an organization or to the system,
+/// system ones can be used in every organization, the ones of an organization
+/// only within it.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Organization {
+    pub org_id: String,
+    pub org_name: String,
+    /// When the organization was created (milliseconds).
+    pub created_at: i64,
+}
+
+impl From<OrganizationCreateTask> for Organization {
+    fn from(value: OrganizationCreateTask) -> Self {
+        Self {
+            org_id: value.org_id,
+            org_name: value.org_name,
+            created_at: value.created_at,
+        }
+    }
+}
+
+impl Organization {
+    pub fn new(org_id: &str, org_name: &str, created_at: i64) -> Organization {
+        Self {
+            org_id: String::from(org_id),
+            org_name: String::from(org_name),
+            created_at,
+        }
+    }
+
+    pub fn builder() -> OrganizationBuilder {
+        OrganizationBuilder::new()
+    }
+
+    /// Whether a role or permission owned by `owner` can be used from
+    /// `org_id`, `None` standing for the system on both sides.
+    ///
+    /// # Arguments
+    /// - `owner`: the organization the role or permission belongs to.
+    /// - `org_id`: the organization it is used from.
+    ///
+    /// # Examples
+    /// ```
+    /// Organization::is_visible(None, Some("acme")); // true
+    /// Organization::is_visible(Some("acme"), None); // false
+    /// ```
+    pub fn is_visible(owner: Option<&str>, org_id: Option<&str>) -> bool {
+        owner.is_none() || owner == org_id
+    }
+
+    /// The name a role or permission is cached under, names are only unique
+    /// within an organization so the ones of an organization are prefixed
+    /// with its id.
+    ///
+    /// # Arguments
+    /// - `owner`: the organization the role or permission belongs to.
+    /// - `name`: its name.
+    ///
+    /// # Examples
+    /// ```
+    /// Organization::qualify(None, "Moderator"); // "Moderator"
+    /// Organization::qualify(Some("acme"), "Moderator"); // "acme:Moderator"
+    /// ```
+    pub fn qualify(owner: Option<&str>, name: &str) -> String {
+        match owner {
+            Some(org_id) => format!("{}:{}", org_id, name),
+            None => String::from(name),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct OrganizationBuilder {
+    org_id: String,
+    org_name: String,
+}
+
+impl OrganizationBuilder {
+    pub fn new() -> OrganizationBuilder {
+        OrganizationBuilder {
+            org_id: Uuid::new_v4().to_string(),
+            org_name: String::default(),
+        }
+    }
+
+    pub fn org_name(mut self, org_name: &str) -> OrganizationBuilder {
+        self.org_name = String::from(org_name);
+        self
+    }
+
+    pub fn build(self) -> Organization {
+        Organization {
+            org_id: self.org_id,
+            org_name: self.org_name,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64,
+        }
+    }
+}
@@ -0,0 +1,725 @@
+use axum::async_trait;
+use bb8_postgres::tokio_postgres::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::app::{
+    database::postgres::PostgresDatabase,
+    platform::iam::{
+        permission::cache::PermissionCache,
+        role::cache::RoleCache,
+        user::{manager::UserCacheManager, model::User},
+    },
+    service::{
+        cache::{notify_cache_hit, notify_cache_miss, LocalizedCache},
+        task::{
+            error::TaskError,
+            message::{TaskRequest, TaskResponse, TaskStatus},
+            Task, TaskHandler,
+        },
+    },
+};
+
+use super::{cache::OrganizationCache, model::Organization};
+
+pub struct OrganizationTaskHandler;
+
+#[async_trait]
+impl TaskHandler<PostgresDatabase> for OrganizationTaskHandler {
+    async fn handle(pg: &PostgresDatabase, task_request: TaskRequest) -> TaskResponse {
+        if task_request.task_action.eq("organization_create") {
+            let payload = match TaskRequest::intepret_request_payload::<OrganizationCreateTask>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return OrganizationCreateTask::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("organization_update") {
+            let payload = match TaskRequest::intepret_request_payload::<OrganizationUpdateTask>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return OrganizationUpdateTask::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("organization_delete") {
+            let payload = match TaskRequest::intepret_request_payload::<OrganizationDeleteTask>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return OrganizationDeleteTask::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("organization_read") {
+            let payload = match TaskRequest::intepret_request_payload::<OrganizationReadTask>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return OrganizationReadTask::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("organization_add_member") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<OrganizationAddMember>(&task_request)
+                {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return OrganizationAddMember::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("organization_remove_member") {
+            let payload = match TaskRequest::intepret_request_payload::<OrganizationRemoveMember>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return OrganizationRemoveMember::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("organization_list_members") {
+            let payload = match TaskRequest::intepret_request_payload::<OrganizationListMembers>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return OrganizationListMembers::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("organization_preload_cache") {
+            let payload = match TaskRequest::intepret_request_payload::<OrganizationPreloadCache>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return OrganizationPreloadCache::run(pg, task_request, payload).await;
+        }
+
+        return TaskResponse::throw_failed_response(
+            task_request,
+            vec![TaskError::FailedToFindAction.to_string()],
+        );
+    }
+}
+
+/// Whether a user is a member of an organization.
+///
+/// # Arguments
+/// - `pool`: a connection of the pool.
+/// - `org_id`: the id of the organization.
+/// - `user_id`: the id of the user.
+pub(crate) async fn is_member(pool: &Client, org_id: &str, user_id: &str) -> bool {
+    let stmt = pool
+        .prepare("SELECT 1 FROM iam_organization_member WHERE org_id = $1 AND user_id = $2")
+        .await
+        .unwrap();
+    matches!(
+        pool.query_opt(&stmt, &[&org_id, &user_id]).await,
+        Ok(Some(_))
+    )
+}
+
+/// Takes the organization out of the cached user along with the roles and
+/// permissions it granted them.
+///
+/// # Arguments
+/// - `user`: the cached user.
+/// - `org_id`: the id of the organization.
+fn leave_organization(user: &mut User, org_id: &str) {
+    let outside_role = |role_id: &String| {
+        RoleCache::get(role_id)
+            .ok()
+            .is_none_or(|role| role.org_id.as_deref() != Some(org_id))
+    };
+    let outside_permission = |permission_id: &String| {
+        PermissionCache::get(permission_id)
+            .ok()
+            .is_none_or(|permission| permission.org_id.as_deref() != Some(org_id))
+    };
+    let access = &mut user.access;
+    access
+        .organizations
+        .retain(|organization| organization != org_id);
+    access.role.retain(outside_role);
+    access
+        .role_expires_at
+        .retain(|role_id, _| outside_role(role_id));
+    access
+        .scoped_role
+        .retain(|scoped| outside_role(&scoped.grant_id));
+    access.permission.retain(outside_permission);
+    access.denied_permission.retain(outside_permission);
+    access
+        .permission_expires_at
+        .retain(|permission_id, _| outside_permission(permission_id));
+    access
+        .scoped_permission
+        .retain(|scoped| outside_permission(&scoped.grant_id));
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OrganizationCreateTask {
+    pub org_id: String,
+    pub org_name: String,
+    pub created_at: i64,
+}
+
+impl From<Organization> for OrganizationCreateTask {
+    fn from(organization: Organization) -> Self {
+        Self {
+            org_id: organization.org_id,
+            org_name: organization.org_name,
+            created_at: organization.created_at,
+        }
+    }
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, OrganizationCreateTask> for OrganizationCreateTask {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: OrganizationCreateTask,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        let stmt = pool
+            .prepare("INSERT INTO iam_organizations (id, org_name, created_at) VALUES ($1, $2, $3)")
+            .await
+            .unwrap();
+        match pool
+            .execute(&stmt, &[&param.org_id, &param.org_name, &param.created_at])
+            .await
+        {
+            Ok(_) => {
+                let organization = Organization::from(param);
+                OrganizationCache::add(organization.clone());
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    organization,
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationDuplication.to_string()],
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct OrganizationUpdateTask {
+    pub search_by: String,
+    pub update_for: String,
+    pub value: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, OrganizationUpdateTask> for OrganizationUpdateTask {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: OrganizationUpdateTask,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        if !param.update_for.eq("org_name") {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::FieldNotMutable.to_string()],
+            );
+        }
+
+        let stmt = pool
+            .prepare(
+                "UPDATE iam_organizations
+                SET org_name = $1
+                WHERE id = $2
+                   OR org_name = $2
+                   RETURNING id, org_name, created_at;",
+            )
+            .await
+            .unwrap();
+        match pool
+            .query_opt(&stmt, &[&param.value, &param.search_by])
+            .await
+        {
+            Ok(Some(row)) => {
+                if let Ok(old_organization) = OrganizationCache::get(row.get(0)) {
+                    let _ = OrganizationCache::remove(&old_organization.org_name);
+                }
+                OrganizationCache::add(Organization::new(row.get(0), row.get(1), row.get(2)));
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    param,
+                    Vec::default(),
+                );
+            }
+            Ok(None) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationNotFound.to_string()],
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationDuplication.to_string()],
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct OrganizationDeleteTask {
+    pub identifier: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, OrganizationDeleteTask> for OrganizationDeleteTask {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: OrganizationDeleteTask,
+    ) -> TaskResponse {
+        let mut pool = db.pool.get().await.unwrap();
+        let transaction = pool.transaction().await.unwrap();
+        // the memberships are taken out first, every member is known whether
+        // they are cached or not.
+        let members: Vec<String> = match transaction
+            .query(
+                "DELETE FROM iam_organization_member
+                    WHERE org_id IN (SELECT id FROM iam_organizations WHERE id = $1 OR org_name = $1)
+                    RETURNING user_id",
+                &[&param.identifier],
+            )
+            .await
+        {
+            Ok(rows) => rows.iter().map(|row| row.get(0)).collect(),
+            Err(er) => {
+                println!("{}", er);
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        };
+        let delete_result = transaction
+            .query_opt(
+                "DELETE FROM iam_organizations
+                    WHERE id = $1
+                       OR org_name = $1
+                    RETURNING id, org_name",
+                &[&param.identifier],
+            )
+            .await;
+        match delete_result {
+            Ok(Some(row)) => {
+                if transaction.commit().await.is_err() {
+                    return TaskResponse::throw_failed_response(
+                        request,
+                        vec![TaskError::TaskInternalError.to_string()],
+                    );
+                }
+                let org_id: String = row.get(0);
+                let _ = OrganizationCache::remove(&org_id);
+                let _ = OrganizationCache::remove(row.get(1));
+                // the members are cleared while the roles and permissions of
+                // the organization can still be told apart.
+                for user_id in &members {
+                    if let Ok(mut cached_user) = UserCacheManager::read_user_from_cache(user_id) {
+                        leave_organization(&mut cached_user, &org_id);
+                        UserCacheManager::add_user_to_cache(cached_user).unwrap();
+                    }
+                }
+                // its roles and permissions were deleted along with it.
+                for role in RoleCache::roles() {
+                    if role.org_id.as_deref() == Some(org_id.as_str()) {
+                        let _ = RoleCache::remove(&role.role_id);
+                        let _ = RoleCache::remove(&role.cache_name());
+                    }
+                }
+                for permission in PermissionCache::permissions() {
+                    if permission.org_id.as_deref() == Some(org_id.as_str()) {
+                        let _ = PermissionCache::remove(&permission.permission_id);
+                        let _ = PermissionCache::remove(&permission.cache_name());
+                        let _ = PermissionCache::remove(&permission.cache_key());
+                    }
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    members,
+                    Vec::default(),
+                );
+            }
+            Ok(None) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationNotFound.to_string()],
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct OrganizationReadTask {
+    pub identifier: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, OrganizationReadTask> for OrganizationReadTask {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: OrganizationReadTask,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        match OrganizationCache::get(&param.identifier) {
+            Ok(organization) => {
+                notify_cache_hit(
+                    "OrganizationCache",
+                    "OrganizationReadTask",
+                    &request.task_id,
+                );
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    organization,
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                let stmt = pool
+                    .prepare(
+                        "SELECT id, org_name, created_at FROM iam_organizations WHERE id = $1
+        OR org_name = $1;",
+                    )
+                    .await
+                    .unwrap();
+                match pool.query_one(&stmt, &[&param.identifier]).await {
+                    Ok(row) => {
+                        notify_cache_miss(
+                            "OrganizationCache",
+                            "OrganizationReadTask",
+                            &request.task_id,
+                        );
+                        let organization = Organization::new(row.get(0), row.get(1), row.get(2));
+                        OrganizationCache::add(organization.clone());
+                        return TaskResponse::compose_response(
+                            request,
+                            TaskStatus::Completed,
+                            organization,
+                            Vec::default(),
+                        );
+                    }
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            request,
+                            vec![TaskError::OrganizationNotFound.to_string()],
+                        )
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct OrganizationAddMember {
+    pub org_id: String,
+    pub user_id: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, OrganizationAddMember> for OrganizationAddMember {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: OrganizationAddMember,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        let org_to_id = match OrganizationCache::get(&param.org_id) {
+            Ok(v) => v.org_id,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationNotFound.to_string()],
+                )
+            }
+        };
+        let stmt = pool
+            .prepare("INSERT INTO iam_organization_member (org_id, user_id) VALUES ($1, $2)")
+            .await
+            .unwrap();
+        let cached_user = UserCacheManager::read_user_from_cache(&param.user_id);
+        match pool.execute(&stmt, &[&org_to_id, &param.user_id]).await {
+            Ok(_) => {
+                if let Ok(mut cached_user) = cached_user {
+                    cached_user.access.organizations.push(org_to_id);
+                    UserCacheManager::add_user_to_cache(cached_user).unwrap();
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
+            // the user exists, so it is the membership that already does.
+            Err(_) => {
+                let er = match cached_user {
+                    Ok(_) => TaskError::OrganizationMemberAlreadyExists,
+                    Err(_) => TaskError::UserNotFound,
+                };
+                return TaskResponse::throw_failed_response(request, vec![er.to_string()]);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct OrganizationRemoveMember {
+    pub org_id: String,
+    pub user_id: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, OrganizationRemoveMember> for OrganizationRemoveMember {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: OrganizationRemoveMember,
+    ) -> TaskResponse {
+        let mut pool = db.pool.get().await.unwrap();
+        let org_to_id = match OrganizationCache::get(&param.org_id) {
+            Ok(v) => v.org_id,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationNotFound.to_string()],
+                )
+            }
+        };
+        // the membership and the grants go together or not at all.
+        let transaction = pool.transaction().await.unwrap();
+        let stmt = transaction
+            .prepare("DELETE FROM iam_organization_member WHERE org_id = $1 AND user_id = $2")
+            .await
+            .unwrap();
+        match transaction
+            .execute(&stmt, &[&org_to_id, &param.user_id])
+            .await
+        {
+            Ok(0) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationMemberNotFound.to_string()],
+                );
+            }
+            Ok(_) => {
+                // the roles and permissions of the organization leave with them.
+                for query in [
+                    "DELETE FROM iam_user_role WHERE user_id = $1
+                        AND role_id IN (SELECT id FROM iam_roles WHERE org_id = $2)",
+                    "DELETE FROM iam_user_scoped_role WHERE user_id = $1
+                        AND role_id IN (SELECT id FROM iam_roles WHERE org_id = $2)",
+                    "DELETE FROM iam_user_permission WHERE user_id = $1
+                        AND permission_id IN (SELECT id FROM iam_permissions WHERE org_id = $2)",
+                    "DELETE FROM iam_user_scoped_permission WHERE user_id = $1
+                        AND permission_id IN (SELECT id FROM iam_permissions WHERE org_id = $2)",
+                ] {
+                    let stmt = transaction.prepare(query).await.unwrap();
+                    if let Err(er) = transaction
+                        .execute(&stmt, &[&param.user_id, &org_to_id])
+                        .await
+                    {
+                        println!("{}", er);
+                        return TaskResponse::throw_failed_response(
+                            request,
+                            vec![TaskError::TaskInternalError.to_string()],
+                        );
+                    }
+                }
+                if transaction.commit().await.is_err() {
+                    return TaskResponse::throw_failed_response(
+                        request,
+                        vec![TaskError::TaskInternalError.to_string()],
+                    );
+                }
+                if let Ok(mut cached_user) = UserCacheManager::read_user_from_cache(&param.user_id)
+                {
+                    leave_organization(&mut cached_user, &org_to_id);
+                    UserCacheManager::add_user_to_cache(cached_user).unwrap();
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        }
+    }
+}
+
+/// Reads the ids of the members of an organization.
+///
+/// # Arguments
+/// - `pool`: a connection of the pool.
+/// - `org_id`: the id of the organization.
+async fn read_members(pool: &Client, org_id: &str) -> Vec<String> {
+    let stmt = pool
+        .prepare("SELECT user_id FROM iam_organization_member WHERE org_id = $1 ORDER BY joined_at")
+        .await
+        .unwrap();
+    match pool.query(&stmt, &[&org_id]).await {
+        Ok(members) => members.iter().map(|member| member.get(0)).collect(),
+        Err(er) => {
+            println!("{}", er);
+            Vec::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct OrganizationListMembers {
+    pub org_id: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, OrganizationListMembers> for OrganizationListMembers {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: OrganizationListMembers,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        match OrganizationCache::get(&param.org_id) {
+            Ok(organization) => {
+                let members = read_members(&pool, &organization.org_id).await;
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    members,
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationNotFound.to_string()],
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct OrganizationPreloadCache;
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, OrganizationPreloadCache> for OrganizationPreloadCache {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        _: OrganizationPreloadCache,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        let stmt = pool
+            .prepare("SELECT id, org_name, created_at FROM iam_organizations")
+            .await
+            .unwrap();
+
+        match pool.query(&stmt, &[]).await {
+            Ok(rows) => {
+                let mut amt_items = 0;
+                for row in rows {
+                    OrganizationCache::add(Organization::new(row.get(0), row.get(1), row.get(2)));
+                    amt_items += 1;
+                }
+                println!("[CACHE] cached {} organization(s) cache.", amt_items);
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationFailedToPreload.to_string()],
+                )
+            }
+        }
+    }
+}
@@ -23,11 +23,8 @@ impl LocalizedCache<Permission> for PermissionCache {
         let shared_item = Arc::new(item); // Create a single Arc reference to the item
         let mut cache = PERMISSION_CACHE.write().unwrap();
         cache.insert(shared_item.permission_id.clone(), Arc::clone(&shared_item));
-        cache.insert(
-            shared_item.permission_name.clone(),
-            Arc::clone(&shared_item),
-        );
-        cache.insert(shared_item.permission_key.clone(), Arc::clone(&shared_item));
+        cache.insert(shared_item.cache_name(), Arc::clone(&shared_item));
+        cache.insert(shared_item.cache_key(), Arc::clone(&shared_item));
     }
 
     fn single_add(item: Permission) {
@@ -52,3 +49,15 @@ impl LocalizedCache<Permission> for PermissionCache {
         }
     }
 }
+
+impl PermissionCache {
+    /// Every cached permission, once each.
+    pub fn permissions() -> Vec<Permission> {
+        let cache = PERMISSION_CACHE.read().unwrap();
+        cache
+            .iter()
+            .filter(|(key, permission)| key.as_str().eq(&permission.permission_id))
+            .map(|(_, permission)| permission.as_ref().clone())
+            .collect()
+    }
+}
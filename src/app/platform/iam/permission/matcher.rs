@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use super::validation::PERMISSION_WILDCARD;
+
+/// Matches permission keys against a set of granted keys, treating dotted
+/// keys as a hierarchy.
+///
+/// - `moderation.ban` is granted by `moderation.ban` only.
+/// - `moderation.*` grants every key below `moderation` (`moderation.ban`,
+///   `moderation.ban.user`) but not `moderation` itself.
+/// - `*` grants every key.
+///
+/// Denials follow the same rules and override grants, a key matched by a
+/// denial is never granted.
+///
+/// Grants and denials are kept in tries of their segments, a check walks the
+/// segments of the key once instead of going through every grant.
+///
+/// # Examples
+/// ```
+/// let mut matcher = PermissionMatcher::new(["moderation.*", "profile.edit"]);
+/// matcher.deny("moderation.ban.*");
+/// matcher.matches("moderation.kick"); // true
+/// matcher.matches("moderation.ban.user"); // false
+/// matcher.matches("profile"); // false
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PermissionMatcher {
+    root: PermissionNode,
+    denied: PermissionNode,
+}
+
+#[derive(Clone, Debug, Default)]
+struct PermissionNode {
+    children: HashMap<String, PermissionNode>,
+    /// A key ending here was granted.
+    granted: bool,
+    /// Everything below was granted through a wildcard.
+    wildcard: bool,
+}
+
+impl PermissionMatcher {
+    pub fn new<I, K>(grants: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        let mut matcher = Self::default();
+        for grant in grants {
+            matcher.grant(grant.as_ref());
+        }
+        matcher
+    }
+
+    /// Adds a granted key, wildcards included.
+    ///
+    /// # Arguments
+    /// - `permission_key`: the granted key ex: `moderation.*`.
+    pub fn grant(&mut self, permission_key: &str) {
+        self.root.insert(permission_key);
+    }
+
+    /// Adds a denied key, wildcards included, it overrides every grant.
+    ///
+    /// # Arguments
+    /// - `permission_key`: the denied key ex: `chat.send`.
+    pub fn deny(&mut self, permission_key: &str) {
+        self.denied.insert(permission_key);
+    }
+
+    /// Whether a key is granted, exactly or through a wildcard, and not denied.
+    ///
+    /// # Arguments
+    /// - `permission_key`: the key to check ex: `moderation.ban.user`.
+    pub fn matches(&self, permission_key: &str) -> bool {
+        !self.is_denied(permission_key) && self.root.contains(permission_key)
+    }
+
+    /// Whether a key is denied, exactly or through a wildcard.
+    ///
+    /// # Arguments
+    /// - `permission_key`: the key to check ex: `chat.send`.
+    pub fn is_denied(&self, permission_key: &str) -> bool {
+        self.denied.contains(permission_key)
+    }
+}
+
+impl PermissionNode {
+    fn insert(&mut self, permission_key: &str) {
+        let mut node = self;
+        for segment in permission_key.split('.') {
+            if segment.eq(PERMISSION_WILDCARD) {
+                node.wildcard = true;
+                return;
+            }
+            node = node.children.entry(String::from(segment)).or_default();
+        }
+        node.granted = true;
+    }
+
+    fn contains(&self, permission_key: &str) -> bool {
+        let mut node = self;
+        for segment in permission_key.split('.') {
+            if node.wildcard {
+                return true;
+            }
+            node = match node.children.get(segment) {
+                Some(child) => child,
+                None => return false,
+            };
+        }
+        node.granted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PermissionMatcher;
+    use crate::app::platform::iam::permission::validation::PermissionValidator;
+
+    #[test]
+    fn wildcard_alone_grants_every_key() {
+        let matcher = PermissionMatcher::new(["*"]);
+        assert!(matcher.matches("moderation"));
+        assert!(matcher.matches("moderation.ban.user"));
+    }
+
+    #[test]
+    fn exact_key_grants_only_itself() {
+        let matcher = PermissionMatcher::new(["moderation.ban"]);
+        assert!(matcher.matches("moderation.ban"));
+        assert!(!matcher.matches("moderation"));
+        assert!(!matcher.matches("moderation.ban.user"));
+        assert!(!matcher.matches("moderation.kick"));
+    }
+
+    #[test]
+    fn wildcard_grants_keys_below_but_not_the_prefix() {
+        let matcher = PermissionMatcher::new(["moderation.*"]);
+        assert!(matcher.matches("moderation.ban"));
+        assert!(matcher.matches("moderation.ban.user"));
+        assert!(!matcher.matches("moderation"));
+        assert!(!matcher.matches("moderationx.ban"));
+    }
+
+    #[test]
+    fn denied_wildcard_overrides_grants() {
+        let mut matcher = PermissionMatcher::new(["*", "moderation.ban.user"]);
+        matcher.deny("moderation.ban.*");
+        assert!(matcher.matches("moderation.kick"));
+        assert!(matcher.matches("moderation.ban"));
+        assert!(!matcher.matches("moderation.ban.user"));
+        assert!(matcher.is_denied("moderation.ban.user"));
+    }
+
+    #[test]
+    fn denied_key_overrides_wildcard_grant() {
+        let mut matcher = PermissionMatcher::new(["chat.*"]);
+        matcher.deny("chat.send");
+        assert!(!matcher.matches("chat.send"));
+        assert!(matcher.matches("chat.read"));
+    }
+
+    #[test]
+    fn wildcard_is_only_valid_as_the_last_segment() {
+        assert!(PermissionValidator::is_key_valid("*"));
+        assert!(PermissionValidator::is_key_valid("moderation.*"));
+        assert!(PermissionValidator::is_key_valid("moderation.ban.user"));
+        assert!(!PermissionValidator::is_key_valid("moderation.*.user"));
+        assert!(!PermissionValidator::is_key_valid("*.ban"));
+        assert!(!PermissionValidator::is_key_valid("moderation..ban"));
+        assert!(!PermissionValidator::is_key_valid(""));
+    }
+}
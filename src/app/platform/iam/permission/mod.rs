@@ -1,7 +1,9 @@
 pub mod cache;
 pub mod manager;
+pub mod matcher;
 pub mod model;
 pub mod task;
+pub mod validation;
 
 
 
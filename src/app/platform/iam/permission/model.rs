@@ -1,13 +1,21 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::task::PermissionCreateTask;
+use crate::app::{
+    platform::iam::organization::model::Organization,
+    service::validation::{error::ValidationResult, Validator},
+};
+
+use super::{task::PermissionCreateTask, validation::PermissionValidator};
 
 #[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Permission {
     pub permission_id: String,
     pub permission_name: String,
     pub permission_key: String,
+    /// The organization the permission belongs to, `None` for a system permission.
+    #[serde(default)]
+    pub org_id: Option<String>,
 }
 
 impl From<PermissionCreateTask> for Permission {
@@ -16,6 +24,7 @@ impl From<PermissionCreateTask> for Permission {
             permission_id: value.permission_id,
             permission_name: value.permission_name,
             permission_key: value.permission_key,
+            org_id: value.org_id,
         }
     }
 }
@@ -26,12 +35,25 @@ impl Permission {
             permission_id: String::from(permission_id),
             permission_name: String::from(permission_name),
             permission_key: String::from(permission_key),
+            org_id: None,
         }
     }
 
     pub fn builder() -> PermissionBuilder {
         PermissionBuilder::new()
     }
+
+    /// The name the permission is cached under, `<org_id>:<permission_name>`
+    /// for the permission of an organization.
+    pub fn cache_name(&self) -> String {
+        Organization::qualify(self.org_id.as_deref(), &self.permission_name)
+    }
+
+    /// The key the permission is cached under, `<org_id>:<permission_key>`
+    /// for the permission of an organization.
+    pub fn cache_key(&self) -> String {
+        Organization::qualify(self.org_id.as_deref(), &self.permission_key)
+    }
 }
 
 #[derive(Default)]
@@ -39,6 +61,7 @@ pub struct PermissionBuilder {
     permission_id: String,
     permission_name: String,
     permission_key: String,
+    org_id: Option<String>,
 }
 
 impl PermissionBuilder {
@@ -47,6 +70,7 @@ impl PermissionBuilder {
             permission_id: Uuid::new_v4().to_string(),
             permission_name: String::default(),
             permission_key: String::default(),
+            org_id: None,
         }
     }
 
@@ -60,11 +84,25 @@ impl PermissionBuilder {
         self
     }
 
+    /// Makes the permission belong to an organization, its name and key only
+    /// have to be unique within it.
+    pub fn organization(mut self, org_id: &str) -> PermissionBuilder {
+        self.org_id = Some(String::from(org_id));
+        self
+    }
+
+    /// Runs the builder result through the validator ensuring
+    /// the key is well formed.
+    pub fn validate_and_build(self) -> ValidationResult<Permission> {
+        PermissionValidator::validate(self.build())
+    }
+
     pub fn build(self) -> Permission {
         Permission {
             permission_id: self.permission_id,
             permission_name: self.permission_name,
             permission_key: self.permission_key,
+            org_id: self.org_id,
         }
     }
 }
@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::app::{
     database::postgres::PostgresDatabase,
+    platform::iam::organization::cache::OrganizationCache,
     service::{
         cache::{notify_cache_hit, notify_cache_miss, LocalizedCache},
         task::{
@@ -13,7 +14,7 @@ use crate::app::{
     },
 };
 
-use super::{cache::PermissionCache, model::Permission};
+use super::{cache::PermissionCache, model::Permission, validation::PermissionValidator};
 
 pub struct PermissionTaskHandler;
 
@@ -122,6 +123,8 @@ pub(super) struct PermissionCreateTask {
     pub permission_id: String,
     pub permission_name: String,
     pub permission_key: String,
+    #[serde(default)]
+    pub org_id: Option<String>,
 }
 
 impl From<Permission> for PermissionCreateTask {
@@ -130,6 +133,7 @@ impl From<Permission> for PermissionCreateTask {
             permission_id: perm.permission_id,
             permission_name: perm.permission_name,
             permission_key: perm.permission_key,
+            org_id: perm.org_id,
         }
     }
 }
@@ -141,10 +145,24 @@ impl Task<PostgresDatabase, TaskRequest, PermissionCreateTask> for PermissionCre
         request: TaskRequest,
         param: PermissionCreateTask,
     ) -> TaskResponse {
+        if !PermissionValidator::is_key_valid(&param.permission_key) {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::PermissionKeyInvalid.to_string()],
+            );
+        }
+        if let Some(org_id) = &param.org_id {
+            if OrganizationCache::get(org_id).is_err() {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationNotFound.to_string()],
+                );
+            }
+        }
         let pool = db.pool.get().await.unwrap();
         let stmt = pool
             .prepare(
-                "INSERT INTO iam_permissions (id, permission_name, permission_key) VALUES ($1, $2, $3)",
+                "INSERT INTO iam_permissions (id, permission_name, permission_key, org_id) VALUES ($1, $2, $3, $4)",
             )
             .await
             .unwrap();
@@ -155,16 +173,13 @@ impl Task<PostgresDatabase, TaskRequest, PermissionCreateTask> for PermissionCre
                     &param.permission_id,
                     &param.permission_name,
                     &param.permission_key,
+                    &param.org_id,
                 ],
             )
             .await
         {
             Ok(_) => {
-                PermissionCache::add(Permission::new(
-                    &param.permission_id,
-                    &param.permission_name,
-                    &param.permission_key,
-                ));
+                PermissionCache::add(Permission::from(param.clone()));
                 return TaskResponse::compose_response(
                     request,
                     TaskStatus::Completed,
@@ -210,23 +225,37 @@ impl Task<PostgresDatabase, TaskRequest, PermissionDeleteTask> for PermissionDel
         param: PermissionDeleteTask,
     ) -> TaskResponse {
         let pool = db.pool.get().await.unwrap();
+        let cached_permission = PermissionCache::get(&param.identifier).ok();
+        // names and keys are only unique within an organization, the permissions of one are found by id.
+        let identifier = cached_permission
+            .as_ref()
+            .map(|permission| permission.permission_id.clone())
+            .unwrap_or(param.identifier.clone());
         let stmt = pool
             .prepare(
                 "DELETE FROM iam_permissions
                 WHERE id = $1
-                   OR permission_name = $1
-                   OR permission_key = $1",
+                   OR ((permission_name = $1 OR permission_key = $1) AND org_id IS NULL)",
             )
             .await
             .unwrap();
-        match pool.execute(&stmt, &[&param.identifier]).await {
+        match pool.execute(&stmt, &[&identifier]).await {
             Ok(v) => {
                 if v != 0 {
                     // get all roles that have this permission then update the cache accordingly.
                     // might need to convert this into a hashmap
                     // RoleCache::update(search_by, update_for, value);
                     // or re-preload the cache?
-                    PermissionCache::remove(&param.identifier).unwrap();
+                    match cached_permission {
+                        Some(permission) => {
+                            let _ = PermissionCache::remove(&permission.permission_id);
+                            let _ = PermissionCache::remove(&permission.cache_name());
+                            let _ = PermissionCache::remove(&permission.cache_key());
+                        }
+                        None => {
+                            let _ = PermissionCache::remove(&param.identifier);
+                        }
+                    }
                     return TaskResponse::compose_response(
                         request,
                         TaskStatus::Completed,
@@ -285,20 +314,26 @@ impl Task<PostgresDatabase, TaskRequest, PermissionUpdateTask> for PermissionUpd
         param: PermissionUpdateTask,
     ) -> TaskResponse {
         let pool = db.pool.get().await.unwrap();
-        if param.update_for.eq_ignore_ascii_case("id") {
+        if param.update_for.eq_ignore_ascii_case("id") || param.update_for.eq_ignore_ascii_case("org_id") {
             return TaskResponse::throw_failed_response(
                 request,
                 vec![TaskError::FieldNotMutable.to_string()],
             );
         }
+        if param.update_for.eq("permission_key") && !PermissionValidator::is_key_valid(&param.value)
+        {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::PermissionKeyInvalid.to_string()],
+            );
+        }
         let stmt = match pool
             .prepare(
                 format!(
                     "UPDATE iam_permissions
                     SET {} = $1
                     WHERE id = $2
-                       OR permission_name = $2
-                       OR permission_key = $2
+                       OR ((permission_name = $2 OR permission_key = $2) AND org_id IS NULL)
                     RETURNING *;",
                     param.update_for
                 )
@@ -315,21 +350,27 @@ impl Task<PostgresDatabase, TaskRequest, PermissionUpdateTask> for PermissionUpd
             }
         };
 
+        let search_by = PermissionCache::get(&param.search_by)
+            .map(|permission| permission.permission_id)
+            .unwrap_or(param.search_by.clone());
         match pool
-            .query_one(&stmt, &[&param.value, &param.search_by])
+            .query_one(&stmt, &[&param.value, &search_by])
             .await
         {
             Ok(v) => {
-                if v.len() != 0 {
+                if !v.is_empty() {
                     let old_perm = PermissionCache::get(v.get(0)).unwrap();
                     // id should automatically get replaced...
                     if param.update_for.eq("permission_name") {
-                        PermissionCache::remove(&old_perm.permission_name).unwrap();
+                        PermissionCache::remove(&old_perm.cache_name()).unwrap();
                     }
                     if param.update_for.eq("permission_key") {
-                        PermissionCache::remove(&old_perm.permission_key).unwrap();
+                        PermissionCache::remove(&old_perm.cache_key()).unwrap();
                     }
-                    PermissionCache::add(Permission::new(v.get(0), v.get(1), v.get(2)));
+                    PermissionCache::add(Permission {
+                        org_id: v.get(3),
+                        ..Permission::new(v.get(0), v.get(1), v.get(2))
+                    });
                     return TaskResponse::compose_response(
                         request,
                         TaskStatus::Completed,
@@ -380,8 +421,7 @@ impl Task<PostgresDatabase, TaskRequest, PermissionReadTask> for PermissionReadT
                 let stmt = pool
                     .prepare(
                         "SELECT * FROM iam_permissions WHERE id = $1
-        OR permission_name = $1
-        OR permission_key = $1",
+        OR ((permission_name = $1 OR permission_key = $1) AND org_id IS NULL)",
                     )
                     .await
                     .unwrap();
@@ -392,7 +432,10 @@ impl Task<PostgresDatabase, TaskRequest, PermissionReadTask> for PermissionReadT
                             "PermissionReadTask",
                             &request.task_id,
                         );
-                        let permission = Permission::new(row.get(0), row.get(1), row.get(2));
+                        let permission = Permission {
+                            org_id: row.get(3),
+                            ..Permission::new(row.get(0), row.get(1), row.get(2))
+                        };
                         PermissionCache::add(permission.clone());
                         return TaskResponse::compose_response(
                             request,
@@ -429,7 +472,10 @@ impl Task<PostgresDatabase, TaskRequest, PermissionPreloadCache> for PermissionP
             Ok(rows) => {
                 let mut amt_items = 0;
                 for row in rows {
-                    PermissionCache::add(Permission::new(row.get(0), row.get(1), row.get(2)));
+                    PermissionCache::add(Permission {
+                        org_id: row.get(3),
+                        ..Permission::new(row.get(0), row.get(1), row.get(2))
+                    });
                     amt_items += 1;
                 }
                 println!("[CACHE] cached {} permission(s) cache.", amt_items);
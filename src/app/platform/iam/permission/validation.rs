@@ -0,0 +1,51 @@
+use crate::app::service::validation::{
+    error::{ValidationError, ValidationResult},
+    Validator,
+};
+
+use super::model::Permission;
+
+/// A wildcard, only ever the last segment of a key.
+pub static PERMISSION_WILDCARD: &str = "*";
+
+pub struct PermissionValidator;
+
+impl PermissionValidator {
+    /// Whether a permission key is well formed.
+    ///
+    /// Keys are segments of ascii letters, digits, `_` or `-` separated by a
+    /// single dot, ex: `moderation.ban.user`. The last segment can be the
+    /// wildcard `*`, granting every key below it (`moderation.*`), `*` alone
+    /// grants every key.
+    ///
+    /// # Arguments
+    /// - `permission_key`: the key to check.
+    ///
+    /// # Examples
+    /// ```
+    /// PermissionValidator::is_key_valid("moderation.*"); // true
+    /// PermissionValidator::is_key_valid("moderation.*.user"); // false
+    /// ```
+    pub fn is_key_valid(permission_key: &str) -> bool {
+        let segments: Vec<&str> = permission_key.split('.').collect();
+        segments.iter().enumerate().all(|(index, segment)| {
+            (segment.eq(&PERMISSION_WILDCARD) && index == segments.len() - 1)
+                || (!segment.is_empty()
+                    && segment
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        })
+    }
+}
+
+impl Validator<Permission> for PermissionValidator {
+    fn validate(permission: Permission) -> ValidationResult<Permission> {
+        if permission.permission_name.trim().is_empty() {
+            return Err(ValidationError::ValidationFieldFailure);
+        }
+        if !Self::is_key_valid(&permission.permission_key) {
+            return Err(ValidationError::PermissionKeyInvalid);
+        }
+        Ok(permission)
+    }
+}
@@ -22,7 +22,7 @@ impl LocalizedCache<Role> for RoleCache {
         let item_arc = Arc::new(item);
         let mut cache = ROLE_CACHE.write().unwrap();
         cache.insert(item_arc.role_id.clone(), Arc::clone(&item_arc));
-        cache.insert(item_arc.role_name.clone(), Arc::clone(&item_arc));
+        cache.insert(item_arc.cache_name(), Arc::clone(&item_arc));
     }
 
     fn single_add(item: Role) {
@@ -48,3 +48,15 @@ impl LocalizedCache<Role> for RoleCache {
         }
     }
 }
+
+impl RoleCache {
+    /// Every cached role, once each.
+    pub fn roles() -> Vec<Role> {
+        let cache = ROLE_CACHE.read().unwrap();
+        cache
+            .iter()
+            .filter(|(key, role)| key.as_str().eq(&role.role_id))
+            .map(|(_, role)| role.as_ref().clone())
+            .collect()
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::app::service::cache::LocalizedCache;
+
+use super::{cache::RoleCache, model::Role};
+
+/// Resolves the inheritance between roles out of `RoleCache`, a role holds
+/// the permissions and the denials of its parents, of their parents and so on.
+///
+/// # Examples
+/// ```
+/// // Admin -> Moderator -> Member
+/// RoleHierarchy::ancestors("Admin"); // [Moderator, Member]
+/// ```
+pub struct RoleHierarchy;
+
+impl RoleHierarchy {
+    /// The ids of every role a role inherits from, nearest first.
+    ///
+    /// # Arguments
+    /// - `role_id`: the id of the role.
+    ///
+    /// # Examples
+    /// ```
+    /// let ancestors = RoleHierarchy::ancestors("dd2546c3-e34a-4fcb-9b12-1a96eb6873e3");
+    /// ```
+    pub fn ancestors(role_id: &str) -> Vec<String> {
+        let mut ancestors = Vec::new();
+        let mut visited = HashSet::from([String::from(role_id)]);
+        let mut queue = VecDeque::from([String::from(role_id)]);
+        while let Some(current) = queue.pop_front() {
+            let role = match RoleCache::get(&current) {
+                Ok(role) => role,
+                Err(_) => continue,
+            };
+            for parent_id in role.parent_roles {
+                if visited.insert(parent_id.clone()) {
+                    ancestors.push(parent_id.clone());
+                    queue.push_back(parent_id);
+                }
+            }
+        }
+        ancestors
+    }
+
+    /// The ids of every role inheriting from a role, directly or not.
+    ///
+    /// # Arguments
+    /// - `role_id`: the id of the role.
+    ///
+    /// # Examples
+    /// ```
+    /// // Admin -> Moderator -> Member
+    /// RoleHierarchy::descendants(member_id); // [Admin, Moderator] in no particular order
+    /// ```
+    pub fn descendants(role_id: &str) -> Vec<String> {
+        RoleCache::roles()
+            .into_iter()
+            .filter(|role| {
+                Self::ancestors(&role.role_id)
+                    .iter()
+                    .any(|ancestor| ancestor.eq(role_id))
+            })
+            .map(|role| role.role_id)
+            .collect()
+    }
+
+    /// Whether making `parent_id` a parent of `role_id` would have the role
+    /// inherit from itself.
+    ///
+    /// # Arguments
+    /// - `role_id`: the id of the inheriting role.
+    /// - `parent_id`: the id of the role inherited from.
+    ///
+    /// # Examples
+    /// ```
+    /// // Admin -> Moderator -> Member
+    /// RoleHierarchy::creates_cycle(member_id, admin_id); // true
+    /// ```
+    pub fn creates_cycle(role_id: &str, parent_id: &str) -> bool {
+        role_id.eq(parent_id)
+            || Self::ancestors(parent_id)
+                .iter()
+                .any(|ancestor| ancestor.eq(role_id))
+    }
+
+    /// The ids of the permissions a role holds through its ancestors and not
+    /// directly.
+    ///
+    /// # Arguments
+    /// - `role`: the role.
+    pub fn inherited_permissions(role: &Role) -> Vec<String> {
+        Self::inherited(role, |role| role.role_permissions)
+    }
+
+    /// The ids of the permissions a role has denied through its ancestors and
+    /// not directly.
+    ///
+    /// # Arguments
+    /// - `role`: the role.
+    pub fn inherited_denied_permissions(role: &Role) -> Vec<String> {
+        Self::inherited(role, |role| role.denied_permissions)
+    }
+
+    /// Collects the links `links` picks out of the ancestors of a role that
+    /// the role does not hold itself.
+    fn inherited(role: &Role, links: fn(Role) -> Vec<String>) -> Vec<String> {
+        let mut seen: HashSet<String> = links(role.clone()).into_iter().collect();
+        Self::ancestors(&role.role_id)
+            .iter()
+            .filter_map(|ancestor| RoleCache::get(ancestor).ok())
+            .flat_map(links)
+            .filter(|permission_id| seen.insert(permission_id.clone()))
+            .collect()
+    }
+
+    /// Flattens the permissions of a role and of every role inheriting from
+    /// it again, run whenever a link or a permission of the role changed.
+    ///
+    /// # Arguments
+    /// - `role_id`: the id of the role that changed.
+    ///
+    /// # Examples
+    /// ```
+    /// RoleHierarchy::refresh("dd2546c3-e34a-4fcb-9b12-1a96eb6873e3");
+    /// ```
+    pub fn refresh(role_id: &str) {
+        for role in RoleCache::roles() {
+            if role.role_id.eq(role_id)
+                || Self::ancestors(&role.role_id)
+                    .iter()
+                    .any(|ancestor| ancestor.eq(role_id))
+            {
+                Self::flatten(role);
+            }
+        }
+    }
+
+    /// Flattens the permissions of every cached role.
+    pub fn refresh_all() {
+        for role in RoleCache::roles() {
+            Self::flatten(role);
+        }
+    }
+
+    /// Unlinks a deleted role from the roles inheriting from it.
+    ///
+    /// # Arguments
+    /// - `role_id`: the id of the deleted role.
+    pub fn detach(role_id: &str) {
+        for mut role in RoleCache::roles() {
+            if role
+                .parent_roles
+                .iter()
+                .any(|parent_id| parent_id.eq(role_id))
+            {
+                role.parent_roles.retain(|parent_id| parent_id.ne(role_id));
+                let child_id = role.role_id.clone();
+                RoleCache::add(role);
+                Self::refresh(&child_id);
+            }
+        }
+    }
+
+    fn flatten(role: Role) {
+        RoleCache::add(Role {
+            inherited_permissions: Self::inherited_permissions(&role),
+            inherited_denied_permissions: Self::inherited_denied_permissions(&role),
+            ..role
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::RoleHierarchy;
+    use crate::app::{
+        platform::iam::role::{cache::RoleCache, model::Role},
+        service::cache::LocalizedCache,
+    };
+
+    /// Caches a role under a fresh id, the cache is shared between tests.
+    fn cache_role(parents: &[&str], permissions: &[&str]) -> String {
+        let role_id = Uuid::new_v4().to_string();
+        let mut role = Role::new(
+            &role_id,
+            &role_id,
+            permissions.iter().map(|p| String::from(*p)).collect(),
+        );
+        role.parent_roles = parents.iter().map(|p| String::from(*p)).collect();
+        RoleCache::add(role);
+        role_id
+    }
+
+    fn set_parents(role_id: &str, parents: &[&str]) {
+        let mut role = RoleCache::get(role_id).unwrap();
+        role.parent_roles = parents.iter().map(|p| String::from(*p)).collect();
+        RoleCache::add(role);
+    }
+
+    #[test]
+    fn role_cannot_parent_itself() {
+        let role = cache_role(&[], &[]);
+        assert!(RoleHierarchy::creates_cycle(&role, &role));
+    }
+
+    #[test]
+    fn indirect_cycle_is_detected() {
+        // top -> middle -> bottom
+        let top = cache_role(&[], &[]);
+        let middle = cache_role(&[&top], &[]);
+        let bottom = cache_role(&[&middle], &[]);
+        assert!(RoleHierarchy::creates_cycle(&top, &bottom));
+        assert!(RoleHierarchy::creates_cycle(&middle, &bottom));
+        assert!(!RoleHierarchy::creates_cycle(&bottom, &top));
+    }
+
+    #[test]
+    fn ancestors_stop_on_a_cached_cycle() {
+        let first = cache_role(&[], &[]);
+        let second = cache_role(&[&first], &[]);
+        set_parents(&first, &[&second]);
+        assert_eq!(RoleHierarchy::ancestors(&first), vec![second.clone()]);
+        assert_eq!(RoleHierarchy::ancestors(&second), vec![first]);
+    }
+
+    #[test]
+    fn diamond_inherits_shared_ancestor_once() {
+        // root -> left, right -> leaf
+        let root = cache_role(&[], &["root-permission"]);
+        let left = cache_role(&[&root], &["left-permission"]);
+        let right = cache_role(&[&root], &["right-permission", "root-permission"]);
+        let leaf = cache_role(&[&left, &right], &[]);
+
+        let ancestors = RoleHierarchy::ancestors(&leaf);
+        assert_eq!(ancestors, vec![left, right, root.clone()]);
+        assert!(!RoleHierarchy::creates_cycle(&leaf, &root));
+
+        let mut inherited = RoleHierarchy::inherited_permissions(&RoleCache::get(&leaf).unwrap());
+        inherited.sort();
+        assert_eq!(
+            inherited,
+            vec!["left-permission", "right-permission", "root-permission"]
+        );
+    }
+
+    #[test]
+    fn detach_recomputes_inherited_permissions() {
+        // parent -> child -> grandchild
+        let parent = cache_role(&[], &["parent-permission"]);
+        let child = cache_role(&[&parent], &["child-permission"]);
+        let grandchild = cache_role(&[&child], &[]);
+        RoleHierarchy::refresh(&parent);
+        assert_eq!(
+            RoleCache::get(&grandchild)
+                .unwrap()
+                .inherited_permissions
+                .len(),
+            2
+        );
+
+        RoleCache::remove(&parent).unwrap();
+        RoleHierarchy::detach(&parent);
+
+        let child = RoleCache::get(&child).unwrap();
+        assert!(child.parent_roles.is_empty());
+        assert!(child.inherited_permissions.is_empty());
+        assert_eq!(
+            RoleCache::get(&grandchild).unwrap().inherited_permissions,
+            vec!["child-permission"]
+        );
+    }
+}
@@ -1,12 +1,20 @@
-use crate::app::service::task::{
-    error::TaskResult,
-    manager::TaskManager,
-    message::{TaskRequest, TaskStatus, TaskType},
+use crate::app::{
+    platform::iam::user::manager::UserManager,
+    service::{
+        cache::LocalizedCache,
+        task::{
+            error::{TaskError, TaskResult},
+            manager::TaskManager,
+            message::{TaskRequest, TaskStatus, TaskType},
+        },
+    },
 };
 
 use super::{
-    model::Role,
-    task::{RoleCreateTask, RoleDeleteTask, RolePreloadCache, RoleReadTask, RoleUpdateTask, RolePermissionLinkToRole, RolePermissionDeleteLinkToRole},
+    cache::RoleCache,
+    hierarchy::RoleHierarchy,
+    model::{Role, SessionPolicy},
+    task::{RoleCreateTask, RoleDeleteTask, RolePreloadCache, RoleReadTask, RoleUpdateTask, RolePermissionLinkToRole, RolePermissionDeleteLinkToRole, RoleSetSessionPolicy, RoleSetParent, RoleRemoveParent, RolePermissionDenyOnRole, RoleListMembers},
 };
 
 pub struct RoleManager;
@@ -135,7 +143,8 @@ impl RoleManager {
         TaskManager::process_task_with_result::<Role>(request)
     }
 
-    /// Add a permission to a role
+    /// Add a permission to a role, a permission denied on the role is granted
+    /// instead.
     ///
     /// # Arguments
     /// - `role_id`: Find a role based on it's identifier.
@@ -183,7 +192,10 @@ impl RoleManager {
     /// ```
     pub fn delete_permission_from_role(role_identifier: &str, permission_identifier: &str) -> TaskResult<TaskStatus> {
         let request = Self::remove_role_add_permission_request(role_identifier, permission_identifier);
-        TaskManager::process_task(request)
+        let status = TaskManager::process_task(request)?;
+        // sessions issued while the members held the permission are revoked.
+        Self::rotate_members_security_stamp(role_identifier)?;
+        Ok(status)
     }
 
     /// Composes an remove role permission request.
@@ -207,6 +219,166 @@ impl RoleManager {
         )
     }
 
+    /// Denies a permission to the members of a role, it overrides whatever
+    /// grants it, other roles and direct grants included. Roles inheriting from
+    /// the role are denied it too. `delete_permission_from_role` lifts the denial.
+    ///
+    /// # Arguments
+    /// - `role_identifier`: Find a role based on it's identifier.
+    /// - `permission_identifier`: Find a permission based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// deny_permission_on_role("Muted", "chat.send");
+    /// ```
+    pub fn deny_permission_on_role(role_identifier: &str, permission_identifier: &str) -> TaskResult<TaskStatus> {
+        let request = Self::deny_permission_on_role_request(role_identifier, permission_identifier);
+        let status = TaskManager::process_task(request)?;
+        Self::rotate_members_security_stamp(role_identifier)?;
+        Ok(status)
+    }
+
+    /// Composes a deny role permission request.
+    ///
+    /// # Arguments
+    /// - `role_identifier`: Find a role based on it's identifier.
+    /// - `permission_identifier`: Find a permission based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// let task_response = deny_permission_on_role_request("Muted", "chat.send");
+    /// ```
+    fn deny_permission_on_role_request(role_identifier: &str, permission_identifier: &str) -> TaskRequest {
+        TaskRequest::compose_request::<RolePermissionDenyOnRole>(
+            RolePermissionDenyOnRole {
+                role_id: String::from(role_identifier),
+                permission_id: String::from(permission_identifier),
+            },
+            TaskType::Role,
+            "role_deny_permission",
+        )
+    }
+
+    /// Sets or removes the session policy of a role, it applies from the next
+    /// sign in of its members. A policy allowing less than one session is
+    /// refused with `TaskError::RoleSessionPolicyInvalid`.
+    ///
+    /// # Arguments
+    /// - `identifier`: Find a role based on it's identifier.
+    /// - `session_policy`: The policy, `None` lifts the limit.
+    ///
+    /// # Examples
+    /// ```
+    /// set_session_policy("Kiosk", Some(SessionPolicy::new(1, SessionLimitAction::EvictOldest)));
+    /// set_session_policy("Trial", Some(SessionPolicy::new(2, SessionLimitAction::Refuse)));
+    /// ```
+    pub fn set_session_policy(identifier: &str, session_policy: Option<SessionPolicy>) -> TaskResult<TaskStatus> {
+        // no member could ever sign in.
+        if session_policy.as_ref().is_some_and(|policy| policy.max_sessions < 1) {
+            return Err(TaskError::RoleSessionPolicyInvalid);
+        }
+        let request = Self::set_session_policy_request(identifier, session_policy);
+        TaskManager::process_task(request)
+    }
+
+    /// Composes a role session policy request.
+    ///
+    /// # Arguments
+    /// - `identifier`: Find a role based on it's identifier.
+    /// - `session_policy`: The policy.
+    ///
+    /// # Examples
+    /// ```
+    /// let task_response = set_session_policy_request("Kiosk", None);
+    /// ```
+    fn set_session_policy_request(identifier: &str, session_policy: Option<SessionPolicy>) -> TaskRequest {
+        TaskRequest::compose_request::<RoleSetSessionPolicy>(
+            RoleSetSessionPolicy {
+                identifier: String::from(identifier),
+                session_policy,
+            },
+            TaskType::Role,
+            "role_set_session_policy",
+        )
+    }
+
+    /// Makes a role inherit the permissions of another role, and of that role's
+    /// ancestors. Links that would make a role inherit from itself are refused
+    /// with `TaskError::RoleCycleDetected`.
+    ///
+    /// # Arguments
+    /// - `role_identifier`: Find the inheriting role based on it's identifier.
+    /// - `parent_identifier`: Find the role inherited from based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// set_parent("Admin", "Moderator");
+    /// set_parent("Moderator", "Member");
+    /// ```
+    pub fn set_parent(role_identifier: &str, parent_identifier: &str) -> TaskResult<TaskStatus> {
+        let request = Self::set_parent_request(role_identifier, parent_identifier);
+        TaskManager::process_task(request)
+    }
+
+    /// Composes a role set parent request.
+    ///
+    /// # Arguments
+    /// - `role_identifier`: Find the inheriting role based on it's identifier.
+    /// - `parent_identifier`: Find the role inherited from based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// let task_response = set_parent_request("Admin", "Moderator");
+    /// ```
+    fn set_parent_request(role_identifier: &str, parent_identifier: &str) -> TaskRequest {
+        TaskRequest::compose_request::<RoleSetParent>(
+            RoleSetParent {
+                role_id: String::from(role_identifier),
+                parent_id: String::from(parent_identifier),
+            },
+            TaskType::Role,
+            "role_set_parent",
+        )
+    }
+
+    /// Stops a role from inheriting the permissions of another role.
+    ///
+    /// # Arguments
+    /// - `role_identifier`: Find the inheriting role based on it's identifier.
+    /// - `parent_identifier`: Find the role inherited from based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// remove_parent("Admin", "Moderator");
+    /// ```
+    pub fn remove_parent(role_identifier: &str, parent_identifier: &str) -> TaskResult<TaskStatus> {
+        let request = Self::remove_parent_request(role_identifier, parent_identifier);
+        let status = TaskManager::process_task(request)?;
+        Self::rotate_members_security_stamp(role_identifier)?;
+        Ok(status)
+    }
+
+    /// Composes a role remove parent request.
+    ///
+    /// # Arguments
+    /// - `role_identifier`: Find the inheriting role based on it's identifier.
+    /// - `parent_identifier`: Find the role inherited from based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// let task_response = remove_parent_request("Admin", "Moderator");
+    /// ```
+    fn remove_parent_request(role_identifier: &str, parent_identifier: &str) -> TaskRequest {
+        TaskRequest::compose_request::<RoleRemoveParent>(
+            RoleRemoveParent {
+                role_id: String::from(role_identifier),
+                parent_id: String::from(parent_identifier),
+            },
+            TaskType::Role,
+            "role_remove_parent",
+        )
+    }
+
     /// Composes a role update request.
     ///
     /// # Arguments
@@ -247,4 +419,63 @@ impl RoleManager {
     fn preload_role_request() -> TaskRequest {
         TaskRequest::compose_request(RolePreloadCache {}, TaskType::Role, "role_preload_cache")
     }
+
+    /// Rotates the security stamp of every user holding a role or a role
+    /// inheriting from it, run once the role lost a permission so the
+    /// sessions issued while its members held it are revoked, as removing a
+    /// permission from a user does.
+    ///
+    /// # Arguments
+    /// - `role_identifier`: Find a role based on it's identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::rotate_members_security_stamp("Moderator")?;
+    /// ```
+    fn rotate_members_security_stamp(role_identifier: &str) -> TaskResult<()> {
+        let role_id = match RoleCache::get(role_identifier) {
+            Ok(role) => role.role_id,
+            Err(_) => return Ok(()),
+        };
+        let mut role_ids = RoleHierarchy::descendants(&role_id);
+        role_ids.push(role_id);
+        let request = Self::list_members_request(role_ids);
+        for user_id in TaskManager::process_task_with_result::<Vec<String>>(request)? {
+            UserManager::rotate_security_stamp(&user_id)?;
+        }
+        Ok(())
+    }
+
+    /// Composes a role list members request.
+    ///
+    /// # Arguments
+    /// - `role_ids`: The ids of the roles.
+    ///
+    /// # Examples
+    /// ```
+    /// let task_response = list_members_request(vec![role_id]);
+    /// ```
+    fn list_members_request(role_ids: Vec<String>) -> TaskRequest {
+        TaskRequest::compose_request(RoleListMembers { role_ids }, TaskType::Role, "role_list_members")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoleManager;
+    use crate::app::{
+        platform::iam::role::model::{SessionLimitAction, SessionPolicy},
+        service::task::error::TaskError,
+    };
+
+    #[test]
+    fn session_policy_allows_at_least_one_session() {
+        for max_sessions in [0, -1] {
+            let policy = SessionPolicy::new(max_sessions, SessionLimitAction::EvictOldest);
+            assert!(matches!(
+                RoleManager::set_session_policy("Kiosk", Some(policy)),
+                Err(TaskError::RoleSessionPolicyInvalid)
+            ));
+        }
+    }
 }
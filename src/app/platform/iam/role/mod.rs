@@ -1,4 +1,5 @@
 pub mod cache;
+pub mod hierarchy;
 pub mod manager;
 pub mod model;
 pub mod task;
@@ -1,13 +1,35 @@
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
+use crate::app::platform::iam::organization::model::Organization;
+
 use super::task::RoleCreateTask;
 
 #[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Role {
     pub role_id: String,
     pub role_name: String,
-    pub role_permissions: Vec<String>
+    pub role_permissions: Vec<String>,
+    #[serde(default)]
+    pub session_policy: Option<SessionPolicy>,
+    /// The ids of the roles this role inherits from.
+    #[serde(default)]
+    pub parent_roles: Vec<String>,
+    /// The ids of the permissions held through the ancestors of the role and
+    /// not directly, kept flattened by `RoleHierarchy`.
+    #[serde(default)]
+    pub inherited_permissions: Vec<String>,
+    /// The ids of the permissions taken away from the members of the role,
+    /// whatever grants them.
+    #[serde(default)]
+    pub denied_permissions: Vec<String>,
+    /// The ids of the permissions denied by the ancestors of the role and not
+    /// directly, kept flattened by `RoleHierarchy`.
+    #[serde(default)]
+    pub inherited_denied_permissions: Vec<String>,
+    /// The organization the role belongs to, `None` for a system role.
+    #[serde(default)]
+    pub org_id: Option<String>,
 }
 
 impl From<RoleCreateTask> for Role {
@@ -16,6 +38,12 @@ impl From<RoleCreateTask> for Role {
             role_id: value.role_id,
             role_name: value.role_name,
             role_permissions: value.role_permissions,
+            session_policy: value.session_policy,
+            parent_roles: Vec::default(),
+            inherited_permissions: Vec::default(),
+            denied_permissions: Vec::default(),
+            inherited_denied_permissions: Vec::default(),
+            org_id: value.org_id,
         }
     }
 }
@@ -26,18 +54,101 @@ impl Role {
             role_id: String::from(role_id),
             role_name: String::from(role_name),
             role_permissions,
+            session_policy: None,
+            parent_roles: Vec::default(),
+            inherited_permissions: Vec::default(),
+            denied_permissions: Vec::default(),
+            inherited_denied_permissions: Vec::default(),
+            org_id: None,
         }
     }
     pub fn builder() -> RoleBuilder {
         RoleBuilder::new()
     }
+
+    /// The name the role is cached under, `<org_id>:<role_name>` for the role
+    /// of an organization.
+    pub fn cache_name(&self) -> String {
+        Organization::qualify(self.org_id.as_deref(), &self.role_name)
+    }
+}
+
+/// Limits how many sessions the members of a role can have at once.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionPolicy {
+    pub max_sessions: i32,
+    pub on_limit: SessionLimitAction,
+}
+
+/// What happens when a member of a role signs in with `max_sessions` already active.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SessionLimitAction {
+    /// The oldest sessions are revoked to make room.
+    EvictOldest,
+    /// The sign in is refused.
+    Refuse,
+}
+
+impl SessionPolicy {
+    pub fn new(max_sessions: i32, on_limit: SessionLimitAction) -> SessionPolicy {
+        Self {
+            max_sessions,
+            on_limit,
+        }
+    }
+
+    /// Reads the policy out of the `max_sessions` and `session_limit_action`
+    /// columns of a role, a role without `max_sessions` has no policy.
+    ///
+    /// # Arguments
+    /// - `max_sessions`: the `max_sessions` column.
+    /// - `on_limit`: the `session_limit_action` column, `refuse` or `evict`.
+    ///
+    /// # Examples
+    /// ```
+    /// let policy = SessionPolicy::from_columns(row.get(2), row.get(3));
+    /// ```
+    pub fn from_columns(max_sessions: Option<i32>, on_limit: Option<String>) -> Option<SessionPolicy> {
+        let on_limit = match on_limit.as_deref() {
+            Some("refuse") => SessionLimitAction::Refuse,
+            _ => SessionLimitAction::EvictOldest,
+        };
+        max_sessions.map(|max_sessions| SessionPolicy::new(max_sessions, on_limit))
+    }
+
+    /// The value of the `session_limit_action` column.
+    pub fn on_limit_column(&self) -> &'static str {
+        match self.on_limit {
+            SessionLimitAction::EvictOldest => "evict",
+            SessionLimitAction::Refuse => "refuse",
+        }
+    }
+
+    /// Resolves the policy of a user out of the policies of their roles, the
+    /// lowest limit wins and refusing wins over evicting.
+    ///
+    /// # Arguments
+    /// - `roles`: the roles of the user.
+    ///
+    /// # Examples
+    /// ```
+    /// let policy = SessionPolicy::resolve(&roles);
+    /// ```
+    pub fn resolve(roles: &[Role]) -> Option<SessionPolicy> {
+        roles
+            .iter()
+            .filter_map(|role| role.session_policy.clone())
+            .min_by_key(|policy| (policy.max_sessions, policy.on_limit != SessionLimitAction::Refuse))
+    }
 }
 
 #[derive(Default)]
 pub struct RoleBuilder {
     role_id: String,
     role_name: String,
-    role_permissions: Vec<String>
+    role_permissions: Vec<String>,
+    session_policy: Option<SessionPolicy>,
+    org_id: Option<String>,
 }
 
 impl RoleBuilder {
@@ -46,6 +157,8 @@ impl RoleBuilder {
             role_id: Uuid::new_v4().to_string(),
             role_name: String::default(),
             role_permissions: Vec::default(),
+            session_policy: None,
+            org_id: None,
         }
     }
 
@@ -54,6 +167,18 @@ impl RoleBuilder {
         self
     }
 
+    pub fn session_policy(mut self, session_policy: SessionPolicy) -> RoleBuilder {
+        self.session_policy = Some(session_policy);
+        self
+    }
+
+    /// Makes the role belong to an organization, its name only has to be
+    /// unique within it.
+    pub fn organization(mut self, org_id: &str) -> RoleBuilder {
+        self.org_id = Some(String::from(org_id));
+        self
+    }
+
     /*
     pub fn role_permissions(mut self, permissions: Vec<Permission>) -> RoleBuilder {
         self.role_permissions = permissions;
@@ -66,6 +191,12 @@ impl RoleBuilder {
             role_id: self.role_id,
             role_name: self.role_name,
             role_permissions: self.role_permissions,
+            session_policy: self.session_policy,
+            parent_roles: Vec::default(),
+            inherited_permissions: Vec::default(),
+            denied_permissions: Vec::default(),
+            inherited_denied_permissions: Vec::default(),
+            org_id: self.org_id,
         }
     }
 }
\ No newline at end of file
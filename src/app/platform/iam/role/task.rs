@@ -1,8 +1,12 @@
 use crate::app::{
-    platform::iam::permission::cache::PermissionCache,
+    platform::iam::{
+        organization::{cache::OrganizationCache, model::Organization},
+        permission::cache::PermissionCache,
+    },
     service::cache::{notify_cache_hit, notify_cache_miss, LocalizedCache},
 };
 use axum::async_trait;
+use bb8_postgres::tokio_postgres::Client;
 use serde::{Deserialize, Serialize};
 
 use crate::app::{
@@ -14,7 +18,11 @@ use crate::app::{
     },
 };
 
-use super::{cache::RoleCache, model::Role};
+use super::{
+    cache::RoleCache,
+    hierarchy::RoleHierarchy,
+    model::{Role, SessionPolicy},
+};
 
 pub struct RoleTaskHandler;
 
@@ -108,6 +116,78 @@ impl TaskHandler<PostgresDatabase> for RoleTaskHandler {
             return RolePermissionDeleteLinkToRole::run(pg, task_request, payload).await;
         }
 
+        if task_request.task_action.eq("role_deny_permission") {
+            let payload = match TaskRequest::intepret_request_payload::<RolePermissionDenyOnRole>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return RolePermissionDenyOnRole::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("role_set_session_policy") {
+            let payload = match TaskRequest::intepret_request_payload::<RoleSetSessionPolicy>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return RoleSetSessionPolicy::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("role_set_parent") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<RoleSetParent>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return RoleSetParent::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("role_remove_parent") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<RoleRemoveParent>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return RoleRemoveParent::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("role_list_members") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<RoleListMembers>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return RoleListMembers::run(pg, task_request, payload).await;
+        }
+
         if task_request.task_action.eq("role_preload_cache") {
             let payload =
                 match TaskRequest::intepret_request_payload::<RolePreloadCache>(&task_request) {
@@ -136,6 +216,9 @@ pub struct RoleCreateTask {
     pub role_id: String,
     pub role_name: String,
     pub role_permissions: Vec<String>,
+    pub session_policy: Option<SessionPolicy>,
+    #[serde(default)]
+    pub org_id: Option<String>,
 }
 
 impl From<Role> for RoleCreateTask {
@@ -144,6 +227,8 @@ impl From<Role> for RoleCreateTask {
             role_id: role.role_id,
             role_name: role.role_name,
             role_permissions: role.role_permissions,
+            session_policy: role.session_policy,
+            org_id: role.org_id,
         }
     }
 }
@@ -157,12 +242,25 @@ impl Task<PostgresDatabase, TaskRequest, RoleCreateTask> for RoleCreateTask {
     ) -> TaskResponse {
         // somehow integrate role_permissions
         let pool = db.pool.get().await.unwrap();
+        if let Some(org_id) = &param.org_id {
+            if OrganizationCache::get(org_id).is_err() {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::OrganizationNotFound.to_string()],
+                );
+            }
+        }
         let stmt = pool
-            .prepare("INSERT INTO iam_roles (id, role_name) VALUES ($1, $2)")
+            .prepare("INSERT INTO iam_roles (id, role_name, max_sessions, session_limit_action, org_id) VALUES ($1, $2, $3, $4, $5)")
             .await
             .unwrap();
+        let max_sessions = param.session_policy.as_ref().map(|policy| policy.max_sessions);
+        let on_limit = param.session_policy.as_ref().map(|policy| policy.on_limit_column());
         match pool
-            .execute(&stmt, &[&param.role_id, &param.role_name])
+            .execute(
+                &stmt,
+                &[&param.role_id, &param.role_name, &max_sessions, &on_limit, &param.org_id],
+            )
             .await
         {
             Ok(_) => {
@@ -201,12 +299,16 @@ impl Task<PostgresDatabase, TaskRequest, RoleUpdateTask> for RoleUpdateTask {
         param: RoleUpdateTask,
     ) -> TaskResponse {
         let pool = db.pool.get().await.unwrap();
-        if param.update_for.eq_ignore_ascii_case("id") {
+        if param.update_for.eq_ignore_ascii_case("id") || param.update_for.eq_ignore_ascii_case("org_id") {
             return TaskResponse::throw_failed_response(
                 request,
                 vec![TaskError::FieldNotMutable.to_string()],
             );
         }
+        // names are only unique within an organization, the roles of one are found by id.
+        let search_by = RoleCache::get(&param.search_by)
+            .map(|role| role.role_id)
+            .unwrap_or(param.search_by.clone());
 
         let stmt = match pool
             .prepare(
@@ -214,7 +316,7 @@ impl Task<PostgresDatabase, TaskRequest, RoleUpdateTask> for RoleUpdateTask {
                     "UPDATE iam_roles
                 SET {} = $1
                 WHERE id = $2
-                   OR role_name = $2
+                   OR (role_name = $2 AND org_id IS NULL)
                    RETURNING *;",
                     param.update_for
                 )
@@ -231,23 +333,23 @@ impl Task<PostgresDatabase, TaskRequest, RoleUpdateTask> for RoleUpdateTask {
             }
         };
         match pool
-            .query_one(&stmt, &[&param.value, &param.search_by])
+            .query_one(&stmt, &[&param.value, &search_by])
             .await
         {
             Ok(v) => {
-                if v.len() != 0 {
+                if !v.is_empty() {
                     let old_role = RoleCache::get(v.get(0)).unwrap();
-                    RoleCache::remove(&old_role.role_name).unwrap();
+                    RoleCache::remove(&old_role.cache_name()).unwrap();
                     // TODO THIS....
                     // TODO THIS....
                     // TODO THIS....
                     // TODO THIS....
                     // NEED TO BE ABLE TO ADD SPECIFIC PERMISSIONS TO THIS
-                    RoleCache::add(Role::new(
-                        v.get(0),
-                        v.get(1),
-                        RoleCache::get(v.get(0)).unwrap().role_permissions,
-                    ));
+                    RoleCache::add(Role {
+                        role_name: v.get(1),
+                        session_policy: SessionPolicy::from_columns(v.get(2), v.get(3)),
+                        ..old_role
+                    });
                     return TaskResponse::compose_response(
                         request,
                         TaskStatus::Completed,
@@ -284,18 +386,33 @@ impl Task<PostgresDatabase, TaskRequest, RoleDeleteTask> for RoleDeleteTask {
         param: RoleDeleteTask,
     ) -> TaskResponse {
         let pool = db.pool.get().await.unwrap();
+        let cached_role = RoleCache::get(&param.identifier).ok();
+        let identifier = cached_role
+            .as_ref()
+            .map(|role| role.role_id.clone())
+            .unwrap_or(param.identifier.clone());
         let stmt = pool
             .prepare(
                 "DELETE FROM iam_roles
                     WHERE id = $1
-                       OR role_name = $1",
+                       OR (role_name = $1 AND org_id IS NULL)",
             )
             .await
             .unwrap();
-        match pool.execute(&stmt, &[&param.identifier]).await {
+        match pool.execute(&stmt, &[&identifier]).await {
             Ok(v) => {
                 if v != 0 {
-                    RoleCache::remove(&param.identifier).unwrap();
+                    match cached_role {
+                        Some(role) => {
+                            // the links to its parents and children are dropped along with it.
+                            let _ = RoleCache::remove(&role.role_id);
+                            let _ = RoleCache::remove(&role.cache_name());
+                            RoleHierarchy::detach(&role.role_id);
+                        }
+                        None => {
+                            let _ = RoleCache::remove(&param.identifier);
+                        }
+                    }
                     return TaskResponse::compose_response(
                         request,
                         TaskStatus::Completed,
@@ -341,7 +458,7 @@ impl Task<PostgresDatabase, TaskRequest, RoleReadTask> for RoleReadTask {
                 let stmt = pool
                     .prepare(
                         "SELECT * FROM iam_roles WHERE id = $1
-        OR role_name = $1;",
+        OR (role_name = $1 AND org_id IS NULL);",
                     )
                     .await
                     .unwrap();
@@ -349,23 +466,40 @@ impl Task<PostgresDatabase, TaskRequest, RoleReadTask> for RoleReadTask {
                     Ok(row) => {
                         notify_cache_miss("RoleCache", "RoleReadTask", &request.task_id);
                         let mut role_permissions: Vec<String> = Vec::new();
+                        let mut denied_permissions: Vec<String> = Vec::new();
                         let stmt = pool
                             .prepare(
-                                "SELECT permission_id FROM iam_role_permission WHERE role_id = $1",
+                                "SELECT permission_id, denied FROM iam_role_permission WHERE role_id = $1",
                             )
                             .await
                             .unwrap();
                         match pool.query(&stmt, &[&row.get::<usize, String>(0)]).await {
                             Ok(permissions) => {
                                 for permission in permissions {
-                                    role_permissions.push(permission.get(0))
+                                    if permission.get::<_, bool>(1) {
+                                        denied_permissions.push(permission.get(0))
+                                    } else {
+                                        role_permissions.push(permission.get(0))
+                                    }
                                 }
                             }
                             Err(er) => {
                                 println!("{}", er);
                             }
                         }
-                        let role = Role::new(row.get(0), row.get(1), role_permissions);
+                        let role = Role {
+                            session_policy: SessionPolicy::from_columns(row.get(2), row.get(3)),
+                            parent_roles: read_parent_roles(&pool, row.get(0)).await,
+                            denied_permissions,
+                            org_id: row.get(4),
+                            ..Role::new(row.get(0), row.get(1), role_permissions)
+                        };
+                        let role = Role {
+                            inherited_permissions: RoleHierarchy::inherited_permissions(&role),
+                            inherited_denied_permissions:
+                                RoleHierarchy::inherited_denied_permissions(&role),
+                            ..role
+                        };
                         RoleCache::add(role.clone());
                         return TaskResponse::compose_response(
                             request,
@@ -403,23 +537,36 @@ impl Task<PostgresDatabase, TaskRequest, RolePreloadCache> for RolePreloadCache
                 let mut amt_items = 0;
                 for row in rows {
                     let mut role_permissions: Vec<String> = Vec::new();
+                    let mut denied_permissions: Vec<String> = Vec::new();
                     let stmt = pool
-                        .prepare("SELECT permission_id FROM iam_role_permission WHERE role_id = $1")
+                        .prepare("SELECT permission_id, denied FROM iam_role_permission WHERE role_id = $1")
                         .await
                         .unwrap();
                     match pool.query(&stmt, &[&row.get::<usize, String>(0)]).await {
                         Ok(permissions) => {
                             for permission in permissions {
-                                role_permissions.push(permission.get(0))
+                                if permission.get::<_, bool>(1) {
+                                    denied_permissions.push(permission.get(0))
+                                } else {
+                                    role_permissions.push(permission.get(0))
+                                }
                             }
                         }
                         Err(er) => {
                             println!("{}", er);
                         }
                     }
-                    RoleCache::add(Role::new(row.get(0), row.get(1), role_permissions.clone()));
+                    RoleCache::add(Role {
+                        session_policy: SessionPolicy::from_columns(row.get(2), row.get(3)),
+                        parent_roles: read_parent_roles(&pool, row.get(0)).await,
+                        denied_permissions,
+                        org_id: row.get(4),
+                        ..Role::new(row.get(0), row.get(1), role_permissions.clone())
+                    });
                     amt_items += 1;
                 }
+                // every role has to be cached before their ancestry can be walked.
+                RoleHierarchy::refresh_all();
                 println!("[CACHE] cached {} role(s) cache.", amt_items);
                 return TaskResponse::compose_response(
                     request,
@@ -452,8 +599,8 @@ impl Task<PostgresDatabase, TaskRequest, RolePermissionLinkToRole> for RolePermi
     ) -> TaskResponse {
         let pool = db.pool.get().await.unwrap();
 
-        let role_to_id = match RoleCache::get(&param.role_id) {
-            Ok(v) => v.role_id,
+        let role = match RoleCache::get(&param.role_id) {
+            Ok(v) => v,
             Err(_) => {
                 return TaskResponse::throw_failed_response(
                     request,
@@ -461,8 +608,8 @@ impl Task<PostgresDatabase, TaskRequest, RolePermissionLinkToRole> for RolePermi
                 )
             }
         };
-        let permission_to_id = match PermissionCache::get(&param.permission_id) {
-            Ok(v) => v.permission_id,
+        let permission = match PermissionCache::get(&param.permission_id) {
+            Ok(v) => v,
             Err(_) => {
                 return TaskResponse::throw_failed_response(
                     request,
@@ -470,9 +617,22 @@ impl Task<PostgresDatabase, TaskRequest, RolePermissionLinkToRole> for RolePermi
                 )
             }
         };
+        // the permissions of an organization are only linked to its roles.
+        if !Organization::is_visible(permission.org_id.as_deref(), role.org_id.as_deref()) {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::OrganizationMismatch.to_string()],
+            );
+        }
+        let role_to_id = role.role_id;
+        let permission_to_id = permission.permission_id;
 
+        // a denied link is turned into a granted one.
         let stmt = pool
-            .prepare("INSERT INTO iam_role_permission (role_id, permission_id) VALUES ($1, $2)")
+            .prepare(
+                "INSERT INTO iam_role_permission (role_id, permission_id) VALUES ($1, $2)
+            ON CONFLICT (role_id, permission_id) DO UPDATE SET denied = FALSE",
+            )
             .await
             .unwrap();
         match pool.query(&stmt, &[&role_to_id, &permission_to_id]).await {
@@ -480,8 +640,13 @@ impl Task<PostgresDatabase, TaskRequest, RolePermissionLinkToRole> for RolePermi
                 // overrides the existing role (because value is the value is a shared state(arc))
                 // it should reflect throughout the rest of the application
                 let mut role = RoleCache::get(&param.role_id).unwrap();
-                role.role_permissions.push(permission_to_id);
+                role.denied_permissions
+                    .retain(|permission| permission != &permission_to_id);
+                if !role.role_permissions.contains(&permission_to_id) {
+                    role.role_permissions.push(permission_to_id);
+                }
                 RoleCache::add(role);
+                RoleHierarchy::refresh(&role_to_id);
                 return TaskResponse::compose_response(
                     request,
                     TaskStatus::Completed,
@@ -549,7 +714,10 @@ impl Task<PostgresDatabase, TaskRequest, RolePermissionDeleteLinkToRole>
                 let mut role = RoleCache::get(&param.role_id).unwrap();
                 role.role_permissions
                     .retain(|permission| permission != &permission_to_id);
+                role.denied_permissions
+                    .retain(|permission| permission != &permission_to_id);
                 RoleCache::add(role);
+                RoleHierarchy::refresh(&role_to_id);
                 return TaskResponse::compose_response(
                     request,
                     TaskStatus::Completed,
@@ -567,3 +735,377 @@ impl Task<PostgresDatabase, TaskRequest, RolePermissionDeleteLinkToRole>
         }
     }
 }
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct RolePermissionDenyOnRole {
+    pub role_id: String,
+    pub permission_id: String,
+}
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, RolePermissionDenyOnRole> for RolePermissionDenyOnRole {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: RolePermissionDenyOnRole,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+
+        let role = match RoleCache::get(&param.role_id) {
+            Ok(v) => v,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::RoleNotFound.to_string()],
+                )
+            }
+        };
+        let permission = match PermissionCache::get(&param.permission_id) {
+            Ok(v) => v,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::PermissionNotFound.to_string()],
+                )
+            }
+        };
+        // the permissions of an organization are only linked to its roles.
+        if !Organization::is_visible(permission.org_id.as_deref(), role.org_id.as_deref()) {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::OrganizationMismatch.to_string()],
+            );
+        }
+        let role_to_id = role.role_id;
+        let permission_to_id = permission.permission_id;
+
+        // a granted link is turned into a denied one.
+        let stmt = pool
+            .prepare(
+                "INSERT INTO iam_role_permission (role_id, permission_id, denied) VALUES ($1, $2, TRUE)
+            ON CONFLICT (role_id, permission_id) DO UPDATE SET denied = TRUE",
+            )
+            .await
+            .unwrap();
+        match pool.execute(&stmt, &[&role_to_id, &permission_to_id]).await {
+            Ok(_) => {
+                let mut role = RoleCache::get(&param.role_id).unwrap();
+                role.role_permissions
+                    .retain(|permission| permission != &permission_to_id);
+                if !role.denied_permissions.contains(&permission_to_id) {
+                    role.denied_permissions.push(permission_to_id);
+                }
+                RoleCache::add(role);
+                RoleHierarchy::refresh(&role_to_id);
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    param,
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::RoleLinkFailedToLink.to_string()],
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct RoleSetSessionPolicy {
+    pub identifier: String,
+    pub session_policy: Option<SessionPolicy>,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, RoleSetSessionPolicy> for RoleSetSessionPolicy {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: RoleSetSessionPolicy,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        let stmt = pool
+            .prepare(
+                "UPDATE iam_roles
+                SET max_sessions = $1, session_limit_action = $2
+                WHERE id = $3
+                   OR (role_name = $3 AND org_id IS NULL)
+                   RETURNING id;",
+            )
+            .await
+            .unwrap();
+        let max_sessions = param.session_policy.as_ref().map(|policy| policy.max_sessions);
+        let on_limit = param.session_policy.as_ref().map(|policy| policy.on_limit_column());
+        let identifier = RoleCache::get(&param.identifier)
+            .map(|role| role.role_id)
+            .unwrap_or(param.identifier.clone());
+        match pool
+            .query_opt(&stmt, &[&max_sessions, &on_limit, &identifier])
+            .await
+        {
+            Ok(Some(row)) => {
+                // the policy applies from the next sign in of its members.
+                if let Ok(role) = RoleCache::get(row.get(0)) {
+                    RoleCache::add(Role {
+                        session_policy: param.session_policy,
+                        ..role
+                    });
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
+            Ok(None) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::RoleNotFound.to_string()],
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        }
+    }
+}
+
+/// Reads the ids of the roles a role inherits from.
+///
+/// # Arguments
+/// - `pool`: a connection of the pool.
+/// - `role_id`: the id of the role.
+async fn read_parent_roles(pool: &Client, role_id: &str) -> Vec<String> {
+    let stmt = pool
+        .prepare("SELECT parent_id FROM iam_role_parent WHERE role_id = $1")
+        .await
+        .unwrap();
+    match pool.query(&stmt, &[&role_id]).await {
+        Ok(parents) => parents.iter().map(|parent| parent.get(0)).collect(),
+        Err(er) => {
+            println!("{}", er);
+            Vec::default()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct RoleSetParent {
+    pub role_id: String,
+    pub parent_id: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, RoleSetParent> for RoleSetParent {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: RoleSetParent,
+    ) -> TaskResponse {
+        let mut pool = db.pool.get().await.unwrap();
+        let mut role = match RoleCache::get(&param.role_id) {
+            Ok(v) => v,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::RoleNotFound.to_string()],
+                )
+            }
+        };
+        let parent = match RoleCache::get(&param.parent_id) {
+            Ok(v) => v,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::RoleNotFound.to_string()],
+                )
+            }
+        };
+        // roles inherit from system roles or from the roles of their organization.
+        if !Organization::is_visible(parent.org_id.as_deref(), role.org_id.as_deref()) {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::OrganizationMismatch.to_string()],
+            );
+        }
+        let parent_to_id = parent.role_id;
+        if RoleHierarchy::creates_cycle(&role.role_id, &parent_to_id) {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::RoleCycleDetected.to_string()],
+            );
+        }
+        let transaction = pool.transaction().await.unwrap();
+        // the cache of another instance may not know of a link made there, the
+        // links are locked against writes until this one is in and checked
+        // against the table.
+        transaction
+            .execute(
+                "LOCK TABLE iam_role_parent IN SHARE ROW EXCLUSIVE MODE",
+                &[],
+            )
+            .await
+            .unwrap();
+        let creates_cycle: bool = transaction
+            .query_one(
+                "WITH RECURSIVE ancestors(id) AS (
+                    SELECT $2::VARCHAR
+                    UNION
+                    SELECT link.parent_id FROM iam_role_parent link
+                        JOIN ancestors ON link.role_id = ancestors.id
+                )
+                SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = $1)",
+                &[&role.role_id, &parent_to_id],
+            )
+            .await
+            .unwrap()
+            .get(0);
+        if creates_cycle {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::RoleCycleDetected.to_string()],
+            );
+        }
+        let insert_result = transaction
+            .execute(
+                "INSERT INTO iam_role_parent (role_id, parent_id) VALUES ($1, $2)",
+                &[&role.role_id, &parent_to_id],
+            )
+            .await;
+        if insert_result.is_err() || transaction.commit().await.is_err() {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::RoleLinkFailedToLink.to_string()],
+            );
+        }
+        let role_id = role.role_id.clone();
+        role.parent_roles.push(parent_to_id);
+        RoleCache::add(role);
+        RoleHierarchy::refresh(&role_id);
+        return TaskResponse::compose_response(
+            request,
+            TaskStatus::Completed,
+            param,
+            Vec::default(),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct RoleRemoveParent {
+    pub role_id: String,
+    pub parent_id: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, RoleRemoveParent> for RoleRemoveParent {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: RoleRemoveParent,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        let mut role = match RoleCache::get(&param.role_id) {
+            Ok(v) => v,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::RoleNotFound.to_string()],
+                )
+            }
+        };
+        let parent_to_id = match RoleCache::get(&param.parent_id) {
+            Ok(v) => v.role_id,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::RoleNotFound.to_string()],
+                )
+            }
+        };
+
+        let stmt = pool
+            .prepare(
+                "DELETE FROM iam_role_parent
+            WHERE role_id = $1
+               AND parent_id = $2",
+            )
+            .await
+            .unwrap();
+        match pool.execute(&stmt, &[&role.role_id, &parent_to_id]).await {
+            Ok(0) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::RoleParentNotFound.to_string()],
+                );
+            }
+            Ok(_) => {
+                let role_id = role.role_id.clone();
+                role.parent_roles.retain(|parent_id| parent_id != &parent_to_id);
+                RoleCache::add(role);
+                RoleHierarchy::refresh(&role_id);
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    param,
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct RoleListMembers {
+    pub role_ids: Vec<String>,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, RoleListMembers> for RoleListMembers {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: RoleListMembers,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        // holders on a single resource are members too.
+        let stmt = pool
+            .prepare(
+                "SELECT user_id FROM iam_user_role WHERE role_id = ANY($1)
+                UNION
+                SELECT user_id FROM iam_user_scoped_role WHERE role_id = ANY($1)",
+            )
+            .await
+            .unwrap();
+        match pool.query(&stmt, &[&param.role_ids]).await {
+            Ok(rows) => {
+                let members: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    members,
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        }
+    }
+}
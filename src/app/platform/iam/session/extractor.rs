@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+};
+use tower_cookies::Cookies;
+
+use crate::app::{
+    ark::{ArkState, SESSION_COOKIE_NAME},
+    platform::{
+        iam::user::{manager::UserManager, model::User},
+        response::ErrorJsonResponse,
+    },
+};
+
+use super::{manager::SessionManager, model::UserSession};
+
+/// The user behind the session of the request.
+///
+/// The session token is read from the private `pl.session` cookie or from an
+/// `Authorization: Bearer <token>` header, expired and revoked sessions are
+/// rejected with a 401. Behind a guard the user it already resolved is reused.
+///
+/// # Examples
+/// ```
+/// async fn me(AuthenticatedUser { user, .. }: AuthenticatedUser) -> CustomJsonResponse<User> {
+///     CustomJsonResponse::new(StatusCode::OK, user)
+/// }
+/// ```
+#[derive(Clone)]
+pub struct AuthenticatedUser {
+    pub user: User,
+    pub session: UserSession,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthenticatedUser {
+    type Rejection = ErrorJsonResponse;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(authenticated) = parts.extensions.get::<AuthenticatedUser>() {
+            return Ok(authenticated.clone());
+        }
+        let token = match bearer_token(parts) {
+            Some(token) => Some(token),
+            None => cookie_token(parts, state).await,
+        }
+        .ok_or_else(|| {
+            ErrorJsonResponse::new(StatusCode::UNAUTHORIZED, "You are not signed in.")
+        })?;
+        let session = SessionManager::validate_session(&token).map_err(|_| {
+            ErrorJsonResponse::new(StatusCode::UNAUTHORIZED, "Your session has expired.")
+        })?;
+        let user = UserManager::get_user(&session.user_id).map_err(|_| {
+            ErrorJsonResponse::new(StatusCode::UNAUTHORIZED, "Your session has expired.")
+        })?;
+        Ok(Self { user, session })
+    }
+}
+
+/// Reads the token of an `Authorization: Bearer <token>` header.
+fn bearer_token(parts: &Parts) -> Option<String> {
+    parts
+        .headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+/// Reads the token of the private `pl.session` cookie.
+async fn cookie_token<S: Send + Sync>(parts: &mut Parts, state: &S) -> Option<String> {
+    let ark = parts.extensions.get::<Arc<ArkState>>()?.clone();
+    let cookies = Cookies::from_request_parts(parts, state).await.ok()?;
+    let cookie = cookies.private(&ark.key).get(SESSION_COOKIE_NAME)?;
+    serde_json::from_str::<UserSession>(cookie.value())
+        .ok()
+        .map(|session| session.token)
+}
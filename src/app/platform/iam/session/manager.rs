@@ -1,59 +1,173 @@
+use once_cell::sync::Lazy;
 use uuid::Uuid;
 
-use crate::app::service::task::{
-    error::TaskResult,
-    manager::TaskManager,
-    message::{TaskRequest, TaskType},
+use crate::app::{
+    platform::iam::{
+        role::model::SessionPolicy,
+        user::{manager::UserManager, model::User},
+    },
+    service::{
+        task::{
+            error::{TaskError, TaskResult},
+            manager::TaskManager,
+            message::{TaskRequest, TaskStatus, TaskType},
+        },
+    },
 };
 
-use super::{model::UserSession, task::{SessionCreateTask, SessionRevocationTask}};
+use super::{model::{SessionConfig, SessionMetadata, UserSession}, task::{SessionCreateTask, SessionEnforcePolicyTask, SessionListTask, SessionPurgeLegacyTask, SessionRefreshTask, SessionRevocationByIdTask, SessionRevocationTask, SessionUpdateSecurityStampTask, SessionValidationTask}};
+
+static SESSION_CONFIG: Lazy<SessionConfig> = Lazy::new(SessionConfig::default);
 
 pub struct SessionManager;
 
 impl SessionManager {
-    /// Create a user session.
+    /// Create a user session, the other sessions of the user are kept.
+    ///
+    /// It lives for `SESSION_IDLE_TIMEOUT` seconds of inactivity and at most
+    /// `SESSION_MAX_LIFETIME` seconds, or until the security stamp of the user
+    /// is rotated. When a role of the user limits its sessions, the oldest
+    /// ones are evicted or `TaskError::SessionLimitReached` is returned.
     ///
     /// # Arguments
-    /// - `user_id`: who to create the sessionf or.
+    /// - `user`: who to create the session for.
+    /// - `metadata`: the device the session is created on.
     ///
     /// # Examples
     /// ```
-    /// let role = PermissionBuilder::builder()
-    ///     .role_name("Member")
-    ///     .build();
-    /// create_role(role);
+    /// let metadata = SessionMetadata::new(Some("127.0.0.1"), Some("Mozilla/5.0 ..."));
+    /// SessionManager::create_session(&user, metadata).unwrap();
     /// ```
-    pub fn create_session(user_id: &str) -> TaskResult<UserSession> {
-        let task_request = Self::create_session_request(UserSession {
+    pub fn create_session(user: &User, metadata: SessionMetadata) -> TaskResult<UserSession> {
+        Self::create_session_replacing(user, metadata, None)
+    }
+
+    /// Create a user session, `replacing` is the id of a session that is about
+    /// to be revoked in its favour and does not count against the limit of
+    /// the roles of the user.
+    fn create_session_replacing(user: &User, metadata: SessionMetadata, replacing: Option<&str>) -> TaskResult<UserSession> {
+        let roles = UserManager::roles_in_org(user, metadata.organization_id.as_deref());
+        if let Some(policy) = SessionPolicy::resolve(&roles) {
+            let task_request = Self::enforce_session_policy_request(&user.info.user_id, policy, replacing);
+            if !TaskManager::process_task_with_result::<bool>(task_request)? {
+                return Err(TaskError::SessionLimitReached);
+            }
+        }
+        let task_request = Self::create_session_request(user.security.stamp.as_deref().unwrap_or_default(), UserSession {
             token: Uuid::new_v4().as_simple().to_string(),
-            expires_in: 604800,
-            user_id: user_id.to_string(),
+            refresh_token: Uuid::new_v4().as_simple().to_string(),
+            expires_in: SESSION_CONFIG.idle_timeout,
+            remaining_lifetime: SESSION_CONFIG.max_lifetime,
+            user_id: user.info.user_id.clone(),
+            metadata,
         });
         TaskManager::process_task_with_result::<UserSession>(task_request)
     }
 
+    /// Create a user session scoped to an organization, permission checks made
+    /// with it consider the roles and permissions the user holds within it.
+    /// Returns `TaskError::OrganizationMemberNotFound` when the user is not a
+    /// member of the organization.
+    ///
+    /// # Arguments
+    /// - `user`: who to create the session for.
+    /// - `org_id`: the id of the organization.
+    /// - `metadata`: the device the session is created on.
+    ///
+    /// # Examples
+    /// ```
+    /// let metadata = SessionMetadata::new(Some("127.0.0.1"), Some("Mozilla/5.0 ..."));
+    /// SessionManager::create_organization_session(&user, "dd2546c3-e34a-4fcb-9b12-1a96eb6873e3", metadata).unwrap();
+    /// ```
+    pub fn create_organization_session(user: &User, org_id: &str, mut metadata: SessionMetadata) -> TaskResult<UserSession> {
+        if !user.access.is_member_of(org_id) {
+            return Err(TaskError::OrganizationMemberNotFound);
+        }
+        metadata.organization_id = Some(String::from(org_id));
+        Self::create_session(user, metadata)
+    }
+
+    /// Replace a session by one scoped to an organization, or by one outside
+    /// of any organization when `org_id` is `None`, created for the same
+    /// device. The session replaced does not count against the limit of the
+    /// roles of the user and is revoked once its replacement exists.
+    ///
+    /// # Arguments
+    /// - `user`: who the session belongs to.
+    /// - `current`: the session to replace.
+    /// - `org_id`: the id of the organization.
+    ///
+    /// # Examples
+    /// ```
+    /// let session = SessionManager::switch_session(&user, &session, Some("dd2546c3-e34a-4fcb-9b12-1a96eb6873e3")).unwrap();
+    /// let session = SessionManager::switch_session(&user, &session, None).unwrap();
+    /// ```
+    pub fn switch_session(user: &User, current: &UserSession, org_id: Option<&str>) -> TaskResult<UserSession> {
+        if org_id.is_some_and(|org_id| !user.access.is_member_of(org_id)) {
+            return Err(TaskError::OrganizationMemberNotFound);
+        }
+        let mut metadata = SessionMetadata::new(current.metadata.ip_address.as_deref(), current.metadata.user_agent.as_deref());
+        metadata.organization_id = org_id.map(String::from);
+        let session = Self::create_session_replacing(user, metadata, Some(&current.metadata.session_id))?;
+        if let Err(er) = Self::revoke_session_by_id(&current.user_id, &current.metadata.session_id) {
+            // the user is left with the session they had rather than two.
+            Self::revoke_session_by_id(&session.user_id, &session.metadata.session_id)?;
+            return Err(er);
+        }
+        Ok(session)
+    }
+
+    /// Composes a session policy enforcement request.
+    ///
+    /// # Arguments
+    /// - `user_id`: The user id.
+    /// - `policy`: The session policy resolved out of the roles of the user.
+    /// - `replacing`: The id of a session left out of the limit.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::enforce_session_policy_request(user_id, policy, None)
+    /// ```
+    fn enforce_session_policy_request(user_id: &str, policy: SessionPolicy, replacing: Option<&str>) -> TaskRequest {
+        TaskRequest::compose_request(
+            SessionEnforcePolicyTask {
+                user_id: String::from(user_id),
+                policy,
+                max_lifetime: SESSION_CONFIG.max_lifetime,
+                replacing: replacing.map(String::from),
+            },
+            TaskType::Session,
+            "session_enforce_policy",
+        )
+    }
+
     /// Composes a user session create request.
     ///
     /// # Arguments
+    /// - `security_stamp`: The security stamp the session is issued under.
     /// - `session`: A reference to the `Session` to process.
     ///
     /// # Examples
     /// ```
-    /// Self::create_role_request(role)
+    /// Self::create_role_request(security_stamp, role)
     /// ```
-    fn create_session_request(session: UserSession) -> TaskRequest {
+    fn create_session_request(security_stamp: &str, session: UserSession) -> TaskRequest {
         TaskRequest::compose_request(
             SessionCreateTask {
                 token: session.token,
-                expires_in: session.expires_in,
+                refresh_token: session.refresh_token,
+                idle_timeout: session.expires_in,
+                max_lifetime: session.remaining_lifetime,
                 user_id: session.user_id,
+                security_stamp: String::from(security_stamp),
+                metadata: session.metadata,
             },
             TaskType::Session,
             "session_create",
         )
     }
 
-    /// Revoke a user session.
+    /// Revoke every session of a user, signing them out everywhere.
     ///
     /// # Arguments
     /// - `user_id`: who to create the sessionf or.
@@ -63,9 +177,9 @@ impl SessionManager {
     /// SessionManager::revoke_session("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX").unwrap();
     /// SessionManager::revoke_session("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX").unwrap();
     /// ```
-    pub fn revoke_session(user_id: &str) -> TaskResult<UserSession> {
+    pub fn revoke_session(user_id: &str) -> TaskResult<TaskStatus> {
         let task_request = Self::revoke_session_request(user_id);
-        TaskManager::process_task_with_result::<UserSession>(task_request)
+        TaskManager::process_task(task_request)
     }
 
     /// Composes a user session revocation request.
@@ -86,4 +200,205 @@ impl SessionManager {
             "session_revocation",
         )
     }
+
+    /// Validate a session token, pushing its idle timeout forward.
+    ///
+    /// # Arguments
+    /// - `token`: the token of the session.
+    ///
+    /// # Examples
+    /// ```
+    /// let session = SessionManager::validate_session("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX").unwrap();
+    /// ```
+    pub fn validate_session(token: &str) -> TaskResult<UserSession> {
+        let task_request = Self::validate_session_request(token);
+        TaskManager::process_task_with_result::<UserSession>(task_request)
+    }
+
+    /// Composes a user session validation request.
+    ///
+    /// # Arguments
+    /// - `token`: The session token.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::validate_session_request(token)
+    /// ```
+    fn validate_session_request(token: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            SessionValidationTask {
+                token: String::from(token),
+                idle_timeout: SESSION_CONFIG.idle_timeout,
+            },
+            TaskType::Session,
+            "session_validation",
+        )
+    }
+
+    /// List the sessions of a user, most recently seen first.
+    ///
+    /// # Arguments
+    /// - `user_id`: whose sessions to list.
+    ///
+    /// # Examples
+    /// ```
+    /// let sessions = SessionManager::list_sessions("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX").unwrap();
+    /// ```
+    pub fn list_sessions(user_id: &str) -> TaskResult<Vec<SessionMetadata>> {
+        let task_request = Self::list_sessions_request(user_id);
+        TaskManager::process_task_with_result::<Vec<SessionMetadata>>(task_request)
+    }
+
+    /// Composes a user session list request.
+    ///
+    /// # Arguments
+    /// - `user_id`: The user id.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::list_sessions_request(user_id)
+    /// ```
+    fn list_sessions_request(user_id: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            SessionListTask {
+                user_id: String::from(user_id),
+            },
+            TaskType::Session,
+            "session_list",
+        )
+    }
+
+    /// Revoke a single session of a user and its refresh tokens, signing them out of that device.
+    ///
+    /// # Arguments
+    /// - `user_id`: whose session to revoke.
+    /// - `session_id`: the `session_id` of the session.
+    ///
+    /// # Examples
+    /// ```
+    /// SessionManager::revoke_session_by_id("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX", "XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX").unwrap();
+    /// ```
+    pub fn revoke_session_by_id(user_id: &str, session_id: &str) -> TaskResult<TaskStatus> {
+        let task_request = Self::revoke_session_by_id_request(user_id, session_id);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Composes a user session revocation by id request.
+    ///
+    /// # Arguments
+    /// - `user_id`: The user id.
+    /// - `session_id`: The session id.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::revoke_session_by_id_request(user_id, session_id)
+    /// ```
+    fn revoke_session_by_id_request(user_id: &str, session_id: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            SessionRevocationByIdTask {
+                user_id: String::from(user_id),
+                session_id: String::from(session_id),
+            },
+            TaskType::Session,
+            "session_revocation_by_id",
+        )
+    }
+
+    /// Exchange a refresh token for a new session and refresh token.
+    ///
+    /// Refresh tokens are used once, presenting one that was already used
+    /// revokes the session along with every refresh token of its family.
+    ///
+    /// # Arguments
+    /// - `refresh_token`: the refresh token issued with the previous session.
+    ///
+    /// # Examples
+    /// ```
+    /// let session = SessionManager::refresh_session(&session.refresh_token).unwrap();
+    /// ```
+    pub fn refresh_session(refresh_token: &str) -> TaskResult<UserSession> {
+        let task_request = Self::refresh_session_request(refresh_token);
+        TaskManager::process_task_with_result::<UserSession>(task_request)
+    }
+
+    /// Composes a user session refresh request.
+    ///
+    /// # Arguments
+    /// - `refresh_token`: The refresh token.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::refresh_session_request(refresh_token)
+    /// ```
+    fn refresh_session_request(refresh_token: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            SessionRefreshTask {
+                refresh_token: String::from(refresh_token),
+                token: Uuid::new_v4().as_simple().to_string(),
+                new_refresh_token: Uuid::new_v4().as_simple().to_string(),
+                idle_timeout: SESSION_CONFIG.idle_timeout,
+            },
+            TaskType::Session,
+            "session_refresh",
+        )
+    }
+
+    /// Record the new security stamp of a user, every session issued under the
+    /// previous one stops validating without having to look them up.
+    ///
+    /// # Arguments
+    /// - `user_id`: whose stamp was rotated.
+    /// - `security_stamp`: the new security stamp.
+    ///
+    /// # Examples
+    /// ```
+    /// SessionManager::update_security_stamp("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX", &stamp).unwrap();
+    /// ```
+    pub fn update_security_stamp(user_id: &str, security_stamp: &str) -> TaskResult<TaskStatus> {
+        let task_request = Self::update_security_stamp_request(user_id, security_stamp);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Composes a security stamp update request.
+    ///
+    /// # Arguments
+    /// - `user_id`: The user id.
+    /// - `security_stamp`: The new security stamp.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::update_security_stamp_request(user_id, security_stamp)
+    /// ```
+    fn update_security_stamp_request(user_id: &str, security_stamp: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            SessionUpdateSecurityStampTask {
+                user_id: String::from(user_id),
+                security_stamp: String::from(security_stamp),
+            },
+            TaskType::Session,
+            "session_update_security_stamp",
+        )
+    }
+
+    /// Purge the sessions stored before tokens were digested, once it
+    /// succeeded it is not run again.
+    ///
+    /// # Examples
+    /// ```
+    /// SessionManager::purge_legacy_sessions().unwrap();
+    /// ```
+    pub fn purge_legacy_sessions() -> TaskResult<TaskStatus> {
+        let task_request = Self::purge_legacy_sessions_request();
+        TaskManager::process_task(task_request)
+    }
+
+    /// Composes a legacy session purge request.
+    ///
+    /// # Examples
+    /// ```
+    /// Self::purge_legacy_sessions_request()
+    /// ```
+    fn purge_legacy_sessions_request() -> TaskRequest {
+        TaskRequest::compose_request(SessionPurgeLegacyTask, TaskType::Session, "session_purge_legacy")
+    }
 }
@@ -1,3 +1,5 @@
+pub mod extractor;
 pub mod manager;
 pub mod model;
+pub mod store;
 pub mod task;
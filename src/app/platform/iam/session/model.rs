@@ -1,19 +1,157 @@
+use std::{
+    env,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// How long sessions live, in seconds.
+///
+/// A session expires once it has been idle for `idle_timeout`, every
+/// validated request pushes that deadline forward, but never past
+/// `max_lifetime` after it was created.
+pub struct SessionConfig {
+    pub idle_timeout: i64,
+    pub max_lifetime: i64,
+}
 
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: env::var("SESSION_IDLE_TIMEOUT")
+                .ok()
+                .and_then(|timeout| timeout.parse().ok())
+                .unwrap_or(900), // 15 minutes
+            max_lifetime: env::var("SESSION_MAX_LIFETIME")
+                .ok()
+                .and_then(|lifetime| lifetime.parse().ok())
+                .unwrap_or(604800), // 7 days
+        }
+    }
+}
 
+/// A session of a user.
+///
+/// `expires_in` is how many seconds are left before the session expires if
+/// it is left idle, `remaining_lifetime` how many are left before it expires
+/// no matter what. `refresh_token` is only set when the session is issued, it
+/// is exchanged for the next session of the family through
+/// `SessionManager::refresh_session`.
 #[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct UserSession {
     pub token: String,
+    #[serde(default)]
+    pub refresh_token: String,
     pub expires_in: i64,
-    pub user_id: String
+    #[serde(default)]
+    pub remaining_lifetime: i64,
+    pub user_id: String,
+    #[serde(default)]
+    pub metadata: SessionMetadata,
 }
 
 impl UserSession {
-    pub fn new(token: &str, expires_in: i64, user_id: &str) -> Self {
+    pub fn new(
+        token: &str,
+        expires_in: i64,
+        remaining_lifetime: i64,
+        user_id: &str,
+        metadata: SessionMetadata,
+    ) -> Self {
         Self {
             token: String::from(token),
+            refresh_token: String::default(),
             expires_in,
-            user_id: String::from(user_id)
+            remaining_lifetime,
+            user_id: String::from(user_id),
+            metadata,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Describes where a session was created, a user can have one per device.
+///
+/// `session_id` identifies the session without exposing its token, the
+/// timestamps are in milliseconds. `expires_at` is set when the session is
+/// stored, it is the end of its lifetime. `organization_id` is set when the
+/// session is scoped to an organization, checks made with it then consider the
+/// roles and permissions of this organization.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    pub session_id: String,
+    pub created_at: i64,
+    pub last_seen: i64,
+    #[serde(default)]
+    pub expires_at: i64,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub device: String,
+    #[serde(default)]
+    pub organization_id: Option<String>,
+}
+
+impl SessionMetadata {
+    /// Creates the metadata of a new session.
+    ///
+    /// # Arguments
+    /// - `ip_address`: the ip address the session was created from.
+    /// - `user_agent`: the `User-Agent` header of the request.
+    ///
+    /// # Examples
+    /// ```
+    /// let metadata = SessionMetadata::new(Some("127.0.0.1"), Some("Mozilla/5.0 (Windows NT 10.0; Win64; x64) ... Chrome/120.0"));
+    /// assert_eq!(metadata.device, "Chrome on Windows");
+    /// ```
+    pub fn new(ip_address: Option<&str>, user_agent: Option<&str>) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        Self {
+            session_id: Uuid::new_v4().to_string(),
+            created_at: now,
+            last_seen: now,
+            expires_at: 0,
+            ip_address: ip_address.map(String::from),
+            user_agent: user_agent.map(String::from),
+            device: Self::device_label(user_agent.unwrap_or_default()),
+            organization_id: None,
+        }
+    }
+
+    /// Builds a readable label ex: "Firefox on Linux" out of a user agent.
+    ///
+    /// # Arguments
+    /// - `user_agent`: the `User-Agent` header of the request.
+    fn device_label(user_agent: &str) -> String {
+        // order matters, most browsers also claim to be chrome and safari.
+        let browser = [
+            ("Edg/", "Edge"),
+            ("OPR/", "Opera"),
+            ("Firefox/", "Firefox"),
+            ("Chrome/", "Chrome"),
+            ("Safari/", "Safari"),
+        ]
+        .iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, name)| *name);
+        let os = [
+            ("Android", "Android"),
+            ("iPhone", "iOS"),
+            ("iPad", "iPadOS"),
+            ("Windows", "Windows"),
+            ("Mac OS X", "macOS"),
+            ("Linux", "Linux"),
+        ]
+        .iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, name)| *name);
+        match (browser, os) {
+            (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+            (Some(browser), None) => String::from(browser),
+            (None, Some(os)) => String::from(os),
+            (None, None) => String::from("Unknown device"),
+        }
+    }
+}
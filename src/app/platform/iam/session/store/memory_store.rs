@@ -0,0 +1,421 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::async_trait;
+
+use super::{RefreshRecord, SessionRecord, SessionStore, SessionStoreResult};
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// A stored item along with when it expires, in milliseconds.
+struct Expiring<T> {
+    item: T,
+    expires_at: i64,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    sessions: HashMap<String, Expiring<SessionRecord>>,
+    refresh_tokens: HashMap<String, Expiring<RefreshRecord>>,
+    /// The digests of the refresh tokens that were used.
+    used_refresh_tokens: HashMap<String, i64>,
+    security_stamps: HashMap<String, String>,
+}
+
+impl MemoryState {
+    /// Drops whatever expired.
+    fn prune(&mut self, now: i64) {
+        self.sessions.retain(|_, session| session.expires_at > now);
+        self.refresh_tokens
+            .retain(|_, refresh| refresh.expires_at > now);
+        self.used_refresh_tokens
+            .retain(|_, expires_at| *expires_at > now);
+    }
+
+    /// Drops the sessions and refresh tokens matching `revoked`, whether any were.
+    fn revoke_where(&mut self, revoked: impl Fn(&str, &str) -> bool) -> bool {
+        let count = self.sessions.len() + self.refresh_tokens.len();
+        self.sessions.retain(|_, session| {
+            !revoked(&session.item.user_id, &session.item.metadata.session_id)
+        });
+        self.refresh_tokens.retain(|_, refresh| {
+            !revoked(&refresh.item.user_id, &refresh.item.metadata.session_id)
+        });
+        count != self.sessions.len() + self.refresh_tokens.len()
+    }
+}
+
+/// Keeps sessions in a map of the process, they are lost on restart and not
+/// shared between instances.
+#[derive(Default)]
+pub struct MemorySessionStore {
+    state: Mutex<MemoryState>,
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn create(
+        &self,
+        session: &SessionRecord,
+        refresh: &RefreshRecord,
+        expires_in: i64,
+        max_lifetime: i64,
+    ) -> SessionStoreResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let now = now();
+        state.prune(now);
+        state.sessions.insert(
+            session.digest.clone(),
+            Expiring {
+                item: session.clone(),
+                expires_at: now + expires_in * 1000,
+            },
+        );
+        state.refresh_tokens.insert(
+            refresh.digest.clone(),
+            Expiring {
+                item: refresh.clone(),
+                expires_at: now + max_lifetime * 1000,
+            },
+        );
+        // the stamp is only ever rotated through `set_security_stamp`.
+        state
+            .security_stamps
+            .entry(session.user_id.clone())
+            .or_insert_with(|| session.security_stamp.clone());
+        Ok(())
+    }
+
+    async fn get(&self, digest: &str) -> SessionStoreResult<Option<SessionRecord>> {
+        let state = self.state.lock().unwrap();
+        let now = now();
+        Ok(state
+            .sessions
+            .get(digest)
+            .filter(|session| session.expires_at > now)
+            .map(|session| session.item.clone()))
+    }
+
+    async fn touch(&self, session: &SessionRecord, expires_in: i64) -> SessionStoreResult<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(stored) = state.sessions.get_mut(&session.digest) {
+            stored.item = session.clone();
+            stored.expires_at = now() + expires_in * 1000;
+        }
+        Ok(())
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> SessionStoreResult<Vec<SessionRecord>> {
+        let state = self.state.lock().unwrap();
+        let now = now();
+        Ok(state
+            .sessions
+            .values()
+            .filter(|session| session.expires_at > now && session.item.user_id.eq(user_id))
+            .map(|session| session.item.clone())
+            .collect())
+    }
+
+    async fn revoke(&self, user_id: &str, session_id: &str) -> SessionStoreResult<bool> {
+        let mut state = self.state.lock().unwrap();
+        state.prune(now());
+        Ok(state.revoke_where(|owner, family| owner.eq(user_id) && family.eq(session_id)))
+    }
+
+    async fn revoke_by_user(&self, user_id: &str) -> SessionStoreResult<bool> {
+        let mut state = self.state.lock().unwrap();
+        state.prune(now());
+        Ok(state.revoke_where(|owner, _| owner.eq(user_id)))
+    }
+
+    async fn get_refresh(&self, digest: &str) -> SessionStoreResult<Option<RefreshRecord>> {
+        let state = self.state.lock().unwrap();
+        let now = now();
+        Ok(state
+            .refresh_tokens
+            .get(digest)
+            .filter(|refresh| refresh.expires_at > now)
+            .map(|refresh| refresh.item.clone()))
+    }
+
+    async fn consume_refresh(
+        &self,
+        digest: &str,
+        remaining_lifetime: i64,
+    ) -> SessionStoreResult<bool> {
+        let mut state = self.state.lock().unwrap();
+        let now = now();
+        state.prune(now);
+        if state.used_refresh_tokens.contains_key(digest) {
+            return Ok(false);
+        }
+        state
+            .used_refresh_tokens
+            .insert(String::from(digest), now + remaining_lifetime * 1000);
+        Ok(true)
+    }
+
+    async fn rotate(
+        &self,
+        previous_digest: &str,
+        session: &SessionRecord,
+        refresh: &RefreshRecord,
+        expires_in: i64,
+        remaining_lifetime: i64,
+    ) -> SessionStoreResult<()> {
+        let mut state = self.state.lock().unwrap();
+        let now = now();
+        state.sessions.remove(previous_digest);
+        state.sessions.insert(
+            session.digest.clone(),
+            Expiring {
+                item: session.clone(),
+                expires_at: now + expires_in * 1000,
+            },
+        );
+        state.refresh_tokens.insert(
+            refresh.digest.clone(),
+            Expiring {
+                item: refresh.clone(),
+                expires_at: now + remaining_lifetime * 1000,
+            },
+        );
+        Ok(())
+    }
+
+    async fn security_stamp(&self, user_id: &str) -> SessionStoreResult<Option<String>> {
+        let state = self.state.lock().unwrap();
+        Ok(state.security_stamps.get(user_id).cloned())
+    }
+
+    async fn set_security_stamp(
+        &self,
+        user_id: &str,
+        security_stamp: &str,
+    ) -> SessionStoreResult<()> {
+        let mut state = self.state.lock().unwrap();
+        // a user without a stamp has no session to invalidate.
+        if let Some(stored) = state.security_stamps.get_mut(user_id) {
+            *stored = String::from(security_stamp);
+        }
+        Ok(())
+    }
+
+    async fn active_families(&self, user_id: &str, since: i64) -> SessionStoreResult<Vec<String>> {
+        let mut state = self.state.lock().unwrap();
+        state.prune(now());
+        let mut families: HashMap<&str, i64> = HashMap::new();
+        for refresh in state.refresh_tokens.values() {
+            let metadata = &refresh.item.metadata;
+            if refresh.item.user_id.eq(user_id) && metadata.created_at > since {
+                families.insert(&metadata.session_id, metadata.created_at);
+            }
+        }
+        let mut families: Vec<(&str, i64)> = families.into_iter().collect();
+        families.sort_by_key(|(_, created_at)| *created_at);
+        Ok(families
+            .into_iter()
+            .map(|(session_id, _)| String::from(session_id))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{now, MemorySessionStore};
+    use crate::app::platform::iam::session::{
+        model::SessionMetadata,
+        store::{RefreshRecord, SessionRecord, SessionStore},
+    };
+
+    fn metadata(session_id: &str, created_at: i64) -> SessionMetadata {
+        SessionMetadata {
+            session_id: String::from(session_id),
+            created_at,
+            last_seen: created_at,
+            ..Default::default()
+        }
+    }
+
+    fn session(digest: &str, user_id: &str, metadata: &SessionMetadata) -> SessionRecord {
+        SessionRecord {
+            digest: String::from(digest),
+            user_id: String::from(user_id),
+            security_stamp: String::from("stamp"),
+            metadata: metadata.clone(),
+        }
+    }
+
+    fn refresh(digest: &str, session: &SessionRecord) -> RefreshRecord {
+        RefreshRecord {
+            digest: String::from(digest),
+            user_id: session.user_id.clone(),
+            session_digest: session.digest.clone(),
+            security_stamp: session.security_stamp.clone(),
+            metadata: session.metadata.clone(),
+        }
+    }
+
+    /// Creates a session of `user_id` in the family `session_id`, returns its
+    /// record and the one of its refresh token.
+    async fn create(
+        store: &MemorySessionStore,
+        user_id: &str,
+        session_id: &str,
+        created_at: i64,
+    ) -> (SessionRecord, RefreshRecord) {
+        let session = session(
+            &format!("session-{}", session_id),
+            user_id,
+            &metadata(session_id, created_at),
+        );
+        let refresh = refresh(&format!("refresh-{}", session_id), &session);
+        store.create(&session, &refresh, 60, 3600).await.unwrap();
+        (session, refresh)
+    }
+
+    #[tokio::test]
+    async fn create_get_and_touch() {
+        let store = MemorySessionStore::default();
+        let (mut session, refresh) = create(&store, "user", "family", now()).await;
+
+        let stored = store.get(&session.digest).await.unwrap().unwrap();
+        assert_eq!(stored.metadata, session.metadata);
+        assert!(store.get("unknown").await.unwrap().is_none());
+        assert_eq!(
+            store
+                .get_refresh(&refresh.digest)
+                .await
+                .unwrap()
+                .unwrap()
+                .session_digest,
+            session.digest
+        );
+        assert_eq!(store.list_by_user("user").await.unwrap().len(), 1);
+
+        session.metadata.last_seen += 1000;
+        store.touch(&session, 60).await.unwrap();
+        let stored = store.get(&session.digest).await.unwrap().unwrap();
+        assert_eq!(stored.metadata.last_seen, session.metadata.last_seen);
+    }
+
+    #[tokio::test]
+    async fn create_keeps_the_stored_security_stamp() {
+        let store = MemorySessionStore::default();
+        assert!(store.security_stamp("user").await.unwrap().is_none());
+        store.set_security_stamp("user", "ignored").await.unwrap();
+        assert!(store.security_stamp("user").await.unwrap().is_none());
+
+        create(&store, "user", "first", now()).await;
+        store.set_security_stamp("user", "rotated").await.unwrap();
+        create(&store, "user", "second", now()).await;
+        assert_eq!(
+            store.security_stamp("user").await.unwrap().as_deref(),
+            Some("rotated")
+        );
+    }
+
+    #[tokio::test]
+    async fn revoke_drops_the_family_only() {
+        let store = MemorySessionStore::default();
+        let (first, first_refresh) = create(&store, "user", "first", now()).await;
+        let (second, _) = create(&store, "user", "second", now()).await;
+        let (other, _) = create(&store, "other", "third", now()).await;
+
+        assert!(store.revoke("user", "first").await.unwrap());
+        assert!(!store.revoke("user", "first").await.unwrap());
+        assert!(!store.revoke("other", "second").await.unwrap());
+        assert!(store.get(&first.digest).await.unwrap().is_none());
+        assert!(store
+            .get_refresh(&first_refresh.digest)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store.get(&second.digest).await.unwrap().is_some());
+
+        assert!(store.revoke_by_user("user").await.unwrap());
+        assert!(store.get(&second.digest).await.unwrap().is_none());
+        assert!(store.get(&other.digest).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn consume_refresh_only_once() {
+        let store = MemorySessionStore::default();
+        let (_, refresh) = create(&store, "user", "family", now()).await;
+
+        assert!(store.consume_refresh(&refresh.digest, 3600).await.unwrap());
+        assert!(!store.consume_refresh(&refresh.digest, 3600).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn rotate_replaces_the_session_and_keeps_the_family() {
+        let store = MemorySessionStore::default();
+        let (previous, previous_refresh) = create(&store, "user", "family", now()).await;
+        let next = session("session-next", "user", &previous.metadata);
+        let next_refresh = refresh("refresh-next", &next);
+
+        assert!(store
+            .consume_refresh(&previous_refresh.digest, 3600)
+            .await
+            .unwrap());
+        store
+            .rotate(&previous.digest, &next, &next_refresh, 60, 3600)
+            .await
+            .unwrap();
+
+        assert!(store.get(&previous.digest).await.unwrap().is_none());
+        assert!(store.get(&next.digest).await.unwrap().is_some());
+        assert_eq!(
+            store
+                .get_refresh(&next_refresh.digest)
+                .await
+                .unwrap()
+                .unwrap()
+                .session_digest,
+            next.digest
+        );
+        assert!(!store
+            .consume_refresh(&previous_refresh.digest, 3600)
+            .await
+            .unwrap());
+        assert_eq!(
+            store.active_families("user", 0).await.unwrap(),
+            vec!["family"]
+        );
+
+        assert!(store.revoke("user", "family").await.unwrap());
+        assert!(store.get(&next.digest).await.unwrap().is_none());
+        assert!(store
+            .get_refresh(&previous_refresh.digest)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn active_families_oldest_first() {
+        let store = MemorySessionStore::default();
+        let now = now();
+        create(&store, "user", "newest", now).await;
+        create(&store, "user", "oldest", now - 2000).await;
+        create(&store, "user", "middle", now - 1000).await;
+        create(&store, "other", "foreign", now - 1500).await;
+
+        assert_eq!(
+            store.active_families("user", 0).await.unwrap(),
+            vec!["oldest", "middle", "newest"]
+        );
+        assert_eq!(
+            store.active_families("user", now - 1500).await.unwrap(),
+            vec!["middle", "newest"]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::{env, sync::Arc};
+
+use axum::async_trait;
+use bb8_postgres::tokio_postgres;
+use bb8_redis::redis::RedisError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::app::database::{postgres::PostgresDatabase, redis::RedisDatabase};
+
+use self::{
+    memory_store::MemorySessionStore, postgres_store::PostgresSessionStore,
+    redis_store::RedisSessionStore,
+};
+
+use super::model::SessionMetadata;
+
+pub mod memory_store;
+pub mod postgres_store;
+pub mod redis_store;
+
+/// A session as it is stored, the owner of the session and its metadata.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    /// The digest of the session token.
+    pub digest: String,
+    pub user_id: String,
+    /// The security stamp of the user when the session was issued.
+    #[serde(default)]
+    pub security_stamp: String,
+    #[serde(flatten)]
+    pub metadata: SessionMetadata,
+}
+
+/// A refresh token as it is stored, enough to issue the next session of the
+/// family once the current one expired.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RefreshRecord {
+    /// The digest of the refresh token.
+    pub digest: String,
+    pub user_id: String,
+    /// The digest of the session issued alongside this refresh token.
+    pub session_digest: String,
+    #[serde(default)]
+    pub security_stamp: String,
+    pub metadata: SessionMetadata,
+}
+
+#[derive(Error, Debug)]
+pub enum SessionStoreError {
+    #[error("SessionStoreRedis: {0}")]
+    Redis(#[from] RedisError),
+    #[error("SessionStorePostgres: {0}")]
+    Postgres(#[from] tokio_postgres::Error),
+    #[error("SessionStoreSerialization: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+pub type SessionStoreResult<T> = Result<T, SessionStoreError>;
+
+/// The store the session tasks run against, picked once at startup.
+pub type SharedSessionStore = Arc<dyn SessionStore>;
+
+/// Where sessions, their refresh tokens and the security stamps they were
+/// issued under are kept.
+///
+/// A store only keeps records, expiring them once their time is up, what is
+/// valid (the lifetime, the security stamp, refresh token reuse) is decided by
+/// the session tasks. Sessions are identified by the digest of their token and
+/// grouped into families by their `session_id`, a family being a session and
+/// every refresh token issued for it. Times are in seconds.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// Stores a new session along with the first refresh token of its family.
+    /// The security stamp of the session becomes the one of the user only
+    /// when the user has none, `set_security_stamp` is what rotates it.
+    ///
+    /// # Arguments
+    /// * `session` - The session, it expires after `expires_in`.
+    /// * `refresh` - Its refresh token, it expires after `max_lifetime`.
+    ///
+    /// # Examples
+    /// ```
+    /// store.create(&session, &refresh, 86400, 604800).await?;
+    /// ```
+    async fn create(
+        &self,
+        session: &SessionRecord,
+        refresh: &RefreshRecord,
+        expires_in: i64,
+        max_lifetime: i64,
+    ) -> SessionStoreResult<()>;
+
+    /// Reads a session by the digest of its token, `None` once it expired or was revoked.
+    ///
+    /// # Examples
+    /// ```
+    /// let session = store.get(&digest).await?;
+    /// ```
+    async fn get(&self, digest: &str) -> SessionStoreResult<Option<SessionRecord>>;
+
+    /// Writes the metadata of a session back and pushes its expiry `expires_in`
+    /// forward, a session revoked in the meantime stays revoked.
+    ///
+    /// # Examples
+    /// ```
+    /// store.touch(&session, 86400).await?;
+    /// ```
+    async fn touch(&self, session: &SessionRecord, expires_in: i64) -> SessionStoreResult<()>;
+
+    /// Reads every session of a user that has not expired.
+    ///
+    /// # Examples
+    /// ```
+    /// let sessions = store.list_by_user(&user_id).await?;
+    /// ```
+    async fn list_by_user(&self, user_id: &str) -> SessionStoreResult<Vec<SessionRecord>>;
+
+    /// Revokes a session and every refresh token of its family, `false` when
+    /// the user has no such family.
+    ///
+    /// # Examples
+    /// ```
+    /// let found = store.revoke(&user_id, &session_id).await?;
+    /// ```
+    async fn revoke(&self, user_id: &str, session_id: &str) -> SessionStoreResult<bool>;
+
+    /// Revokes every session and refresh token of a user, `false` when there was none.
+    ///
+    /// # Examples
+    /// ```
+    /// let found = store.revoke_by_user(&user_id).await?;
+    /// ```
+    async fn revoke_by_user(&self, user_id: &str) -> SessionStoreResult<bool>;
+
+    /// Reads a refresh token by its digest, used ones included until they expire.
+    ///
+    /// # Examples
+    /// ```
+    /// let refresh = store.get_refresh(&digest).await?;
+    /// ```
+    async fn get_refresh(&self, digest: &str) -> SessionStoreResult<Option<RefreshRecord>>;
+
+    /// Marks a refresh token as used, `false` when it already was. The mark is
+    /// kept for `remaining_lifetime` so that reuse is noticed.
+    ///
+    /// # Examples
+    /// ```
+    /// let first_use = store.consume_refresh(&digest, 3600).await?;
+    /// ```
+    async fn consume_refresh(
+        &self,
+        digest: &str,
+        remaining_lifetime: i64,
+    ) -> SessionStoreResult<bool>;
+
+    /// Replaces the session `previous_digest` with the next one of its family.
+    ///
+    /// # Arguments
+    /// * `session` - The new session, it expires after `expires_in`.
+    /// * `refresh` - Its refresh token, it expires after `remaining_lifetime`.
+    ///
+    /// # Examples
+    /// ```
+    /// store.rotate(&previous_digest, &session, &refresh, 86400, 3600).await?;
+    /// ```
+    async fn rotate(
+        &self,
+        previous_digest: &str,
+        session: &SessionRecord,
+        refresh: &RefreshRecord,
+        expires_in: i64,
+        remaining_lifetime: i64,
+    ) -> SessionStoreResult<()>;
+
+    /// Reads the current security stamp of a user.
+    ///
+    /// # Examples
+    /// ```
+    /// let security_stamp = store.security_stamp(&user_id).await?;
+    /// ```
+    async fn security_stamp(&self, user_id: &str) -> SessionStoreResult<Option<String>>;
+
+    /// Replaces the security stamp of a user who has one.
+    ///
+    /// # Examples
+    /// ```
+    /// store.set_security_stamp(&user_id, &security_stamp).await?;
+    /// ```
+    async fn set_security_stamp(
+        &self,
+        user_id: &str,
+        security_stamp: &str,
+    ) -> SessionStoreResult<()>;
+
+    /// Reads the `session_id` of every family of a user created after `since`
+    /// (milliseconds), oldest first.
+    ///
+    /// # Examples
+    /// ```
+    /// let families = store.active_families(&user_id, since).await?;
+    /// ```
+    async fn active_families(&self, user_id: &str, since: i64) -> SessionStoreResult<Vec<String>>;
+
+    /// Clears whatever an older version of the store left behind, run once at startup.
+    async fn purge_legacy(&self) -> SessionStoreResult<()> {
+        Ok(())
+    }
+}
+
+/// Picks the session store named by `SESSION_STORE`, `redis` (the default),
+/// `postgres` or `memory`. The memory store only lives as long as the process
+/// and is not shared between instances.
+///
+/// # Arguments
+/// * `postgres` - The postgres database, used by the postgres store.
+/// * `redis` - The redis database, used by the redis store.
+///
+/// # Examples
+/// ```
+/// let sessions = session_store_from_env(&postgres, &redis);
+/// ```
+pub fn session_store_from_env(
+    postgres: &PostgresDatabase,
+    redis: &RedisDatabase,
+) -> SharedSessionStore {
+    match env::var("SESSION_STORE")
+        .unwrap_or_else(|_| String::from("redis"))
+        .as_str()
+    {
+        "redis" => Arc::new(RedisSessionStore::new(redis.clone())),
+        "postgres" => Arc::new(PostgresSessionStore::new(postgres.clone())),
+        "memory" => Arc::new(MemorySessionStore::default()),
+        store => panic!("SESSION_STORE: unknown session store {}", store),
+    }
+}
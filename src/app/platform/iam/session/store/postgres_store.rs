@@ -0,0 +1,281 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::async_trait;
+use bb8_postgres::tokio_postgres::Row;
+
+use crate::app::database::postgres::PostgresDatabase;
+
+use super::{RefreshRecord, SessionRecord, SessionStore, SessionStoreResult};
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+fn session_from_row(row: &Row) -> SessionStoreResult<SessionRecord> {
+    Ok(SessionRecord {
+        digest: row.get(0),
+        user_id: row.get(1),
+        security_stamp: row.get(2),
+        metadata: serde_json::from_str(row.get(3))?,
+    })
+}
+
+/// Keeps sessions in `iam_sessions` and their refresh tokens in
+/// `iam_session_refresh_tokens`, expired rows are skipped and cleared when
+/// the user signs in again. The security stamp is the one of `iam_users`.
+pub struct PostgresSessionStore {
+    db: PostgresDatabase,
+}
+
+impl PostgresSessionStore {
+    pub fn new(db: PostgresDatabase) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl SessionStore for PostgresSessionStore {
+    async fn create(
+        &self,
+        session: &SessionRecord,
+        refresh: &RefreshRecord,
+        expires_in: i64,
+        max_lifetime: i64,
+    ) -> SessionStoreResult<()> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let now = now();
+        let transaction = pool.transaction().await?;
+        transaction
+            .execute(
+                "DELETE FROM iam_sessions WHERE user_id = $1 AND expires_at <= $2",
+                &[&session.user_id, &now],
+            )
+            .await?;
+        transaction
+            .execute(
+                "DELETE FROM iam_session_refresh_tokens WHERE user_id = $1 AND expires_at <= $2",
+                &[&session.user_id, &now],
+            )
+            .await?;
+        transaction
+            .execute(
+                "INSERT INTO iam_sessions (digest, user_id, security_stamp, metadata, session_id, expires_at) VALUES ($1, $2, $3, $4, $5, $6)",
+                &[
+                    &session.digest,
+                    &session.user_id,
+                    &session.security_stamp,
+                    &serde_json::to_string(&session.metadata)?,
+                    &session.metadata.session_id,
+                    &(now + expires_in * 1000),
+                ],
+            )
+            .await?;
+        transaction
+            .execute(
+                "INSERT INTO iam_session_refresh_tokens (digest, user_id, security_stamp, metadata, session_id, session_digest, created_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &refresh.digest,
+                    &refresh.user_id,
+                    &refresh.security_stamp,
+                    &serde_json::to_string(&refresh.metadata)?,
+                    &refresh.metadata.session_id,
+                    &refresh.session_digest,
+                    &refresh.metadata.created_at,
+                    &(now + max_lifetime * 1000),
+                ],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn get(&self, digest: &str) -> SessionStoreResult<Option<SessionRecord>> {
+        let pool = self.db.pool.get().await.unwrap();
+        let row = pool
+            .query_opt(
+                "SELECT digest, user_id, security_stamp, metadata FROM iam_sessions WHERE digest = $1 AND expires_at > $2",
+                &[&digest, &now()],
+            )
+            .await?;
+        row.map(|row| session_from_row(&row)).transpose()
+    }
+
+    async fn touch(&self, session: &SessionRecord, expires_in: i64) -> SessionStoreResult<()> {
+        let pool = self.db.pool.get().await.unwrap();
+        pool.execute(
+            "UPDATE iam_sessions SET metadata = $2, expires_at = $3 WHERE digest = $1",
+            &[
+                &session.digest,
+                &serde_json::to_string(&session.metadata)?,
+                &(now() + expires_in * 1000),
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> SessionStoreResult<Vec<SessionRecord>> {
+        let pool = self.db.pool.get().await.unwrap();
+        let rows = pool
+            .query(
+                "SELECT digest, user_id, security_stamp, metadata FROM iam_sessions WHERE user_id = $1 AND expires_at > $2",
+                &[&user_id, &now()],
+            )
+            .await?;
+        rows.iter().map(session_from_row).collect()
+    }
+
+    async fn revoke(&self, user_id: &str, session_id: &str) -> SessionStoreResult<bool> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let transaction = pool.transaction().await?;
+        let sessions = transaction
+            .execute(
+                "DELETE FROM iam_sessions WHERE user_id = $1 AND session_id = $2",
+                &[&user_id, &session_id],
+            )
+            .await?;
+        let refresh_tokens = transaction
+            .execute(
+                "DELETE FROM iam_session_refresh_tokens WHERE user_id = $1 AND session_id = $2",
+                &[&user_id, &session_id],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(sessions > 0 || refresh_tokens > 0)
+    }
+
+    async fn revoke_by_user(&self, user_id: &str) -> SessionStoreResult<bool> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let transaction = pool.transaction().await?;
+        let sessions = transaction
+            .execute("DELETE FROM iam_sessions WHERE user_id = $1", &[&user_id])
+            .await?;
+        let refresh_tokens = transaction
+            .execute(
+                "DELETE FROM iam_session_refresh_tokens WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(sessions > 0 || refresh_tokens > 0)
+    }
+
+    async fn get_refresh(&self, digest: &str) -> SessionStoreResult<Option<RefreshRecord>> {
+        let pool = self.db.pool.get().await.unwrap();
+        let row = pool
+            .query_opt(
+                "SELECT digest, user_id, security_stamp, metadata, session_digest FROM iam_session_refresh_tokens WHERE digest = $1 AND expires_at > $2",
+                &[&digest, &now()],
+            )
+            .await?;
+        match row {
+            Some(row) => Ok(Some(RefreshRecord {
+                digest: row.get(0),
+                user_id: row.get(1),
+                security_stamp: row.get(2),
+                metadata: serde_json::from_str(row.get(3))?,
+                session_digest: row.get(4),
+            })),
+            None => Ok(None),
+        }
+    }
+
+    async fn consume_refresh(
+        &self,
+        digest: &str,
+        _remaining_lifetime: i64,
+    ) -> SessionStoreResult<bool> {
+        let pool = self.db.pool.get().await.unwrap();
+        // the row is kept until it expires, it is how reuse is recognized.
+        let consumed = pool
+            .execute(
+                "UPDATE iam_session_refresh_tokens SET used = TRUE WHERE digest = $1 AND used = FALSE",
+                &[&digest],
+            )
+            .await?;
+        Ok(consumed == 1)
+    }
+
+    async fn rotate(
+        &self,
+        previous_digest: &str,
+        session: &SessionRecord,
+        refresh: &RefreshRecord,
+        expires_in: i64,
+        remaining_lifetime: i64,
+    ) -> SessionStoreResult<()> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let now = now();
+        let transaction = pool.transaction().await?;
+        transaction
+            .execute(
+                "DELETE FROM iam_sessions WHERE digest = $1",
+                &[&previous_digest],
+            )
+            .await?;
+        transaction
+            .execute(
+                "INSERT INTO iam_sessions (digest, user_id, security_stamp, metadata, session_id, expires_at) VALUES ($1, $2, $3, $4, $5, $6)",
+                &[
+                    &session.digest,
+                    &session.user_id,
+                    &session.security_stamp,
+                    &serde_json::to_string(&session.metadata)?,
+                    &session.metadata.session_id,
+                    &(now + expires_in * 1000),
+                ],
+            )
+            .await?;
+        transaction
+            .execute(
+                "INSERT INTO iam_session_refresh_tokens (digest, user_id, security_stamp, metadata, session_id, session_digest, created_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+                &[
+                    &refresh.digest,
+                    &refresh.user_id,
+                    &refresh.security_stamp,
+                    &serde_json::to_string(&refresh.metadata)?,
+                    &refresh.metadata.session_id,
+                    &refresh.session_digest,
+                    &refresh.metadata.created_at,
+                    &(now + remaining_lifetime * 1000),
+                ],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn security_stamp(&self, user_id: &str) -> SessionStoreResult<Option<String>> {
+        let pool = self.db.pool.get().await.unwrap();
+        let row = pool
+            .query_opt(
+                "SELECT COALESCE(security_stamp, '') FROM iam_users WHERE id = $1",
+                &[&user_id],
+            )
+            .await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    async fn set_security_stamp(
+        &self,
+        _user_id: &str,
+        _security_stamp: &str,
+    ) -> SessionStoreResult<()> {
+        // the stamp is read from iam_users, where it was already rotated.
+        Ok(())
+    }
+
+    async fn active_families(&self, user_id: &str, since: i64) -> SessionStoreResult<Vec<String>> {
+        let pool = self.db.pool.get().await.unwrap();
+        let rows = pool
+            .query(
+                "SELECT session_id FROM iam_session_refresh_tokens WHERE user_id = $1 AND created_at > $2 GROUP BY session_id ORDER BY MIN(created_at)",
+                &[&user_id, &since],
+            )
+            .await?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+}
@@ -0,0 +1,354 @@
+use std::mem;
+
+use axum::async_trait;
+use bb8_redis::redis::{self, AsyncCommands, AsyncIter};
+
+use crate::app::database::redis::RedisDatabase;
+
+use super::{RefreshRecord, SessionRecord, SessionStore, SessionStoreResult};
+
+/// Set once the sessions stored under their raw token have been purged.
+static LEGACY_PURGE_MARKER: &str = "session-migration:hashed-tokens";
+
+fn session_key(digest: &str) -> String {
+    format!("session:{}", digest)
+}
+
+/// The set holding the token digests of every session of a user.
+fn user_sessions_key(user_id: &str) -> String {
+    format!("user-sessions:{}", user_id)
+}
+
+/// Holds the current security stamp of a user, sessions issued under another
+/// stamp are no longer valid. It lives at least as long as the sessions of the user.
+fn user_stamp_key(user_id: &str) -> String {
+    format!("user-stamp:{}", user_id)
+}
+
+/// Whether a key suffix is the SHA-256 digest of a token rather than the token.
+fn is_digest(suffix: &str) -> bool {
+    suffix.len() == 64 && suffix.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn refresh_key(digest: &str) -> String {
+    format!("refresh:{}", digest)
+}
+
+/// Set once a refresh token has been used, it is kept so that reuse is noticed.
+fn refresh_used_key(digest: &str) -> String {
+    format!("refresh-used:{}", digest)
+}
+
+/// The set holding the digests of every refresh token issued for a session.
+fn refresh_family_key(session_id: &str) -> String {
+    format!("refresh-family:{}", session_id)
+}
+
+/// The sorted set holding the `session_id` of every refresh token family of a
+/// user, scored by when the session was created. A family lives as long as
+/// the sessions it issues may, it is how active sessions are counted.
+fn user_families_key(user_id: &str) -> String {
+    format!("user-session-families:{}", user_id)
+}
+
+/// Keeps sessions in redis, under `session:{digest}` and indexed per user so
+/// that nothing scans the keyspace. Shared by every instance.
+pub struct RedisSessionStore {
+    db: RedisDatabase,
+}
+
+impl RedisSessionStore {
+    pub fn new(db: RedisDatabase) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn create(
+        &self,
+        session: &SessionRecord,
+        refresh: &RefreshRecord,
+        expires_in: i64,
+        max_lifetime: i64,
+    ) -> SessionStoreResult<()> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let index_key = user_sessions_key(&session.user_id);
+        let family_key = refresh_family_key(&session.metadata.session_id);
+        let families_key = user_families_key(&session.user_id);
+        // the newest session lives the longest, the indexes expire along with it.
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(
+                session_key(&session.digest),
+                serde_json::to_string(session)?,
+                expires_in as u64,
+            )
+            .ignore()
+            .sadd(&index_key, &session.digest)
+            .ignore()
+            .expire(&index_key, max_lifetime)
+            .ignore()
+            // the refresh token lives as long as the session may.
+            .set_ex(
+                refresh_key(&refresh.digest),
+                serde_json::to_string(refresh)?,
+                max_lifetime as u64,
+            )
+            .ignore()
+            .sadd(&family_key, &refresh.digest)
+            .ignore()
+            .expire(&family_key, max_lifetime)
+            .ignore()
+            .zadd(
+                &families_key,
+                &session.metadata.session_id,
+                session.metadata.created_at,
+            )
+            .ignore()
+            .expire(&families_key, max_lifetime)
+            .ignore()
+            // the stamp is only ever rotated through `set_security_stamp`, a
+            // session issued under a stamp rotated in the meantime stays invalid.
+            .cmd("SET")
+            .arg(user_stamp_key(&session.user_id))
+            .arg(&session.security_stamp)
+            .arg("NX")
+            .ignore()
+            .expire(user_stamp_key(&session.user_id), max_lifetime)
+            .ignore()
+            .query_async(&mut *pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, digest: &str) -> SessionStoreResult<Option<SessionRecord>> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let record: Option<String> = pool.get(session_key(digest)).await?;
+        Ok(record.and_then(|record| serde_json::from_str(&record).ok()))
+    }
+
+    async fn touch(&self, session: &SessionRecord, expires_in: i64) -> SessionStoreResult<()> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        // XX so that a session revoked in the meantime is not written back.
+        let _: Option<String> = redis::cmd("SET")
+            .arg(session_key(&session.digest))
+            .arg(serde_json::to_string(session)?)
+            .arg("XX")
+            .arg("EX")
+            .arg(expires_in)
+            .query_async(&mut *pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> SessionStoreResult<Vec<SessionRecord>> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let index_key = user_sessions_key(user_id);
+        let digests: Vec<String> = pool.smembers(&index_key).await?;
+        let mut sessions: Vec<SessionRecord> = Vec::new();
+        let mut stale: Vec<String> = Vec::new();
+        for digest in digests.into_iter() {
+            let record: Option<String> = pool.get(session_key(&digest)).await?;
+            match record.and_then(|record| serde_json::from_str::<SessionRecord>(&record).ok()) {
+                Some(record) => sessions.push(record),
+                None => stale.push(digest),
+            }
+        }
+        if !stale.is_empty() {
+            // sessions expire on their own, the index is not notified.
+            let _: () = pool.srem(&index_key, stale).await?;
+        }
+        Ok(sessions)
+    }
+
+    async fn revoke(&self, user_id: &str, session_id: &str) -> SessionStoreResult<bool> {
+        let digests: Vec<String> = self
+            .list_by_user(user_id)
+            .await?
+            .into_iter()
+            .filter(|session| session.metadata.session_id.eq(session_id))
+            .map(|session| session.digest)
+            .collect();
+        let mut pool = self.db.pool.get().await.unwrap();
+        let family_key = refresh_family_key(session_id);
+        let refresh_digests: Vec<String> = pool.smembers(&family_key).await?;
+        let created_at: Option<i64> = pool.zscore(user_families_key(user_id), session_id).await?;
+        if digests.is_empty() && created_at.is_none() {
+            return Ok(false);
+        }
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for digest in digests.iter() {
+            pipe.del(session_key(digest))
+                .ignore()
+                .srem(user_sessions_key(user_id), digest)
+                .ignore();
+        }
+        for digest in refresh_digests.iter() {
+            pipe.del(refresh_key(digest)).ignore();
+        }
+        pipe.del(&family_key)
+            .ignore()
+            .zrem(user_families_key(user_id), session_id)
+            .ignore();
+        let _: () = pipe.query_async(&mut *pool).await?;
+        Ok(true)
+    }
+
+    async fn revoke_by_user(&self, user_id: &str) -> SessionStoreResult<bool> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let index_key = user_sessions_key(user_id);
+        let families_key = user_families_key(user_id);
+        let digests: Vec<String> = pool.smembers(&index_key).await?;
+        let families: Vec<String> = pool.zrange(&families_key, 0, -1).await?;
+        if digests.is_empty() && families.is_empty() {
+            return Ok(false);
+        }
+        // expired ones are simply not there anymore.
+        let mut keys: Vec<String> = digests.iter().map(|digest| session_key(digest)).collect();
+        for session_id in families.iter() {
+            let family_key = refresh_family_key(session_id);
+            let refresh_digests: Vec<String> = pool.smembers(&family_key).await?;
+            keys.extend(refresh_digests.iter().map(|digest| refresh_key(digest)));
+            keys.push(family_key);
+        }
+        keys.push(index_key);
+        keys.push(families_key);
+        let _: () = pool.del(keys).await?;
+        Ok(true)
+    }
+
+    async fn get_refresh(&self, digest: &str) -> SessionStoreResult<Option<RefreshRecord>> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let record: Option<String> = pool.get(refresh_key(digest)).await?;
+        Ok(record.and_then(|record| serde_json::from_str(&record).ok()))
+    }
+
+    async fn consume_refresh(
+        &self,
+        digest: &str,
+        remaining_lifetime: i64,
+    ) -> SessionStoreResult<bool> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let first_use: Option<String> = redis::cmd("SET")
+            .arg(refresh_used_key(digest))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(remaining_lifetime)
+            .query_async(&mut *pool)
+            .await?;
+        Ok(first_use.is_some())
+    }
+
+    async fn rotate(
+        &self,
+        previous_digest: &str,
+        session: &SessionRecord,
+        refresh: &RefreshRecord,
+        expires_in: i64,
+        remaining_lifetime: i64,
+    ) -> SessionStoreResult<()> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let index_key = user_sessions_key(&session.user_id);
+        // the used refresh token is left to expire, it is how reuse is recognized.
+        let _: () = redis::pipe()
+            .atomic()
+            .del(session_key(previous_digest))
+            .ignore()
+            .srem(&index_key, previous_digest)
+            .ignore()
+            .set_ex(
+                session_key(&session.digest),
+                serde_json::to_string(session)?,
+                expires_in as u64,
+            )
+            .ignore()
+            .sadd(&index_key, &session.digest)
+            .ignore()
+            .set_ex(
+                refresh_key(&refresh.digest),
+                serde_json::to_string(refresh)?,
+                remaining_lifetime as u64,
+            )
+            .ignore()
+            .sadd(
+                refresh_family_key(&session.metadata.session_id),
+                &refresh.digest,
+            )
+            .ignore()
+            .query_async(&mut *pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn security_stamp(&self, user_id: &str) -> SessionStoreResult<Option<String>> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        Ok(pool.get(user_stamp_key(user_id)).await?)
+    }
+
+    async fn set_security_stamp(
+        &self,
+        user_id: &str,
+        security_stamp: &str,
+    ) -> SessionStoreResult<()> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        // a user without a stamp has no session to invalidate.
+        let _: Option<String> = redis::cmd("SET")
+            .arg(user_stamp_key(user_id))
+            .arg(security_stamp)
+            .arg("XX")
+            .arg("KEEPTTL")
+            .query_async(&mut *pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn active_families(&self, user_id: &str, since: i64) -> SessionStoreResult<Vec<String>> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let families_key = user_families_key(user_id);
+        let _: () = pool.zrembyscore(&families_key, "-inf", since).await?;
+        Ok(pool.zrange(&families_key, 0, -1).await?)
+    }
+
+    async fn purge_legacy(&self) -> SessionStoreResult<()> {
+        let mut pool = self.db.pool.get().await.unwrap();
+        let purged: bool = pool.exists(LEGACY_PURGE_MARKER).await?;
+        if purged {
+            return Ok(());
+        }
+        // sessions keyed by their raw token (`session:{token}` or `session:{token}:{user_id}`)
+        // and the indexes listing raw tokens are invalidated, their users sign in again.
+        let mut legacy_keys: Vec<String> = Vec::new();
+        let mut scan_result: AsyncIter<String> = pool.scan_match("session:*").await?;
+        while let Some(key) = scan_result.next_item().await {
+            if !is_digest(key.trim_start_matches("session:")) {
+                legacy_keys.push(key);
+            }
+        }
+        mem::drop(scan_result);
+        let mut index_keys: Vec<String> = Vec::new();
+        let mut scan_result: AsyncIter<String> = pool.scan_match("user-sessions:*").await?;
+        while let Some(key) = scan_result.next_item().await {
+            index_keys.push(key);
+        }
+        mem::drop(scan_result);
+        for key in index_keys {
+            // an index that is not a set of digests lists raw tokens.
+            let is_legacy = match pool.smembers::<_, Vec<String>>(&key).await {
+                Ok(members) => !members.iter().all(|member| is_digest(member)),
+                Err(_) => true,
+            };
+            if is_legacy {
+                legacy_keys.push(key);
+            }
+        }
+        for keys in legacy_keys.chunks(500) {
+            let _: () = pool.del(keys).await?;
+        }
+        // only marked once done, a purge that failed partway runs again on the next start.
+        let _: () = pool.set(LEGACY_PURGE_MARKER, 1).await?;
+        Ok(())
+    }
+}
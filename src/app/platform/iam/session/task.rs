@@ -1,11 +1,11 @@
-use std::mem;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use axum::async_trait;
-use bb8_redis::redis::{AsyncCommands, AsyncIter};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use crate::app::{
-    database::redis::RedisDatabase,
+    platform::iam::role::model::{SessionLimitAction, SessionPolicy},
     service::task::{
         error::TaskError,
         message::{TaskRequest, TaskResponse, TaskStatus},
@@ -13,13 +13,16 @@ use crate::app::{
     },
 };
 
-use super::model::UserSession;
+use super::{
+    model::{SessionMetadata, UserSession},
+    store::{RefreshRecord, SessionRecord, SharedSessionStore},
+};
 
 pub struct SessionTaskHandler;
 
 #[async_trait]
-impl TaskHandler<RedisDatabase> for SessionTaskHandler {
-    async fn handle(redis: &RedisDatabase, task_request: TaskRequest) -> TaskResponse {
+impl TaskHandler<SharedSessionStore> for SessionTaskHandler {
+    async fn handle(store: &SharedSessionStore, task_request: TaskRequest) -> TaskResponse {
         if task_request.task_action.eq("session_create") {
             let payload =
                 match TaskRequest::intepret_request_payload::<SessionCreateTask>(&task_request) {
@@ -31,12 +34,13 @@ impl TaskHandler<RedisDatabase> for SessionTaskHandler {
                         )
                     }
                 };
-            return SessionCreateTask::run(redis, task_request, payload).await;
+            return SessionCreateTask::run(store, task_request, payload).await;
         }
-        
+
         if task_request.task_action.eq("session_revocation") {
             let payload =
-                match TaskRequest::intepret_request_payload::<SessionRevocationTask>(&task_request) {
+                match TaskRequest::intepret_request_payload::<SessionRevocationTask>(&task_request)
+                {
                     Ok(p) => p,
                     Err(_) => {
                         return TaskResponse::throw_failed_response(
@@ -45,7 +49,111 @@ impl TaskHandler<RedisDatabase> for SessionTaskHandler {
                         )
                     }
                 };
-            return SessionRevocationTask::run(redis, task_request, payload).await;
+            return SessionRevocationTask::run(store, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("session_list") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<SessionListTask>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return SessionListTask::run(store, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("session_revocation_by_id") {
+            let payload = match TaskRequest::intepret_request_payload::<SessionRevocationByIdTask>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return SessionRevocationByIdTask::run(store, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("session_validation") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<SessionValidationTask>(&task_request)
+                {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return SessionValidationTask::run(store, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("session_refresh") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<SessionRefreshTask>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return SessionRefreshTask::run(store, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("session_enforce_policy") {
+            let payload = match TaskRequest::intepret_request_payload::<SessionEnforcePolicyTask>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return SessionEnforcePolicyTask::run(store, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("session_update_security_stamp") {
+            let payload = match TaskRequest::intepret_request_payload::<
+                SessionUpdateSecurityStampTask,
+            >(&task_request)
+            {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return SessionUpdateSecurityStampTask::run(store, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("session_purge_legacy") {
+            let payload = match TaskRequest::intepret_request_payload::<SessionPurgeLegacyTask>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return SessionPurgeLegacyTask::run(store, task_request, payload).await;
         }
 
         return TaskResponse::throw_failed_response(
@@ -55,112 +163,541 @@ impl TaskHandler<RedisDatabase> for SessionTaskHandler {
     }
 }
 
+/// Digests a session token, the raw token only ever lives in the client cookie.
+///
+/// # Arguments
+/// * `token` - The session token.
+///
+/// # Examples
+/// ```
+/// let digest = token_digest("XXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX");
+/// ```
+fn token_digest(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token);
+    hex::encode(hasher.finalize())
+}
+
+/// Compares two digests without leaking where they differ through timing.
+fn digest_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SessionCreateTask {
     pub token: String,
-    pub expires_in: i64,
+    pub refresh_token: String,
+    pub idle_timeout: i64,
+    pub max_lifetime: i64,
     pub user_id: String,
+    pub security_stamp: String,
+    pub metadata: SessionMetadata,
 }
 
 #[async_trait]
-impl Task<RedisDatabase, TaskRequest, SessionCreateTask> for SessionCreateTask {
+impl Task<SharedSessionStore, TaskRequest, SessionCreateTask> for SessionCreateTask {
     async fn run(
-        db: &RedisDatabase,
+        store: &SharedSessionStore,
         request: TaskRequest,
         param: SessionCreateTask,
     ) -> TaskResponse {
-        let mut pool = db.pool.get().await.unwrap();
-        //let hash: Vec<(String, String)> = pool.hgetall("user-sessions").await.unwrap();
-        //if let Some(existing_key) =
-        //    hash.iter()
-        //        .find_map(|(k, v)| if v.eq(&param.user_id) { Some(k) } else { None })
-        //{
-        //    // If an existing session is found, delete it
-        //    pool.hdel::<&str, &str, ()>("user-sessions", existing_key)
-        //        .await
-        //        .unwrap();
-        //}
-        let pattern = format!("session:*:{}", param.user_id);
-        let mut scan_result: AsyncIter<String> = pool.scan_match(&pattern).await.unwrap();
-        // need to collect into a vec to go around rust borrowing rules
-        let mut sessions_to_invalidate: Vec<String> = Vec::new();
-        while let Some(key_result) = scan_result.next_item().await {
-            sessions_to_invalidate.push(key_result);
-        }
-        // drop scan_result after loop so it can be reused.
-        mem::drop(scan_result);
-        // invalidate any existing session.
-        for key in sessions_to_invalidate.iter() {
-            let _: () = pool.del(key).await.unwrap();
-        }
-        // Set the new session token for the user
-        //  let hset_result = pool
-        //    .hset::<&str, String, String, ()>(
-        //        &session_key,
-        //        param.token.clone(),
-        //        param.user_id.clone(),
-        //    )1
-        //    .await;
-        // session key
-        let session_key = format!("session:{}:{}", param.token, param.user_id).to_string();
-        let hset_result = pool
-            .set::<&str, String, ()>(&session_key, param.user_id.clone())
+        let digest = token_digest(&param.token);
+        let mut metadata = param.metadata;
+        metadata.expires_at = metadata.created_at + param.max_lifetime * 1000;
+        let record = SessionRecord {
+            digest: digest.clone(),
+            user_id: param.user_id.clone(),
+            security_stamp: param.security_stamp.clone(),
+            metadata,
+        };
+        let refresh_record = RefreshRecord {
+            digest: token_digest(&param.refresh_token),
+            user_id: param.user_id.clone(),
+            session_digest: digest,
+            security_stamp: param.security_stamp.clone(),
+            metadata: record.metadata.clone(),
+        };
+        let expires_in = param.idle_timeout.min(param.max_lifetime);
+        // a user has one session per device, the others are left untouched.
+        let create_result = store
+            .create(&record, &refresh_record, expires_in, param.max_lifetime)
             .await;
-        match hset_result {
+        if create_result.is_err() {
+            // should not happen...
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::SessionCreationFailed.to_string()],
+            );
+        }
+        return TaskResponse::compose_response(
+            request,
+            TaskStatus::Completed,
+            UserSession {
+                refresh_token: param.refresh_token,
+                ..UserSession::new(
+                    &param.token,
+                    expires_in,
+                    param.max_lifetime,
+                    &param.user_id,
+                    record.metadata,
+                )
+            },
+            Vec::default(),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionRevocationTask {
+    pub user_id: String,
+}
+
+#[async_trait]
+impl Task<SharedSessionStore, TaskRequest, SessionRevocationTask> for SessionRevocationTask {
+    async fn run(
+        store: &SharedSessionStore,
+        request: TaskRequest,
+        param: SessionRevocationTask,
+    ) -> TaskResponse {
+        match store.revoke_by_user(&param.user_id).await {
+            Ok(true) => {}
+            // return session not found if session is not found by user id.
+            Ok(false) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::SessionNotFound.to_string()],
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::SessionDeletionFailed.to_string()],
+                );
+            }
+        }
+        return TaskResponse::compose_response(
+            request,
+            TaskStatus::Completed,
+            String::default(),
+            Vec::default(),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionValidationTask {
+    pub token: String,
+    pub idle_timeout: i64,
+}
+
+#[async_trait]
+impl Task<SharedSessionStore, TaskRequest, SessionValidationTask> for SessionValidationTask {
+    async fn run(
+        store: &SharedSessionStore,
+        request: TaskRequest,
+        param: SessionValidationTask,
+    ) -> TaskResponse {
+        if param.token.is_empty() {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::SessionNotFound.to_string()],
+            );
+        }
+        let digest = token_digest(&param.token);
+        let mut record = match store.get(&digest).await {
+            Ok(Some(record)) if digest_eq(&record.digest, &digest) => record,
             Ok(_) => {
-                pool.expire::<&str, i64>(&session_key, param.expires_in) // 7 days in seconds
-                    .await
-                    .unwrap();
+                // expired or revoked.
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::SessionNotFound.to_string()],
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
             }
+        };
+        let security_stamp = match store.security_stamp(&record.user_id).await {
+            Ok(security_stamp) => security_stamp,
             Err(_) => {
-                // should not happen...
                 return TaskResponse::throw_failed_response(
                     request,
-                    vec![TaskError::SessionCreationFailed.to_string()],
+                    vec![TaskError::TaskInternalError.to_string()],
                 );
             }
+        };
+        let now = now();
+        let remaining_lifetime = (record.metadata.expires_at - now) / 1000;
+        // issued before the security stamp of the user was rotated, or past its
+        // lifetime however active it was.
+        if security_stamp.as_ref() != Some(&record.security_stamp) || remaining_lifetime <= 0 {
+            let _ = store
+                .revoke(&record.user_id, &record.metadata.session_id)
+                .await;
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::SessionNotFound.to_string()],
+            );
+        }
+        // the idle timeout is pushed forward, never past the lifetime.
+        let expires_in = param.idle_timeout.min(remaining_lifetime);
+        record.metadata.last_seen = now;
+        if store.touch(&record, expires_in).await.is_err() {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::TaskInternalError.to_string()],
+            );
         }
         return TaskResponse::compose_response(
             request,
             TaskStatus::Completed,
-            UserSession::new(&param.token, param.expires_in, &param.user_id),
+            UserSession::new(
+                &param.token,
+                expires_in,
+                remaining_lifetime,
+                &record.user_id,
+                record.metadata,
+            ),
             Vec::default(),
         );
     }
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct SessionRevocationTask {
+pub struct SessionListTask {
     pub user_id: String,
 }
 
-// untested.
 #[async_trait]
-impl Task<RedisDatabase, TaskRequest, SessionRevocationTask> for SessionRevocationTask {
+impl Task<SharedSessionStore, TaskRequest, SessionListTask> for SessionListTask {
     async fn run(
-        db: &RedisDatabase,
+        store: &SharedSessionStore,
         request: TaskRequest,
-        param: SessionRevocationTask,
+        param: SessionListTask,
     ) -> TaskResponse {
-        let mut pool = db.pool.get().await.unwrap();
-        let pattern = format!("session:*:{}", param.user_id);
-        let mut scan_result: AsyncIter<String> = pool.scan_match(&pattern).await.unwrap();
-        // need to collect into a vec to go around rust borrowing rules
-        let mut sessions_to_invalidate: Vec<String> = Vec::new();
-        while let Some(key_result) = scan_result.next_item().await {
-            sessions_to_invalidate.push(key_result);
-        }
-        // return session not found if session is not found by user id.
-        if sessions_to_invalidate.len() == 0 {
+        let (records, security_stamp) = match (
+            store.list_by_user(&param.user_id).await,
+            store.security_stamp(&param.user_id).await,
+        ) {
+            (Ok(records), Ok(security_stamp)) => (records, security_stamp),
+            _ => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        };
+        // sessions of a previous security stamp are left out.
+        let mut sessions: Vec<SessionMetadata> = records
+            .into_iter()
+            .filter(|record| security_stamp.as_ref() == Some(&record.security_stamp))
+            .map(|record| record.metadata)
+            .collect();
+        sessions.sort_by_key(|metadata| std::cmp::Reverse(metadata.last_seen));
+        return TaskResponse::compose_response(
+            request,
+            TaskStatus::Completed,
+            sessions,
+            Vec::default(),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionRevocationByIdTask {
+    pub user_id: String,
+    pub session_id: String,
+}
+
+#[async_trait]
+impl Task<SharedSessionStore, TaskRequest, SessionRevocationByIdTask>
+    for SessionRevocationByIdTask
+{
+    async fn run(
+        store: &SharedSessionStore,
+        request: TaskRequest,
+        param: SessionRevocationByIdTask,
+    ) -> TaskResponse {
+        match store.revoke(&param.user_id, &param.session_id).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::SessionNotFound.to_string()],
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::SessionDeletionFailed.to_string()],
+                );
+            }
+        }
+        return TaskResponse::compose_response(
+            request,
+            TaskStatus::Completed,
+            String::default(),
+            Vec::default(),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionRefreshTask {
+    pub refresh_token: String,
+    pub token: String,
+    pub new_refresh_token: String,
+    pub idle_timeout: i64,
+}
+
+#[async_trait]
+impl Task<SharedSessionStore, TaskRequest, SessionRefreshTask> for SessionRefreshTask {
+    async fn run(
+        store: &SharedSessionStore,
+        request: TaskRequest,
+        param: SessionRefreshTask,
+    ) -> TaskResponse {
+        let refresh_digest = token_digest(&param.refresh_token);
+        let record = match store.get_refresh(&refresh_digest).await {
+            Ok(Some(record)) if digest_eq(&record.digest, &refresh_digest) => record,
+            Ok(_) => {
+                // expired or revoked.
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::SessionNotFound.to_string()],
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        };
+        let now = now();
+        let remaining_lifetime = (record.metadata.expires_at - now) / 1000;
+        if remaining_lifetime <= 0 {
             return TaskResponse::throw_failed_response(
                 request,
                 vec![TaskError::SessionNotFound.to_string()],
             );
         }
-        mem::drop(scan_result);
-        // revocate session.
-        for key in sessions_to_invalidate.iter() {
-            let _: () = pool.del(key).await.unwrap();
+        let security_stamp = match store.security_stamp(&record.user_id).await {
+            Ok(security_stamp) => security_stamp,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        };
+        if security_stamp.as_ref() != Some(&record.security_stamp) {
+            // issued before the security stamp of the user was rotated.
+            let _ = store
+                .revoke(&record.user_id, &record.metadata.session_id)
+                .await;
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::SessionNotFound.to_string()],
+            );
+        }
+        // a refresh token is used once, whoever comes second holds a stolen copy
+        // or the legitimate client was robbed of the rotated one, either way the
+        // family goes.
+        match store
+            .consume_refresh(&refresh_digest, remaining_lifetime)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                let _ = store
+                    .revoke(&record.user_id, &record.metadata.session_id)
+                    .await;
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::SessionRefreshTokenReused.to_string()],
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        }
+        let digest = token_digest(&param.token);
+        let mut metadata = record.metadata;
+        metadata.last_seen = now;
+        let session_record = SessionRecord {
+            digest: digest.clone(),
+            user_id: record.user_id.clone(),
+            security_stamp: record.security_stamp.clone(),
+            metadata: metadata.clone(),
+        };
+        let refresh_record = RefreshRecord {
+            digest: token_digest(&param.new_refresh_token),
+            user_id: record.user_id.clone(),
+            session_digest: digest,
+            security_stamp: record.security_stamp.clone(),
+            metadata: metadata.clone(),
+        };
+        let expires_in = param.idle_timeout.min(remaining_lifetime);
+        let rotate_result = store
+            .rotate(
+                &record.session_digest,
+                &session_record,
+                &refresh_record,
+                expires_in,
+                remaining_lifetime,
+            )
+            .await;
+        if rotate_result.is_err() {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::SessionCreationFailed.to_string()],
+            );
+        }
+        return TaskResponse::compose_response(
+            request,
+            TaskStatus::Completed,
+            UserSession {
+                refresh_token: param.new_refresh_token,
+                ..UserSession::new(
+                    &param.token,
+                    expires_in,
+                    remaining_lifetime,
+                    &record.user_id,
+                    metadata,
+                )
+            },
+            Vec::default(),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionEnforcePolicyTask {
+    pub user_id: String,
+    pub policy: SessionPolicy,
+    pub max_lifetime: i64,
+    /// a session about to be revoked, it does not count.
+    #[serde(default)]
+    pub replacing: Option<String>,
+}
+
+#[async_trait]
+impl Task<SharedSessionStore, TaskRequest, SessionEnforcePolicyTask> for SessionEnforcePolicyTask {
+    async fn run(
+        store: &SharedSessionStore,
+        request: TaskRequest,
+        param: SessionEnforcePolicyTask,
+    ) -> TaskResponse {
+        // families older than the lifetime of a session have expired.
+        let active: Vec<String> = match store
+            .active_families(&param.user_id, now() - param.max_lifetime * 1000)
+            .await
+        {
+            Ok(active) => active
+                .into_iter()
+                .filter(|session_id| param.replacing.as_ref() != Some(session_id))
+                .collect(),
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        };
+        let max_sessions = param.policy.max_sessions.max(0) as usize;
+        // whether the sign in may go through.
+        let allowed = if active.len() < max_sessions {
+            true
+        } else {
+            match param.policy.on_limit {
+                SessionLimitAction::Refuse => false,
+                SessionLimitAction::EvictOldest => {
+                    // room is made for the session about to be created.
+                    for session_id in active.iter().take(active.len() - max_sessions + 1) {
+                        let _ = store.revoke(&param.user_id, session_id).await;
+                    }
+                    true
+                }
+            }
+        };
+        return TaskResponse::compose_response(
+            request,
+            TaskStatus::Completed,
+            allowed,
+            Vec::default(),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionUpdateSecurityStampTask {
+    pub user_id: String,
+    pub security_stamp: String,
+}
+
+#[async_trait]
+impl Task<SharedSessionStore, TaskRequest, SessionUpdateSecurityStampTask>
+    for SessionUpdateSecurityStampTask
+{
+    async fn run(
+        store: &SharedSessionStore,
+        request: TaskRequest,
+        param: SessionUpdateSecurityStampTask,
+    ) -> TaskResponse {
+        // every session issued under the previous stamp stops validating.
+        let update_result = store
+            .set_security_stamp(&param.user_id, &param.security_stamp)
+            .await;
+        // they are cleared right away so they no longer count as active.
+        let revoke_result = store.revoke_by_user(&param.user_id).await;
+        if update_result.is_err() || revoke_result.is_err() {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::TaskInternalError.to_string()],
+            );
+        }
+        return TaskResponse::compose_response(
+            request,
+            TaskStatus::Completed,
+            String::default(),
+            Vec::default(),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionPurgeLegacyTask;
+
+#[async_trait]
+impl Task<SharedSessionStore, TaskRequest, SessionPurgeLegacyTask> for SessionPurgeLegacyTask {
+    async fn run(
+        store: &SharedSessionStore,
+        request: TaskRequest,
+        _param: SessionPurgeLegacyTask,
+    ) -> TaskResponse {
+        if store.purge_legacy().await.is_err() {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::TaskInternalError.to_string()],
+            );
         }
         return TaskResponse::compose_response(
             request,
@@ -169,4 +706,249 @@ impl Task<RedisDatabase, TaskRequest, SessionRevocationTask> for SessionRevocati
             Vec::default(),
         );
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::app::{
+        platform::iam::session::store::memory_store::MemorySessionStore,
+        service::task::message::TaskType,
+    };
+
+    fn store() -> SharedSessionStore {
+        Arc::new(MemorySessionStore::default())
+    }
+
+    fn request<T: Serialize>(payload: &T, action: &str) -> TaskRequest {
+        TaskRequest {
+            task_id: String::from("task"),
+            task_payload: serde_json::to_string(payload).unwrap(),
+            task_type: TaskType::Session,
+            task_action: String::from(action),
+        }
+    }
+
+    fn failure(response: &TaskResponse) -> &str {
+        assert_eq!(response.task_status, TaskStatus::Failed);
+        &response.task_error[0]
+    }
+
+    /// Creates a session of `user` living `max_lifetime` seconds from
+    /// `created_at`, its tokens are derived from `name`.
+    async fn create(
+        store: &SharedSessionStore,
+        name: &str,
+        idle_timeout: i64,
+        max_lifetime: i64,
+        created_at: i64,
+    ) -> UserSession {
+        let param = SessionCreateTask {
+            token: format!("session-{}", name),
+            refresh_token: format!("refresh-{}", name),
+            idle_timeout,
+            max_lifetime,
+            user_id: String::from("user"),
+            security_stamp: String::from("stamp"),
+            metadata: SessionMetadata {
+                created_at,
+                ..SessionMetadata::new(None, None)
+            },
+        };
+        let response =
+            SessionCreateTask::run(store, request(&param, "session_create"), param).await;
+        TaskResponse::intepret_response_result(&response).unwrap()
+    }
+
+    async fn validate(store: &SharedSessionStore, token: &str, idle_timeout: i64) -> TaskResponse {
+        let param = SessionValidationTask {
+            token: String::from(token),
+            idle_timeout,
+        };
+        SessionValidationTask::run(store, request(&param, "session_validation"), param).await
+    }
+
+    async fn refresh(store: &SharedSessionStore, refresh_token: &str, name: &str) -> TaskResponse {
+        let param = SessionRefreshTask {
+            refresh_token: String::from(refresh_token),
+            token: format!("session-{}", name),
+            new_refresh_token: format!("refresh-{}", name),
+            idle_timeout: 60,
+        };
+        SessionRefreshTask::run(store, request(&param, "session_refresh"), param).await
+    }
+
+    #[tokio::test]
+    async fn validation_pushes_the_idle_timeout_up_to_the_lifetime() {
+        let store = store();
+        let session = create(&store, "long", 60, 3600, now()).await;
+        assert_eq!((session.expires_in, session.remaining_lifetime), (60, 3600));
+
+        let response = validate(&store, &session.token, 60).await;
+        let validated: UserSession = TaskResponse::intepret_response_result(&response).unwrap();
+        assert_eq!(validated.expires_in, 60);
+        assert!(validated.remaining_lifetime > 3590 && validated.remaining_lifetime <= 3600);
+
+        // the idle timeout never outlives the session.
+        let session = create(&store, "short", 900, 30, now()).await;
+        assert_eq!((session.expires_in, session.remaining_lifetime), (30, 30));
+        let response = validate(&store, &session.token, 900).await;
+        let validated: UserSession = TaskResponse::intepret_response_result(&response).unwrap();
+        assert!(validated.expires_in <= 30);
+        assert_eq!(validated.expires_in, validated.remaining_lifetime);
+    }
+
+    #[tokio::test]
+    async fn validation_fails_past_the_lifetime() {
+        let store = store();
+        // still within the idle timeout, past the lifetime.
+        let session = create(&store, "expired", 60, 5, now() - 10_000).await;
+
+        let response = validate(&store, &session.token, 60).await;
+        assert_eq!(failure(&response), "SessionNotFound");
+        assert!(store.list_by_user("user").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn validation_fails_on_a_stale_security_stamp() {
+        let store = store();
+        let session = create(&store, "stale", 60, 3600, now()).await;
+        store.set_security_stamp("user", "rotated").await.unwrap();
+
+        let response = validate(&store, &session.token, 60).await;
+        assert_eq!(failure(&response), "SessionNotFound");
+        assert!(store.list_by_user("user").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refresh_rotates_the_session() {
+        let store = store();
+        let session = create(&store, "first", 60, 3600, now()).await;
+
+        let response = refresh(&store, &session.refresh_token, "second").await;
+        let refreshed: UserSession = TaskResponse::intepret_response_result(&response).unwrap();
+        assert_eq!(refreshed.refresh_token, "refresh-second");
+        assert_eq!(refreshed.metadata.session_id, session.metadata.session_id);
+        assert!(refreshed.remaining_lifetime <= session.remaining_lifetime);
+
+        // the previous session is replaced, the next one validates.
+        let response = validate(&store, &session.token, 60).await;
+        assert_eq!(failure(&response), "SessionNotFound");
+        let response = validate(&store, &refreshed.token, 60).await;
+        assert_eq!(response.task_status, TaskStatus::Completed);
+
+        // and its refresh token is the one to use next.
+        let response = refresh(&store, &refreshed.refresh_token, "third").await;
+        assert_eq!(response.task_status, TaskStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn refresh_token_reuse_revokes_the_family() {
+        let store = store();
+        let session = create(&store, "first", 60, 3600, now()).await;
+        let other = create(&store, "other", 60, 3600, now()).await;
+
+        let response = refresh(&store, &session.refresh_token, "second").await;
+        let refreshed: UserSession = TaskResponse::intepret_response_result(&response).unwrap();
+
+        let response = refresh(&store, &session.refresh_token, "third").await;
+        assert_eq!(failure(&response), "SessionRefreshTokenReused");
+
+        // the whole family is gone, the session of the other device is not.
+        let response = validate(&store, &refreshed.token, 60).await;
+        assert_eq!(failure(&response), "SessionNotFound");
+        let response = refresh(&store, &refreshed.refresh_token, "fourth").await;
+        assert_eq!(failure(&response), "SessionNotFound");
+        let sessions = store.list_by_user("user").await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].metadata.session_id, other.metadata.session_id);
+    }
+
+    #[tokio::test]
+    async fn refresh_fails_on_a_stale_security_stamp() {
+        let store = store();
+        let session = create(&store, "stale", 60, 3600, now()).await;
+        store.set_security_stamp("user", "rotated").await.unwrap();
+
+        let response = refresh(&store, &session.refresh_token, "second").await;
+        assert_eq!(failure(&response), "SessionNotFound");
+        assert!(store.list_by_user("user").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn refresh_fails_past_the_lifetime() {
+        let store = store();
+        let session = create(&store, "expired", 60, 5, now() - 10_000).await;
+
+        let response = refresh(&store, &session.refresh_token, "second").await;
+        assert_eq!(failure(&response), "SessionNotFound");
+        let response = validate(&store, "session-second", 60).await;
+        assert_eq!(failure(&response), "SessionNotFound");
+    }
+
+    async fn enforce(
+        store: &SharedSessionStore,
+        max_sessions: i32,
+        on_limit: SessionLimitAction,
+        replacing: Option<String>,
+    ) -> bool {
+        let param = SessionEnforcePolicyTask {
+            user_id: String::from("user"),
+            policy: SessionPolicy::new(max_sessions, on_limit),
+            max_lifetime: 3600,
+            replacing,
+        };
+        let response =
+            SessionEnforcePolicyTask::run(store, request(&param, "session_enforce_policy"), param)
+                .await;
+        TaskResponse::intepret_response_result(&response).unwrap()
+    }
+
+    async fn families(store: &SharedSessionStore) -> Vec<String> {
+        store.active_families("user", 0).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn policy_evicts_the_oldest_families() {
+        let store = store();
+        let oldest = create(&store, "oldest", 60, 3600, now() - 2000).await;
+        let older = create(&store, "older", 60, 3600, now() - 1000).await;
+        let newest = create(&store, "newest", 60, 3600, now()).await;
+
+        assert!(enforce(&store, 2, SessionLimitAction::EvictOldest, None).await);
+        assert_eq!(
+            families(&store).await,
+            vec![newest.metadata.session_id.clone()]
+        );
+        let response = validate(&store, &oldest.token, 60).await;
+        assert_eq!(failure(&response), "SessionNotFound");
+        let response = validate(&store, &older.token, 60).await;
+        assert_eq!(failure(&response), "SessionNotFound");
+    }
+
+    #[tokio::test]
+    async fn policy_evicts_nothing_under_the_limit() {
+        let store = store();
+        let session = create(&store, "only", 60, 3600, now()).await;
+
+        assert!(enforce(&store, 2, SessionLimitAction::EvictOldest, None).await);
+        assert!(enforce(&store, 2, SessionLimitAction::Refuse, None).await);
+        assert_eq!(families(&store).await, vec![session.metadata.session_id]);
+    }
+
+    #[tokio::test]
+    async fn policy_refuses_at_the_limit() {
+        let store = store();
+        create(&store, "first", 60, 3600, now() - 1000).await;
+        let second = create(&store, "second", 60, 3600, now()).await;
+
+        assert!(!enforce(&store, 2, SessionLimitAction::Refuse, None).await);
+        assert_eq!(families(&store).await.len(), 2);
+
+        // the session being replaced does not count.
+        let replacing = Some(second.metadata.session_id);
+        assert!(enforce(&store, 2, SessionLimitAction::Refuse, replacing).await);
+    }
+}
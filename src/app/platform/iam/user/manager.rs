@@ -1,16 +1,28 @@
 use core::panic;
-use std::any::TypeId;
+use std::{
+    any::TypeId,
+    collections::HashSet,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crate::app::{
-    platform::iam::user::task::{UserUpdateAsBooleanTask, UserUpdateAsIntegerTask},
+    adapter::oauth_adapter::{OAuthProfile, OAuthProvider, OAuthTokens},
+    platform::iam::{
+        organization::model::Organization,
+        permission::{cache::PermissionCache, matcher::PermissionMatcher},
+        role::{cache::RoleCache, model::Role},
+        session::manager::SessionManager,
+        user::task::{UserUpdateAsBooleanTask, UserUpdateAsIntegerTask},
+    },
     service::{
         cache::{
             error::CacheResult,
             manager::CacheManager,
             message::{CacheLocation, CacheRequest, CacheStatus},
+            LocalizedCache,
         },
         task::{
-            error::TaskResult,
+            error::{TaskError, TaskResult},
             manager::TaskManager,
             message::{TaskRequest, TaskStatus, TaskType},
         },
@@ -19,9 +31,9 @@ use crate::app::{
 
 use super::{
     cache::{UserAddToCache, UserReadFromCache},
-    model::{User, UserSecurity},
+    model::{Grant, GrantLapsed, ResourceScope, User, UserIdentityTokens, UserSecurity},
     task::{
-        UserAddPermission, UserAddRole, UserCreateSecurityToken, UserCreateTask, UserDeletePermission, UserDeleteRole, UserExchangeOAuthIdForId, UserPreloadCache, UserReadTask, UserUpdateTask
+        UserAddPermission, UserAddRole, UserCreateSecurityToken, UserCreateTask, UserDeletePermission, UserDeleteRole, UserDenyPermission, UserSweepExpiredGrants, UserAddScopedGrant, UserDeleteScopedGrant, UserExchangeOAuthIdForId, UserLinkIdentity, UserPreloadCache, UserReadIdentityTokens, UserReadTask, UserRotateSecurityStamp, UserStoreIdentityTokens, UserSyncProfile, UserUnlinkIdentity, UserUpdateTask
     },
 };
 
@@ -188,7 +200,7 @@ impl UserManager {
     /// Creates a security token for user.
     ///
     /// # Note
-    /// Security stamp automatically gets updated
+    /// Security stamp automatically gets updated, which signs the user out everywhere.
     /// 
     /// # Arguments
     /// - `search_for`: the user identifier, id, username or email.
@@ -200,7 +212,10 @@ impl UserManager {
     /// ```
     pub fn create_security_token(search_by: &str, action: &str) -> TaskResult<UserSecurity> {
         let task_request = Self::create_security_token_request(search_by, action);
-        TaskManager::process_task_with_result::<UserSecurity>(task_request)
+        let security = TaskManager::process_task_with_result::<UserSecurity>(task_request)?;
+        let user = Self::get_user(search_by)?;
+        SessionManager::update_security_stamp(&user.info.user_id, security.stamp.as_deref().unwrap_or_default())?;
+        Ok(security)
     }
 
     /// Composes a security token request.
@@ -220,6 +235,56 @@ impl UserManager {
         )
     }
 
+    /// Rotates the security stamp of a user, every one of their sessions and
+    /// refresh tokens stops being valid across every instance.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    ///
+    /// # Examples
+    /// ```
+    /// let stamp = rotate_security_stamp("2f4afce2-ec56-429a-96b1-480c0b20943a").unwrap();
+    /// ```
+    pub fn rotate_security_stamp(user_identifier: &str) -> TaskResult<String> {
+        let task_request = Self::rotate_security_stamp_request(user_identifier);
+        let security_stamp = TaskManager::process_task_with_result::<String>(task_request)?;
+        SessionManager::update_security_stamp(user_identifier, &security_stamp)?;
+        Ok(security_stamp)
+    }
+
+    /// Composes a security stamp rotation request.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    ///
+    /// # Examples
+    /// ```
+    /// rotate_security_stamp_request("2f4afce2-ec56-429a-96b1-480c0b20943a");
+    /// ```
+    fn rotate_security_stamp_request(user_identifier: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            UserRotateSecurityStamp {
+                user_id: String::from(user_identifier),
+            },
+            TaskType::User,
+            "user_rotate_security_stamp",
+        )
+    }
+
+    /// Resets the credentials of a user, signing them out of every device.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    ///
+    /// # Examples
+    /// ```
+    /// reset_credentials("2f4afce2-ec56-429a-96b1-480c0b20943a").unwrap();
+    /// ```
+    pub fn reset_credentials(user_identifier: &str) -> TaskResult<TaskStatus> {
+        Self::rotate_security_stamp(user_identifier)?;
+        Ok(TaskStatus::Completed)
+    }
+
     /// Exchange an oauth_id for a user_id.
     /// 
     /// # Arguments
@@ -256,6 +321,216 @@ impl UserManager {
         )
     }
 
+    /// Link an OAuth identity to a user, letting them sign in with it.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `oauth_id`: the oauth id of the identity.
+    /// - `oauth_provider`: the provider of the identity.
+    ///
+    /// # Examples
+    /// ```
+    /// link_identity("2f4afce2-ec56-429a-96b1-480c0b20943a", "oauth_id_here", "github");
+    /// ```
+    pub fn link_identity(user_identifier: &str, oauth_id: &str, oauth_provider: &str) -> TaskResult<TaskStatus> {
+        let task_request = Self::link_identity_request(user_identifier, oauth_id, oauth_provider);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Link identity request.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `oauth_id`: the oauth id of the identity.
+    /// - `oauth_provider`: the provider of the identity.
+    ///
+    /// # Examples
+    /// ```
+    /// link_identity_request("2f4afce2-ec56-429a-96b1-480c0b20943a", "oauth_id_here", "github");
+    /// ```
+    fn link_identity_request(user_identifier: &str, oauth_id: &str, oauth_provider: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            UserLinkIdentity {
+                target_user_id: String::from(user_identifier),
+                oauth_id: String::from(oauth_id),
+                oauth_provider: String::from(oauth_provider),
+            },
+            TaskType::User,
+            "user_link_identity",
+        )
+    }
+
+    /// Unlink an OAuth identity from a user, the last identity of a user
+    /// cannot be unlinked.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `oauth_id`: the oauth id of the identity.
+    /// - `oauth_provider`: the provider of the identity.
+    ///
+    /// # Examples
+    /// ```
+    /// unlink_identity("2f4afce2-ec56-429a-96b1-480c0b20943a", "oauth_id_here", "github");
+    /// ```
+    pub fn unlink_identity(user_identifier: &str, oauth_id: &str, oauth_provider: &str) -> TaskResult<TaskStatus> {
+        let task_request = Self::unlink_identity_request(user_identifier, oauth_id, oauth_provider);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Unlink identity request.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `oauth_id`: the oauth id of the identity.
+    /// - `oauth_provider`: the provider of the identity.
+    ///
+    /// # Examples
+    /// ```
+    /// unlink_identity_request("2f4afce2-ec56-429a-96b1-480c0b20943a", "oauth_id_here", "github");
+    /// ```
+    fn unlink_identity_request(user_identifier: &str, oauth_id: &str, oauth_provider: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            UserUnlinkIdentity {
+                target_user_id: String::from(user_identifier),
+                oauth_id: String::from(oauth_id),
+                oauth_provider: String::from(oauth_provider),
+            },
+            TaskType::User,
+            "user_unlink_identity",
+        )
+    }
+
+    /// Store the tokens a provider issued for one of the user's identities,
+    /// they are encrypted before they leave this function.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `oauth_id`: the oauth id of the identity.
+    /// - `oauth_provider`: the provider of the identity.
+    /// - `tokens`: the tokens to store, `None` clears them.
+    ///
+    /// # Examples
+    /// ```
+    /// store_identity_tokens("2f4afce2-ec56-429a-96b1-480c0b20943a", "oauth_id_here", "discord", Some(&tokens));
+    /// ```
+    pub fn store_identity_tokens(user_identifier: &str, oauth_id: &str, oauth_provider: &str, tokens: Option<&OAuthTokens>) -> TaskResult<TaskStatus> {
+        let task_request = Self::store_identity_tokens_request(user_identifier, oauth_id, oauth_provider, tokens);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Store identity tokens request.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `oauth_id`: the oauth id of the identity.
+    /// - `oauth_provider`: the provider of the identity.
+    /// - `tokens`: the tokens to store, `None` clears them.
+    ///
+    /// # Examples
+    /// ```
+    /// store_identity_tokens_request("2f4afce2-ec56-429a-96b1-480c0b20943a", "oauth_id_here", "discord", None);
+    /// ```
+    fn store_identity_tokens_request(user_identifier: &str, oauth_id: &str, oauth_provider: &str, tokens: Option<&OAuthTokens>) -> TaskRequest {
+        TaskRequest::compose_request(
+            UserStoreIdentityTokens {
+                target_user_id: String::from(user_identifier),
+                oauth_id: String::from(oauth_id),
+                oauth_provider: String::from(oauth_provider),
+                tokens: tokens.map(|tokens| tokens.seal(user_identifier, oauth_provider, oauth_id)),
+            },
+            TaskType::User,
+            "user_store_identity_tokens",
+        )
+    }
+
+    /// Read the encrypted tokens of one of the user's identities.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `oauth_provider`: the provider of the identity.
+    /// - `oauth_id`: the oauth id of the identity, `None` picks the most recent tokens of the provider.
+    ///
+    /// # Examples
+    /// ```
+    /// read_identity_tokens("2f4afce2-ec56-429a-96b1-480c0b20943a", "discord", None);
+    /// ```
+    pub fn read_identity_tokens(user_identifier: &str, oauth_provider: &str, oauth_id: Option<&str>) -> TaskResult<UserIdentityTokens> {
+        let task_request = Self::read_identity_tokens_request(user_identifier, oauth_provider, oauth_id);
+        TaskManager::process_task_with_result::<UserIdentityTokens>(task_request)
+    }
+
+    /// Read identity tokens request.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `oauth_provider`: the provider of the identity.
+    /// - `oauth_id`: the oauth id of the identity.
+    ///
+    /// # Examples
+    /// ```
+    /// read_identity_tokens_request("2f4afce2-ec56-429a-96b1-480c0b20943a", "discord", None);
+    /// ```
+    fn read_identity_tokens_request(user_identifier: &str, oauth_provider: &str, oauth_id: Option<&str>) -> TaskRequest {
+        TaskRequest::compose_request(
+            UserReadIdentityTokens {
+                target_user_id: String::from(user_identifier),
+                oauth_provider: String::from(oauth_provider),
+                oauth_id: oauth_id.map(String::from),
+            },
+            TaskType::User,
+            "user_read_identity_tokens",
+        )
+    }
+
+    /// Get an access token to call the provider on the user's behalf, the
+    /// token is refreshed (and stored again) when it expired.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `provider`: the provider to get a token for.
+    ///
+    /// # Examples
+    /// ```
+    /// let discord = state.auth.get_from("discord").unwrap();
+    /// let access_token = UserManager::get_provider_token("2f4afce2-ec56-429a-96b1-480c0b20943a", discord).await?;
+    /// ```
+    pub async fn get_provider_token(user_identifier: &str, provider: &OAuthProvider) -> TaskResult<String> {
+        let identity = Self::read_identity_tokens(user_identifier, &provider.name, None)?;
+        let tokens = OAuthTokens::open(&identity.tokens, user_identifier, &identity.oauth_provider, &identity.oauth_id)
+            .map_err(|_| TaskError::UserIdentityTokenNotFound)?;
+        if !tokens.is_expired() {
+            return Ok(tokens.access_token);
+        }
+        let tokens = provider
+            .refresh_tokens(&tokens)
+            .await
+            .map_err(|_| TaskError::UserIdentityTokenExpired)?;
+        Self::store_identity_tokens(user_identifier, &identity.oauth_id, &provider.name, Some(&tokens))?;
+        Ok(tokens.access_token)
+    }
+
+    /// Revoke the tokens of one of the user's identities at the provider and
+    /// forget them.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `oauth_id`: the oauth id of the identity.
+    /// - `provider`: the provider of the identity.
+    ///
+    /// # Examples
+    /// ```
+    /// let discord = state.auth.get_from("discord").unwrap();
+    /// UserManager::revoke_provider_tokens("2f4afce2-ec56-429a-96b1-480c0b20943a", "oauth_id_here", discord).await?;
+    /// ```
+    pub async fn revoke_provider_tokens(user_identifier: &str, oauth_id: &str, provider: &OAuthProvider) -> TaskResult<TaskStatus> {
+        let identity = Self::read_identity_tokens(user_identifier, &provider.name, Some(oauth_id))?;
+        if let Ok(tokens) = OAuthTokens::open(&identity.tokens, user_identifier, &identity.oauth_provider, oauth_id) {
+            // the provider may have already revoked them on its own.
+            let _ = provider.revoke_tokens(&tokens).await;
+        }
+        Self::store_identity_tokens(user_identifier, oauth_id, &provider.name, None)
+    }
+
     /// Add permission to user.
     /// 
     /// # Arguments
@@ -267,25 +542,45 @@ impl UserManager {
     /// add_permission_to_user("oauth_id_here", "discord");
     /// ```
     pub fn add_permission_to_user(user_identifier: &str, permission_identifier: &str) -> TaskResult<TaskStatus> {
-        let task_request = Self::add_permission_to_user_request(user_identifier, permission_identifier);
+        let task_request = Self::add_permission_to_user_request(user_identifier, permission_identifier, None);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Add a permission to a user until `expires_at`, the grant is ignored once
+    /// it lapsed and deleted by the grant sweeper.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `permission_identifier`: the permission identifier.
+    /// - `expires_at`: when the grant lapses (milliseconds).
+    ///
+    /// # Examples
+    /// ```
+    /// // a trial entitlement for a week.
+    /// add_permission_to_user_until("user_uuid", "export.pdf", now + 604800000);
+    /// ```
+    pub fn add_permission_to_user_until(user_identifier: &str, permission_identifier: &str, expires_at: i64) -> TaskResult<TaskStatus> {
+        let task_request = Self::add_permission_to_user_request(user_identifier, permission_identifier, Some(expires_at));
         TaskManager::process_task(task_request)
     }
     
     /// Composes a add user permission to a user request.
     ///
     /// # Arguments
-    /// - `oauth_id`: The id of the user.
-    /// - `oauth_provider`: the provider.
+    /// - `user_identifier`: The id of the user.
+    /// - `permission_identifier`: the permission identifier.
+    /// - `expires_at`: when the grant lapses, `None` never.
     /// 
     /// # Examples
     /// ```
-    /// add_permission_to_user_request("user_identifier", "permission_identifier");
+    /// add_permission_to_user_request("user_identifier", "permission_identifier", None);
     /// ```
-    fn add_permission_to_user_request(user_identifier: &str, permission_identifier: &str) -> TaskRequest {
+    fn add_permission_to_user_request(user_identifier: &str, permission_identifier: &str, expires_at: Option<i64>) -> TaskRequest {
         TaskRequest::compose_request(
             UserAddPermission {
                 target_user_id: String::from(user_identifier),
                 permission_identifier: String::from(permission_identifier),
+                expires_at,
             },
             TaskType::User,
             "user_add_permission",
@@ -304,7 +599,10 @@ impl UserManager {
     /// ```
     pub fn delete_permission_from_user(user_identifier: &str, permission_identifier: &str) -> TaskResult<TaskStatus> {
         let task_request = Self::delete_permission_from_user_request(user_identifier, permission_identifier);
-        TaskManager::process_task(task_request)
+        let status = TaskManager::process_task(task_request)?;
+        // sessions issued while the user held the permission are revoked.
+        Self::rotate_security_stamp(user_identifier)?;
+        Ok(status)
     }
     
     /// Composes a delete permission from user request.
@@ -328,6 +626,46 @@ impl UserManager {
         )
     }
 
+    /// Deny a permission to a user, it overrides whatever grants it, their
+    /// roles included. `delete_permission_from_user` lifts the denial.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `permission_identifier`: the permission identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// deny_permission_to_user("2f4afce2-ec56-429a-96b1-480c0b20943a", "chat.send");
+    /// ```
+    pub fn deny_permission_to_user(user_identifier: &str, permission_identifier: &str) -> TaskResult<TaskStatus> {
+        let task_request = Self::deny_permission_to_user_request(user_identifier, permission_identifier);
+        let status = TaskManager::process_task(task_request)?;
+        // sessions issued while the user held the permission are revoked.
+        Self::rotate_security_stamp(user_identifier)?;
+        Ok(status)
+    }
+
+    /// Composes a deny permission to user request.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `permission_identifier`: the permission identifier.
+    ///
+    /// # Examples
+    /// ```
+    /// deny_permission_to_user_request("user_identifier", "permission_identifier");
+    /// ```
+    fn deny_permission_to_user_request(user_identifier: &str, permission_identifier: &str) -> TaskRequest {
+        TaskRequest::compose_request(
+            UserDenyPermission {
+                target_user_id: String::from(user_identifier),
+                permission_identifier: String::from(permission_identifier),
+            },
+            TaskType::User,
+            "user_deny_permission",
+        )
+    }
+
     /// Add role to user.
     /// 
     /// # Arguments
@@ -339,7 +677,25 @@ impl UserManager {
     /// add_role_to_user("user_uuid", "role_identiifer");
     /// ```
     pub fn add_role_to_user(user_identifier: &str, role_identifier: &str) -> TaskResult<TaskStatus> {
-        let task_request = Self::add_role_from_user_request(user_identifier, role_identifier);
+        let task_request = Self::add_role_from_user_request(user_identifier, role_identifier, None);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Add a role to a user until `expires_at`, the grant is ignored once it
+    /// lapsed and deleted by the grant sweeper.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `role_identifier`: the role identifier.
+    /// - `expires_at`: when the grant lapses (milliseconds).
+    ///
+    /// # Examples
+    /// ```
+    /// // a temporary moderator for a day.
+    /// add_role_to_user_until("user_uuid", "Moderator", now + 86400000);
+    /// ```
+    pub fn add_role_to_user_until(user_identifier: &str, role_identifier: &str, expires_at: i64) -> TaskResult<TaskStatus> {
+        let task_request = Self::add_role_from_user_request(user_identifier, role_identifier, Some(expires_at));
         TaskManager::process_task(task_request)
     }
     
@@ -348,16 +704,18 @@ impl UserManager {
     /// # Arguments
     /// - `user_identifier`: the oauth id of the user.
     /// - `role_identifier`: the permission identifier.
+    /// - `expires_at`: when the grant lapses, `None` never.
     /// 
     /// # Examples
     /// ```
-    /// add_role_from_user_request("user_identifier", "permission_identifier");
+    /// add_role_from_user_request("user_identifier", "permission_identifier", None);
     /// ```
-    fn add_role_from_user_request(user_identifier: &str, role_identifier: &str) -> TaskRequest {
+    fn add_role_from_user_request(user_identifier: &str, role_identifier: &str, expires_at: Option<i64>) -> TaskRequest {
         TaskRequest::compose_request(
             UserAddRole {
                 target_user_id: String::from(user_identifier),
                 role_identifier: String::from(role_identifier),
+                expires_at,
             },
             TaskType::User,
             "user_add_role",
@@ -376,7 +734,10 @@ impl UserManager {
     /// ```
     pub fn delete_role_from_user(user_identifier: &str, role_identifier: &str) -> TaskResult<TaskStatus> {
         let task_request = Self::delete_role_from_user_request(user_identifier, role_identifier);
-        TaskManager::process_task(task_request)
+        let status = TaskManager::process_task(task_request)?;
+        // sessions issued while the user held the role are revoked.
+        Self::rotate_security_stamp(user_identifier)?;
+        Ok(status)
     }
     
     /// Composes a delete role from user request.
@@ -400,6 +761,476 @@ impl UserManager {
         )
     }
 
+    /// Refresh the profile of a user with what their OAuth provider knows
+    /// about them, in the database and in the cache.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `profile`: the profile returned by the provider.
+    ///
+    /// # Examples
+    /// ```
+    /// sync_user_profile("2f4afce2-ec56-429a-96b1-480c0b20943a", &profile);
+    /// ```
+    pub fn sync_user_profile(user_identifier: &str, profile: &OAuthProfile) -> TaskResult<TaskStatus> {
+        let task_request = Self::sync_user_profile_request(user_identifier, profile);
+        TaskManager::process_task(task_request)
+    }
+
+    /// Sync user profile request.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the uuid of the user.
+    /// - `profile`: the profile returned by the provider.
+    ///
+    /// # Examples
+    /// ```
+    /// sync_user_profile_request("2f4afce2-ec56-429a-96b1-480c0b20943a", &profile);
+    /// ```
+    fn sync_user_profile_request(user_identifier: &str, profile: &OAuthProfile) -> TaskRequest {
+        TaskRequest::compose_request(
+            UserSyncProfile {
+                user_id: String::from(user_identifier),
+                username: profile.username.clone(),
+                email: profile.email.clone(),
+                verified: profile.verified,
+                avatar_url: profile.avatar_url.clone(),
+            },
+            TaskType::User,
+            "user_sync_profile",
+        )
+    }
+
+    /// Resolve everything a user can do, the permissions granted to them
+    /// directly merged with the permissions of every role they hold, inherited
+    /// ones included, less the ones denied to them.
+    ///
+    /// Roles and permissions are resolved through `RoleCache` and
+    /// `PermissionCache`, ids that are no longer cached (deleted) are skipped.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    ///
+    /// # Examples
+    /// ```
+    /// let permissions = UserManager::effective_permissions(&user);
+    /// permissions.contains("ban.user");
+    /// ```
+    pub fn effective_permissions(user: &User) -> HashSet<String> {
+        Self::effective_permissions_in(user, None, None)
+    }
+
+    /// Resolve everything a user can do on a resource, the permissions and
+    /// roles granted on every resource merged with the ones granted on this
+    /// one, less the ones denied to them.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    /// - `scope`: the resource ex: `("channel", "123")`.
+    ///
+    /// # Examples
+    /// ```
+    /// let permissions = UserManager::effective_permissions_on(&user, ("channel", "123"));
+    /// permissions.contains("message.delete");
+    /// ```
+    pub fn effective_permissions_on(user: &User, scope: impl Into<ResourceScope>) -> HashSet<String> {
+        Self::effective_permissions_in(user, Some(&scope.into()), None)
+    }
+
+    /// Resolve everything a user can do within an organization, the system
+    /// roles and permissions they hold merged with the ones of the
+    /// organization, less the ones denied to them. Users who are not members
+    /// of the organization can do nothing within it.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    /// - `org_id`: the id of the organization.
+    ///
+    /// # Examples
+    /// ```
+    /// let permissions = UserManager::effective_permissions_in_org(&user, "dd2546c3-e34a-4fcb-9b12-1a96eb6873e3");
+    /// permissions.contains("message.delete");
+    /// ```
+    pub fn effective_permissions_in_org(user: &User, org_id: &str) -> HashSet<String> {
+        Self::effective_permissions_in(user, None, Some(org_id))
+    }
+
+    fn effective_permissions_in(user: &User, scope: Option<&ResourceScope>, org_id: Option<&str>) -> HashSet<String> {
+        let mut denials = PermissionMatcher::default();
+        for permission_key in Self::denied_permissions_in(user, scope, org_id) {
+            denials.deny(&permission_key);
+        }
+        let mut permissions = Self::granted_permissions_in(user, scope, org_id);
+        permissions.retain(|permission_key| !denials.is_denied(permission_key));
+        permissions
+    }
+
+    /// Resolve every permission denied to a user, directly or through one of
+    /// their roles, inherited ones included. A denial overrides every grant.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    ///
+    /// # Examples
+    /// ```
+    /// let denied = UserManager::denied_permissions(&user);
+    /// denied.contains("chat.send");
+    /// ```
+    pub fn denied_permissions(user: &User) -> HashSet<String> {
+        Self::denied_permissions_in(user, None, None)
+    }
+
+    /// Resolve every permission denied to a user, on every resource when
+    /// `scope` is `None`, on a resource otherwise, within the organization
+    /// `org_id` or outside of any when it is `None`.
+    fn denied_permissions_in(user: &User, scope: Option<&ResourceScope>, org_id: Option<&str>) -> HashSet<String> {
+        let permission_ids = Self::visible_permissions(user.access.denied_permission.clone(), org_id);
+        Self::resolve_permission_keys(permission_ids, Self::roles_in(user, scope, org_id), |role| {
+            role.denied_permissions
+                .into_iter()
+                .chain(role.inherited_denied_permissions)
+                .collect()
+        })
+    }
+
+    /// Resolve every permission granted to a user, on every resource when
+    /// `scope` is `None`, on a resource otherwise, within the organization
+    /// `org_id` or outside of any when it is `None`, denials aside.
+    fn granted_permissions_in(user: &User, scope: Option<&ResourceScope>, org_id: Option<&str>) -> HashSet<String> {
+        let permission_ids = match scope {
+            Some(scope) => user.access.permissions_on(scope),
+            None => user.access.active_permissions(),
+        };
+        let permission_ids = Self::visible_permissions(permission_ids, org_id);
+        Self::resolve_permission_keys(permission_ids, Self::roles_in(user, scope, org_id), |role| {
+            role.role_permissions
+                .into_iter()
+                .chain(role.inherited_permissions)
+                .collect()
+        })
+    }
+
+    /// The ids of the roles of a user that have not lapsed, on every resource
+    /// when `scope` is `None`, on a resource otherwise, that can be used within
+    /// the organization `org_id`, or the system roles when it is `None`.
+    fn roles_in(user: &User, scope: Option<&ResourceScope>, org_id: Option<&str>) -> Vec<String> {
+        if org_id.is_some_and(|org_id| !user.access.is_member_of(org_id)) {
+            return Vec::default();
+        }
+        let role_ids = match scope {
+            Some(scope) => user.access.roles_on(scope),
+            None => user.access.active_roles(),
+        };
+        role_ids
+            .into_iter()
+            .filter(|role_id| {
+                RoleCache::get(role_id)
+                    .is_ok_and(|role| Organization::is_visible(role.org_id.as_deref(), org_id))
+            })
+            .collect()
+    }
+
+    /// Keeps the permissions that can be used within the organization
+    /// `org_id`, or the system permissions when it is `None`.
+    fn visible_permissions(permission_ids: Vec<String>, org_id: Option<&str>) -> Vec<String> {
+        permission_ids
+            .into_iter()
+            .filter(|permission_id| {
+                PermissionCache::get(permission_id).is_ok_and(|permission| {
+                    Organization::is_visible(permission.org_id.as_deref(), org_id)
+                })
+            })
+            .collect()
+    }
+
+    /// The roles of a user that have not lapsed and can be used within an
+    /// organization, inherited ones aside. `None` stands for outside of any
+    /// organization, where only system roles apply.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    /// - `org_id`: the id of the organization.
+    ///
+    /// # Examples
+    /// ```
+    /// let roles = UserManager::roles_in_org(&user, Some("dd2546c3-e34a-4fcb-9b12-1a96eb6873e3"));
+    /// ```
+    pub fn roles_in_org(user: &User, org_id: Option<&str>) -> Vec<Role> {
+        Self::roles_in(user, None, org_id)
+            .into_iter()
+            .filter_map(|role_id| RoleCache::get(&role_id).ok())
+            .collect()
+    }
+
+    /// Merges permission ids with the ones `links` picks out of roles,
+    /// resolved to their keys.
+    fn resolve_permission_keys(
+        permission_ids: Vec<String>,
+        role_ids: Vec<String>,
+        links: fn(Role) -> Vec<String>,
+    ) -> HashSet<String> {
+        let role_permissions = role_ids
+            .into_iter()
+            .filter_map(|role_id| RoleCache::get(&role_id).ok())
+            .flat_map(links);
+        permission_ids
+            .into_iter()
+            .chain(role_permissions)
+            .filter_map(|permission_id| PermissionCache::get(&permission_id).ok())
+            .map(|permission| permission.permission_key)
+            .collect()
+    }
+
+    /// The permissions of a user compiled into a `PermissionMatcher`, wildcard
+    /// grants (`moderation.*`, `*`) and denials included.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    ///
+    /// # Examples
+    /// ```
+    /// let matcher = UserManager::permission_matcher(&user);
+    /// matcher.matches("moderation.ban.user");
+    /// ```
+    pub fn permission_matcher(user: &User) -> PermissionMatcher {
+        Self::permission_matcher_in(user, None, None)
+    }
+
+    /// The permissions of a user on a resource compiled into a `PermissionMatcher`.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    /// - `scope`: the resource ex: `("channel", "123")`.
+    ///
+    /// # Examples
+    /// ```
+    /// let matcher = UserManager::permission_matcher_on(&user, ("channel", "123"));
+    /// matcher.matches("message.delete");
+    /// ```
+    pub fn permission_matcher_on(user: &User, scope: impl Into<ResourceScope>) -> PermissionMatcher {
+        Self::permission_matcher_in(user, Some(&scope.into()), None)
+    }
+
+    /// The permissions of a user within an organization compiled into a
+    /// `PermissionMatcher`, `None` standing for outside of any organization.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    /// - `org_id`: the id of the organization.
+    ///
+    /// # Examples
+    /// ```
+    /// let matcher = UserManager::permission_matcher_in_org(&user, session.metadata.organization_id.as_deref());
+    /// matcher.matches("message.delete");
+    /// ```
+    pub fn permission_matcher_in_org(user: &User, org_id: Option<&str>) -> PermissionMatcher {
+        Self::permission_matcher_in(user, None, org_id)
+    }
+
+    fn permission_matcher_in(user: &User, scope: Option<&ResourceScope>, org_id: Option<&str>) -> PermissionMatcher {
+        let mut matcher = PermissionMatcher::new(Self::granted_permissions_in(user, scope, org_id));
+        for permission_key in Self::denied_permissions_in(user, scope, org_id) {
+            matcher.deny(&permission_key);
+        }
+        matcher
+    }
+
+    /// Whether a user holds a permission, directly or through one of their
+    /// roles, exactly or through a wildcard, and is not denied it.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    /// - `permission_key`: the key of the permission ex: `ban.user`.
+    ///
+    /// # Examples
+    /// ```
+    /// if UserManager::has_permission(&user, "ban.user") { ... }
+    /// ```
+    pub fn has_permission(user: &User, permission_key: &str) -> bool {
+        Self::permission_matcher(user).matches(permission_key)
+    }
+
+    /// Whether a user holds a permission on a resource, through a grant on
+    /// this resource or on every resource, and is not denied it.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    /// - `permission_key`: the key of the permission ex: `message.delete`.
+    /// - `scope`: the resource ex: `("channel", "123")`.
+    ///
+    /// # Examples
+    /// ```
+    /// if UserManager::has_permission_on(&user, "message.delete", ("channel", "123")) { ... }
+    /// ```
+    pub fn has_permission_on(user: &User, permission_key: &str, scope: impl Into<ResourceScope>) -> bool {
+        Self::permission_matcher_on(user, scope).matches(permission_key)
+    }
+
+    /// Whether a member of an organization holds a permission within it,
+    /// through a system role or permission or one of the organization, and is
+    /// not denied it.
+    ///
+    /// # Arguments
+    /// - `user`: the user.
+    /// - `org_id`: the id of the organization.
+    /// - `permission_key`: the key of the permission ex: `message.delete`.
+    ///
+    /// # Examples
+    /// ```
+    /// if UserManager::has_permission_in_org(&user, "dd2546c3-e34a-4fcb-9b12-1a96eb6873e3", "message.delete") { ... }
+    /// ```
+    pub fn has_permission_in_org(user: &User, org_id: &str, permission_key: &str) -> bool {
+        Self::permission_matcher_in_org(user, Some(org_id)).matches(permission_key)
+    }
+
+    /// Add a role to a user on a single resource, roles held on every resource
+    /// still apply there.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `role_identifier`: the role identifier.
+    /// - `scope`: the resource ex: `("channel", "123")`.
+    ///
+    /// # Examples
+    /// ```
+    /// add_role_to_user_on("user_uuid", "Moderator", ("channel", "123"));
+    /// ```
+    pub fn add_role_to_user_on(user_identifier: &str, role_identifier: &str, scope: impl Into<ResourceScope>) -> TaskResult<TaskStatus> {
+        let task_request = Self::add_scoped_grant_request(user_identifier, Grant::Role(String::from(role_identifier)), scope.into());
+        TaskManager::process_task(task_request)
+    }
+
+    /// Add a permission to a user on a single resource.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `permission_identifier`: the permission identifier.
+    /// - `scope`: the resource ex: `("project", "42")`.
+    ///
+    /// # Examples
+    /// ```
+    /// add_permission_to_user_on("user_uuid", "project.edit", ("project", "42"));
+    /// ```
+    pub fn add_permission_to_user_on(user_identifier: &str, permission_identifier: &str, scope: impl Into<ResourceScope>) -> TaskResult<TaskStatus> {
+        let task_request = Self::add_scoped_grant_request(user_identifier, Grant::Permission(String::from(permission_identifier)), scope.into());
+        TaskManager::process_task(task_request)
+    }
+
+    /// Composes an add scoped grant request.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `grant`: the role or permission, by identifier.
+    /// - `scope`: the resource.
+    ///
+    /// # Examples
+    /// ```
+    /// add_scoped_grant_request("user_identifier", Grant::Role(String::from("Moderator")), scope);
+    /// ```
+    fn add_scoped_grant_request(user_identifier: &str, grant: Grant, scope: ResourceScope) -> TaskRequest {
+        TaskRequest::compose_request(
+            UserAddScopedGrant {
+                target_user_id: String::from(user_identifier),
+                grant,
+                scope,
+            },
+            TaskType::User,
+            "user_add_scoped_grant",
+        )
+    }
+
+    /// Delete a role held on a single resource from a user.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `role_identifier`: the role identifier.
+    /// - `scope`: the resource ex: `("channel", "123")`.
+    ///
+    /// # Examples
+    /// ```
+    /// delete_role_from_user_on("user_uuid", "Moderator", ("channel", "123"));
+    /// ```
+    pub fn delete_role_from_user_on(user_identifier: &str, role_identifier: &str, scope: impl Into<ResourceScope>) -> TaskResult<TaskStatus> {
+        let task_request = Self::delete_scoped_grant_request(user_identifier, Grant::Role(String::from(role_identifier)), scope.into());
+        let status = TaskManager::process_task(task_request)?;
+        // sessions issued while the user held the role are revoked.
+        Self::rotate_security_stamp(user_identifier)?;
+        Ok(status)
+    }
+
+    /// Delete a permission granted on a single resource from a user.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `permission_identifier`: the permission identifier.
+    /// - `scope`: the resource ex: `("project", "42")`.
+    ///
+    /// # Examples
+    /// ```
+    /// delete_permission_from_user_on("user_uuid", "project.edit", ("project", "42"));
+    /// ```
+    pub fn delete_permission_from_user_on(user_identifier: &str, permission_identifier: &str, scope: impl Into<ResourceScope>) -> TaskResult<TaskStatus> {
+        let task_request = Self::delete_scoped_grant_request(user_identifier, Grant::Permission(String::from(permission_identifier)), scope.into());
+        let status = TaskManager::process_task(task_request)?;
+        // sessions issued while the user held the permission are revoked.
+        Self::rotate_security_stamp(user_identifier)?;
+        Ok(status)
+    }
+
+    /// Composes a delete scoped grant request.
+    ///
+    /// # Arguments
+    /// - `user_identifier`: the id of the user.
+    /// - `grant`: the role or permission, by identifier.
+    /// - `scope`: the resource.
+    ///
+    /// # Examples
+    /// ```
+    /// delete_scoped_grant_request("user_identifier", Grant::Role(String::from("Moderator")), scope);
+    /// ```
+    fn delete_scoped_grant_request(user_identifier: &str, grant: Grant, scope: ResourceScope) -> TaskRequest {
+        TaskRequest::compose_request(
+            UserDeleteScopedGrant {
+                target_user_id: String::from(user_identifier),
+                grant,
+                scope,
+            },
+            TaskType::User,
+            "user_delete_scoped_grant",
+        )
+    }
+
+    /// Deletes every time-bound grant that lapsed and refreshes the cached
+    /// users that held one. The grant sweeper runs it periodically.
+    ///
+    /// # Examples
+    /// ```
+    /// let lapsed = UserManager::sweep_expired_grants().unwrap();
+    /// ```
+    pub fn sweep_expired_grants() -> TaskResult<Vec<GrantLapsed>> {
+        let task_request = Self::sweep_expired_grants_request();
+        TaskManager::process_task_with_result::<Vec<GrantLapsed>>(task_request)
+    }
+
+    /// Composes a sweep expired grants request.
+    ///
+    /// # Examples
+    /// ```
+    /// let task_response = sweep_expired_grants_request();
+    /// ```
+    fn sweep_expired_grants_request() -> TaskRequest {
+        TaskRequest::compose_request(
+            UserSweepExpiredGrants {
+                now: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as i64,
+            },
+            TaskType::User,
+            "user_sweep_expired_grants",
+        )
+    }
+
     /// Preload user cache.
     ///
     /// # Examples
@@ -423,7 +1254,7 @@ impl UserManager {
     }
 }
 
-pub(super) struct UserCacheManager;
+pub(crate) struct UserCacheManager;
 
 impl UserCacheManager {
     /// Add user to cache.
@@ -2,5 +2,6 @@ pub mod cache;
 pub mod manager;
 pub mod model;
 pub mod route;
+pub mod sweeper;
 pub mod task;
 pub mod validation;
@@ -1,9 +1,14 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
+use crate::app::adapter::oauth_adapter::OAuthSealedTokens;
+
 /// Represents a user's basic information.
 ///
 /// Includes identification, contact details, and account timestamps.
@@ -13,6 +18,8 @@ pub struct UserInfo {
     pub username: Option<String>,
     pub email: Option<String>,
     pub verified: bool,
+    #[serde(default)]
+    pub avatar_url: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
 }
@@ -24,10 +31,204 @@ pub struct UserAuthInfo {
     pub oauth_provider: String,
 }
 
+impl UserAuthInfo {
+    /// Pairs the aggregated `oauth_id` and `oauth_provider` columns of
+    /// `iam_user_oauth`, both are expected to be ordered the same way.
+    pub fn from_columns(
+        oauth_ids: Option<Vec<String>>,
+        oauth_providers: Option<Vec<String>>,
+    ) -> Vec<UserAuthInfo> {
+        oauth_ids
+            .unwrap_or_default()
+            .into_iter()
+            .zip(oauth_providers.unwrap_or_default())
+            .map(|(oauth_id, oauth_provider)| UserAuthInfo {
+                oauth_id,
+                oauth_provider,
+            })
+            .collect()
+    }
+}
+
+/// Represents the encrypted provider tokens of one of the user's identities.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserIdentityTokens {
+    pub oauth_id: String,
+    pub oauth_provider: String,
+    pub tokens: OAuthSealedTokens,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserAccessInfo {
     pub role: Vec<String>,
     pub permission: Vec<String>,
+    /// Permissions taken away from the user, whatever grants them.
+    #[serde(default)]
+    pub denied_permission: Vec<String>,
+    /// When the time-bound roles of the user lapse (milliseconds), by role id.
+    #[serde(default)]
+    pub role_expires_at: HashMap<String, i64>,
+    /// When the time-bound permissions of the user lapse (milliseconds), by
+    /// permission id.
+    #[serde(default)]
+    pub permission_expires_at: HashMap<String, i64>,
+    /// Roles held on a single resource.
+    #[serde(default)]
+    pub scoped_role: Vec<ScopedGrant>,
+    /// Permissions granted on a single resource.
+    #[serde(default)]
+    pub scoped_permission: Vec<ScopedGrant>,
+    /// The ids of the organizations the user is a member of.
+    #[serde(default)]
+    pub organizations: Vec<String>,
+}
+
+impl UserAccessInfo {
+    /// The roles of the user that have not lapsed, expired grants are ignored
+    /// until the sweeper deletes them.
+    pub fn active_roles(&self) -> Vec<String> {
+        Self::active(&self.role, &self.role_expires_at)
+    }
+
+    /// The permissions granted to the user that have not lapsed.
+    pub fn active_permissions(&self) -> Vec<String> {
+        Self::active(&self.permission, &self.permission_expires_at)
+    }
+
+    /// The roles of the user that apply on a resource, the ones held on every
+    /// resource included.
+    ///
+    /// # Arguments
+    /// - `scope`: the resource.
+    pub fn roles_on(&self, scope: &ResourceScope) -> Vec<String> {
+        let mut roles = self.active_roles();
+        roles.extend(ScopedGrant::on(&self.scoped_role, scope));
+        roles
+    }
+
+    /// The permissions granted to the user that apply on a resource, the ones
+    /// granted on every resource included.
+    ///
+    /// # Arguments
+    /// - `scope`: the resource.
+    pub fn permissions_on(&self, scope: &ResourceScope) -> Vec<String> {
+        let mut permissions = self.active_permissions();
+        permissions.extend(ScopedGrant::on(&self.scoped_permission, scope));
+        permissions
+    }
+
+    /// Whether the user is a member of an organization.
+    ///
+    /// # Arguments
+    /// - `org_id`: the id of the organization.
+    pub fn is_member_of(&self, org_id: &str) -> bool {
+        self.organizations
+            .iter()
+            .any(|organization| organization.eq(org_id))
+    }
+
+    fn active(grants: &[String], expires_at: &HashMap<String, i64>) -> Vec<String> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        grants
+            .iter()
+            .filter(|grant| {
+                expires_at
+                    .get(*grant)
+                    .is_none_or(|expires_at| *expires_at > now)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Pairs the aggregated ids and `expires_at` columns of the time-bound
+    /// grants, both are expected to be ordered the same way.
+    pub fn expiries_from_columns(
+        grant_ids: Option<Vec<String>>,
+        expires_at: Option<Vec<i64>>,
+    ) -> HashMap<String, i64> {
+        grant_ids
+            .unwrap_or_default()
+            .into_iter()
+            .zip(expires_at.unwrap_or_default())
+            .collect()
+    }
+}
+
+/// What a grant gives the user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Grant {
+    /// A role, by id.
+    Role(String),
+    /// A permission, by id.
+    Permission(String),
+}
+
+/// A resource grants can be scoped to, ex: the channel `123`.
+///
+/// # Examples
+/// ```
+/// let scope = ResourceScope::from(("channel", "123"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ResourceScope {
+    pub resource_type: String,
+    pub resource_id: String,
+}
+
+impl ResourceScope {
+    pub fn new(resource_type: &str, resource_id: &str) -> ResourceScope {
+        Self {
+            resource_type: String::from(resource_type),
+            resource_id: String::from(resource_id),
+        }
+    }
+}
+
+impl From<(&str, &str)> for ResourceScope {
+    fn from((resource_type, resource_id): (&str, &str)) -> Self {
+        ResourceScope::new(resource_type, resource_id)
+    }
+}
+
+/// A role or permission held on a single resource.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScopedGrant {
+    /// The id of the role or permission.
+    pub grant_id: String,
+    pub scope: ResourceScope,
+}
+
+impl ScopedGrant {
+    /// Reads the scoped grants aggregated into a json array of `ScopedGrant`,
+    /// `NULL` when the user holds none.
+    pub fn from_json(grants: Option<String>) -> Vec<ScopedGrant> {
+        grants
+            .and_then(|grants| serde_json::from_str(&grants).ok())
+            .unwrap_or_default()
+    }
+
+    /// The ids of the grants held on a resource.
+    fn on<'a>(
+        grants: &'a [ScopedGrant],
+        scope: &'a ResourceScope,
+    ) -> impl Iterator<Item = String> + 'a {
+        grants
+            .iter()
+            .filter(move |grant| grant.scope.eq(scope))
+            .map(|grant| grant.grant_id.clone())
+    }
+}
+
+/// A time-bound grant that lapsed and was deleted by the sweeper.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrantLapsed {
+    pub user_id: String,
+    pub grant: Grant,
+    /// When the grant lapsed (milliseconds).
+    pub expired_at: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -55,8 +256,7 @@ impl UserSecurity {
             .as_millis() as i64;
         hasher.update(time.to_string());
         let no_hex_stamp = hasher.finalize();
-        let hex_stamp = hex::encode(no_hex_stamp);
-        hex_stamp
+        hex::encode(no_hex_stamp)
     }
 }
 
@@ -91,10 +291,7 @@ impl SecurityToken {
 
     /// Deserialize a security token
     pub fn decode_then_deserialize(security_token: Option<String>) -> Option<SecurityToken> {
-        if security_token.is_none() {
-            return None;
-        }
-        let decode_data = hex::decode(security_token.unwrap());
+        let decode_data = hex::decode(security_token?);
         let decode_data_result = match decode_data {
             Ok(data) => data,
             Err(_) => return None,
@@ -104,10 +301,7 @@ impl SecurityToken {
             Ok(data) => data,
             Err(_) => return None,
         };
-        match serde_json::from_str::<SecurityToken>(&from_utf8_data_result) {
-            Ok(data) => Some(data),
-            Err(_) => return None,
-        }
+        serde_json::from_str::<SecurityToken>(&from_utf8_data_result).ok()
     }
 
     /// Serializes then encodes the serialization via hex.
@@ -128,8 +322,7 @@ impl SecurityToken {
         let key_f = format!("{}:{}", security_stamp, action);
         hasher.update(key_f);
         let no_hex_token = hasher.finalize();
-        let hex_token = hex::encode(no_hex_token);
-        hex_token
+        hex::encode(no_hex_token)
     }
 
 
@@ -211,7 +404,7 @@ pub struct User {
     // Fields from the 'users' table
     pub info: UserInfo, // Basic user information
     // Fields from the 'user_oauth' table
-    pub auth: UserAuthInfo, // Authentication details
+    pub auth: Vec<UserAuthInfo>, // Every linked identity
     // Fields from 'user_roles' and 'user_permissions'
     pub access: UserAccessInfo, // Permission and role details
     // Security stamp and token used to generate reset passwords etc;
@@ -219,15 +412,16 @@ pub struct User {
 }
 
 impl User {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_id: String,
         username: Option<String>,
         email: Option<String>,
         verified: bool,
+        avatar_url: Option<String>,
         created_at: i64,
         updated_at: i64,
-        oauth_id: String,
-        oauth_provider: String,
+        auth: Vec<UserAuthInfo>,
         roles: Vec<String>,
         permissions: Vec<String>,
         security: UserSecurity,
@@ -238,16 +432,20 @@ impl User {
                 username,
                 email,
                 verified,
+                avatar_url,
                 created_at,
                 updated_at,
             },
-            auth: UserAuthInfo {
-                oauth_id,
-                oauth_provider,
-            },
+            auth,
             access: UserAccessInfo {
                 role: roles,
                 permission: permissions,
+                denied_permission: Vec::default(),
+                role_expires_at: HashMap::default(),
+                permission_expires_at: HashMap::default(),
+                scoped_role: Vec::default(),
+                scoped_permission: Vec::default(),
+                organizations: Vec::default(),
             },
             security,
         }
@@ -273,6 +471,7 @@ impl UserBuilder {
                 username: None,
                 email: None,
                 verified: false,
+                avatar_url: None,
                 created_at: SystemTime::now()
                     .duration_since(UNIX_EPOCH)
                     .unwrap()
@@ -303,6 +502,11 @@ impl UserBuilder {
         self
     }
 
+    pub fn avatar_url(mut self, avatar_url: &str) -> UserBuilder {
+        self.info.avatar_url = Some(String::from(avatar_url));
+        self
+    }
+
     pub fn created_at(mut self, created_at: i64) -> UserBuilder {
         self.info.created_at = created_at;
         self
@@ -333,13 +537,18 @@ impl UserBuilder {
         self
     }
 
+    pub fn denied_permission(mut self, permissions: Vec<String>) -> UserBuilder {
+        self.access.denied_permission = permissions;
+        self
+    }
+
     pub fn security_stamp(mut self) -> UserBuilder {
         self.security.stamp = Some(Uuid::new_v4().as_simple().to_string());
         self
     }
 
-    /// Runs the builder result through the validator ensuring
-    /// it meets the criteria.
+    // Runs the builder result through the validator ensuring
+    // it meets the criteria.
     /*
     pub fn validate_and_build(self) -> ValidationResult<User> {
         UserValidator::validate(User {
@@ -353,9 +562,132 @@ impl UserBuilder {
     pub fn build(self) -> User {
         User {
             info: self.info,
-            auth: self.auth,
+            auth: vec![self.auth],
             access: self.access,
             security: UserSecurity::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use super::{ResourceScope, ScopedGrant, UserAccessInfo};
+
+    fn now() -> i64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| String::from(*id)).collect()
+    }
+
+    /// Holds a permanent, a lapsed and a still running grant of each kind.
+    fn access() -> UserAccessInfo {
+        let expires_at = |prefix: &str| {
+            HashMap::from([
+                (format!("{}-expired", prefix), now() - 1000),
+                (format!("{}-future", prefix), now() + 60_000),
+            ])
+        };
+        UserAccessInfo {
+            role: ids(&["role-permanent", "role-expired", "role-future"]),
+            role_expires_at: expires_at("role"),
+            permission: ids(&[
+                "permission-permanent",
+                "permission-expired",
+                "permission-future",
+            ]),
+            permission_expires_at: expires_at("permission"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn active_roles_skip_the_lapsed_ones() {
+        assert_eq!(
+            access().active_roles(),
+            ids(&["role-permanent", "role-future"])
+        );
+    }
+
+    #[test]
+    fn active_permissions_skip_the_lapsed_ones() {
+        assert_eq!(
+            access().active_permissions(),
+            ids(&["permission-permanent", "permission-future"])
+        );
+    }
+
+    #[test]
+    fn active_ignores_the_expiries_of_other_grants() {
+        let expires_at = HashMap::from([(String::from("other"), now() - 1000)]);
+        let grants = ids(&["permanent"]);
+        assert_eq!(UserAccessInfo::active(&grants, &expires_at), grants);
+        assert!(UserAccessInfo::active(&[], &expires_at).is_empty());
+    }
+
+    fn scoped(grant_id: &str, resource_type: &str, resource_id: &str) -> ScopedGrant {
+        ScopedGrant {
+            grant_id: String::from(grant_id),
+            scope: ResourceScope::new(resource_type, resource_id),
+        }
+    }
+
+    #[test]
+    fn scoped_grants_match_the_type_and_the_id() {
+        let grants = vec![
+            scoped("moderator", "channel", "123"),
+            scoped("other-channel", "channel", "456"),
+            scoped("other-type", "thread", "123"),
+        ];
+        let on = |scope: (&str, &str)| {
+            ScopedGrant::on(&grants, &ResourceScope::from(scope)).collect::<Vec<String>>()
+        };
+        assert_eq!(on(("channel", "123")), ids(&["moderator"]));
+        assert_eq!(on(("channel", "456")), ids(&["other-channel"]));
+        assert_eq!(on(("thread", "123")), ids(&["other-type"]));
+        assert!(on(("channel", "789")).is_empty());
+        assert!(on(("guild", "123")).is_empty());
+    }
+
+    #[test]
+    fn grants_on_a_resource_include_the_global_ones() {
+        let access = UserAccessInfo {
+            scoped_role: vec![
+                scoped("role-scoped", "channel", "123"),
+                scoped("role-elsewhere", "channel", "456"),
+            ],
+            scoped_permission: vec![
+                scoped("permission-scoped", "channel", "123"),
+                scoped("permission-elsewhere", "thread", "123"),
+            ],
+            ..access()
+        };
+        let scope = ResourceScope::from(("channel", "123"));
+        assert_eq!(
+            access.roles_on(&scope),
+            ids(&["role-permanent", "role-future", "role-scoped"])
+        );
+        assert_eq!(
+            access.permissions_on(&scope),
+            ids(&[
+                "permission-permanent",
+                "permission-future",
+                "permission-scoped"
+            ])
+        );
+
+        // only the global ones apply where nothing is scoped.
+        let scope = ResourceScope::from(("channel", "789"));
+        assert_eq!(access.roles_on(&scope), access.active_roles());
+        assert_eq!(access.permissions_on(&scope), access.active_permissions());
+    }
+}
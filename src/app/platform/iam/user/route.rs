@@ -0,0 +1,556 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, Path, Query},
+    http::{header::USER_AGENT, HeaderMap, StatusCode},
+    response::Redirect,
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use tower_cookies::{
+    cookie::{time::Duration, SameSite},
+    Cookie, Cookies,
+};
+
+use crate::app::{
+    adapter::oauth_adapter::{OAuthIntegrity, OAuthTokens},
+    ark::{ArkState, INTEGRITY_COOKIE_NAME, REFRESH_COOKIE_NAME, SESSION_COOKIE_NAME},
+    platform::{
+        iam::session::{
+            extractor::AuthenticatedUser,
+            manager::SessionManager,
+            model::{SessionMetadata, UserSession},
+        },
+        response::{CustomJsonResponse, ErrorJsonResponse},
+    },
+    service::task::error::TaskError,
+};
+
+use super::{manager::UserManager, model::User};
+
+/// How long the user has to complete the authorization with the provider.
+static INTEGRITY_COOKIE_MAX_AGE: i64 = 600;
+
+#[derive(Deserialize)]
+pub struct RefreshBody {
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct AuthCallbackQuery {
+    pub code: String,
+    pub state: String,
+}
+
+/// Creates the routes responsible for signing a user in and out.
+///
+/// # Routes
+/// - `GET /auth/login/:provider` - redirects the user to the provider.
+/// - `GET /auth/callback` - the provider redirects back here with the code.
+/// - `POST /auth/logout` - revokes the session and clears the cookie.
+/// - `POST /auth/logout/all` - revokes every session of the user.
+/// - `GET /auth/session` - the current session and how long it has left.
+/// - `POST /auth/refresh` - rotates the refresh token for a new session.
+/// - `GET /auth/sessions` - lists the sessions of the user.
+/// - `POST /auth/unlink/:provider` - unlinks the provider from the user.
+/// - `POST /auth/organization/:org_id` - switches to a session scoped to the organization.
+/// - `DELETE /auth/organization` - switches back to a session outside of any organization.
+pub fn routes() -> Router {
+    Router::new()
+        .route("/auth/login/:provider", get(login))
+        .route("/auth/callback", get(callback))
+        .route("/auth/logout", post(logout))
+        .route("/auth/logout/all", post(logout_everywhere))
+        .route("/auth/session", get(session))
+        .route("/auth/refresh", post(refresh))
+        .route("/auth/sessions", get(sessions))
+        .route("/auth/unlink/:provider", post(unlink))
+        .route("/auth/organization/:org_id", post(switch_organization))
+        .route("/auth/organization", delete(leave_organization))
+}
+
+/// Redirects the user to the authorization page of the specified provider.
+///
+/// The provider, the `state` and the PKCE verifier are remembered inside of
+/// the private `pl.integrity` cookie so the callback can verify that it is
+/// completing a sign in this browser started.
+///
+/// # Arguments
+/// - `provider`: the provider to sign in with ex: discord.
+async fn login(
+    Extension(state): Extension<Arc<ArkState>>,
+    cookies: Cookies,
+    Path(provider): Path<String>,
+) -> Result<Redirect, ErrorJsonResponse> {
+    let provider = state
+        .auth
+        .get_from(&provider)
+        .map_err(|er| ErrorJsonResponse::new(StatusCode::NOT_FOUND, &er.to_string()))?;
+    let (authorize_url, integrity) = provider.authorize_url();
+    cookies.private(&state.key).add(
+        Cookie::build((
+            INTEGRITY_COOKIE_NAME,
+            serde_json::to_string(&integrity).unwrap(),
+        ))
+        .path("/")
+        .http_only(true)
+        .max_age(Duration::seconds(INTEGRITY_COOKIE_MAX_AGE))
+        .build(),
+    );
+    Ok(Redirect::to(authorize_url.as_str()))
+}
+
+/// Completes the sign in after the provider redirects back.
+///
+/// Rejects the request when the `pl.integrity` cookie is missing or its
+/// `state` does not match the one the provider returned. Exchanges the code
+/// for a token, resolves the user behind the oauth id (creating one on their
+/// first sign in, or linking the identity to the user that is already signed
+/// in) and refreshes their profile, then creates a session and stores it in
+/// the private `pl.session` cookie.
+async fn callback(
+    Extension(state): Extension<Arc<ArkState>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    cookies: Cookies,
+    Query(query): Query<AuthCallbackQuery>,
+) -> Result<Redirect, ErrorJsonResponse> {
+    let private = cookies.private(&state.key);
+    let integrity = private
+        .get(INTEGRITY_COOKIE_NAME)
+        .and_then(|cookie| serde_json::from_str::<OAuthIntegrity>(cookie.value()).ok())
+        .ok_or_else(|| {
+            ErrorJsonResponse::new(StatusCode::BAD_REQUEST, "The sign in request has expired.")
+        })?;
+    // the integrity can only be used once.
+    private.remove(Cookie::build((INTEGRITY_COOKIE_NAME, "")).path("/").build());
+    if !integrity.verify_state(&query.state) {
+        return Err(ErrorJsonResponse::new(
+            StatusCode::FORBIDDEN,
+            "The sign in request could not be verified.",
+        ));
+    }
+
+    let provider = state
+        .auth
+        .get_from(&integrity.provider)
+        .map_err(|er| ErrorJsonResponse::new(StatusCode::BAD_REQUEST, &er.to_string()))?;
+    let (profile, tokens) = provider
+        .authenticate(&query.code, &integrity)
+        .await
+        .map_err(|er| ErrorJsonResponse::new(StatusCode::BAD_GATEWAY, &er.to_string()))?;
+
+    // a user that is already signed in is linking another identity.
+    let signed_in = private
+        .get(SESSION_COOKIE_NAME)
+        .and_then(|cookie| serde_json::from_str::<UserSession>(cookie.value()).ok())
+        .and_then(|session| SessionManager::validate_session(&session.token).ok());
+    let user_id = match (
+        UserManager::exchange_oauth_for_id(&profile.oauth_id, &provider.name),
+        signed_in,
+    ) {
+        (Ok(user_id), Some(session)) if !user_id.eq(&session.user_id) => {
+            return Err(ErrorJsonResponse::new(
+                StatusCode::CONFLICT,
+                "This account is already linked to another user.",
+            ))
+        }
+        (Ok(user_id), _) => user_id,
+        (Err(_), Some(session)) => {
+            UserManager::link_identity(&session.user_id, &profile.oauth_id, &provider.name)
+                .map_err(|er| ErrorJsonResponse::new(StatusCode::CONFLICT, &er.to_string()))?;
+            session.user_id
+        }
+        (Err(_), None) => {
+            // the profile is filled in by the sync below.
+            let user = User::builder()
+                .oauth_id(&profile.oauth_id)
+                .oauth_provider(&provider.name)
+                .build();
+            let user_id = user.info.user_id.clone();
+            UserManager::create_user(user).map_err(|er| {
+                ErrorJsonResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &er.to_string())
+            })?;
+            user_id
+        }
+    };
+
+    // a failed sync (e.g. the provider is missing some fields) shouldn't
+    // prevent the user from signing in.
+    let _ = UserManager::sync_user_profile(&user_id, &profile);
+    // neither should failing to keep the provider tokens around.
+    let _ = UserManager::store_identity_tokens(
+        &user_id,
+        &profile.oauth_id,
+        &provider.name,
+        Some(&tokens),
+    );
+
+    let metadata = SessionMetadata::new(
+        Some(&address.ip().to_string()),
+        headers
+            .get(USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok()),
+    );
+    let user = UserManager::get_user(&user_id)
+        .map_err(|er| ErrorJsonResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &er.to_string()))?;
+    let session = SessionManager::create_session(&user, metadata).map_err(|er| match er {
+        TaskError::SessionLimitReached => ErrorJsonResponse::new(
+            StatusCode::FORBIDDEN,
+            "You have too many active sessions, sign out of another device first.",
+        ),
+        er => ErrorJsonResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &er.to_string()),
+    })?;
+    set_session_cookie(&state, &cookies, &session);
+    Ok(Redirect::to("/"))
+}
+
+/// Signs the user out of this device by revoking its session and removing
+/// the `pl.session` cookie. The tokens their providers issued are revoked
+/// once their last session is gone.
+async fn logout(
+    Extension(state): Extension<Arc<ArkState>>,
+    cookies: Cookies,
+) -> Result<Redirect, ErrorJsonResponse> {
+    let session = take_session_cookie(&state, &cookies)?;
+    // the session may have already expired on its own.
+    let _ = SessionManager::revoke_session_by_id(&session.user_id, &session.metadata.session_id);
+    if SessionManager::list_sessions(&session.user_id).is_ok_and(|sessions| sessions.is_empty()) {
+        if let Ok(user) = UserManager::get_user(&session.user_id) {
+            revoke_provider_tokens(&state, &user).await;
+        }
+    }
+    Ok(Redirect::to("/"))
+}
+
+/// Signs the user out everywhere by revoking every one of their sessions and
+/// the tokens their providers issued, only a valid session can do so.
+async fn logout_everywhere(
+    Extension(state): Extension<Arc<ArkState>>,
+    cookies: Cookies,
+    AuthenticatedUser { user, session }: AuthenticatedUser,
+) -> Result<Redirect, ErrorJsonResponse> {
+    SessionManager::revoke_session(&session.user_id).map_err(|er| {
+        ErrorJsonResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &er.to_string())
+    })?;
+    remove_session_cookie(&state, &cookies);
+    revoke_provider_tokens(&state, &user).await;
+    Ok(Redirect::to("/"))
+}
+
+/// Returns the current session so clients can warn before it expires, the
+/// tokens are left out.
+async fn session(
+    AuthenticatedUser { session, .. }: AuthenticatedUser,
+) -> CustomJsonResponse<UserSession> {
+    CustomJsonResponse::new(
+        StatusCode::OK,
+        UserSession {
+            token: String::default(),
+            refresh_token: String::default(),
+            ..session
+        },
+    )
+}
+
+/// Lists the sessions of the signed in user, one per device.
+async fn sessions(
+    AuthenticatedUser { session, .. }: AuthenticatedUser,
+) -> Result<CustomJsonResponse<Vec<SessionMetadata>>, ErrorJsonResponse> {
+    let sessions = SessionManager::list_sessions(&session.user_id)
+        .map_err(|er| ErrorJsonResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &er.to_string()))?;
+    Ok(CustomJsonResponse::new(StatusCode::OK, sessions))
+}
+
+/// Exchanges a refresh token for a new session and refresh token.
+///
+/// Clients holding their tokens send `{ "refresh_token": "..." }` and receive
+/// the new ones, browsers send nothing and the `pl.refresh` cookie is
+/// exchanged instead, both cookies are replaced. A refresh token that was
+/// already used revokes the whole session.
+async fn refresh(
+    Extension(state): Extension<Arc<ArkState>>,
+    cookies: Cookies,
+    body: Option<Json<RefreshBody>>,
+) -> Result<CustomJsonResponse<UserSession>, ErrorJsonResponse> {
+    let unauthorized =
+        |_| ErrorJsonResponse::new(StatusCode::UNAUTHORIZED, "The refresh token is not valid.");
+    if let Some(Json(body)) = body {
+        let session = SessionManager::refresh_session(&body.refresh_token).map_err(unauthorized)?;
+        return Ok(CustomJsonResponse::new(StatusCode::OK, session));
+    }
+    let refresh_token = cookies
+        .private(&state.key)
+        .get(REFRESH_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_string())
+        .ok_or_else(|| {
+            ErrorJsonResponse::new(StatusCode::UNAUTHORIZED, "You are not signed in.")
+        })?;
+    let session = SessionManager::refresh_session(&refresh_token).map_err(|er| {
+        remove_session_cookie(&state, &cookies);
+        unauthorized(er)
+    })?;
+    set_session_cookie(&state, &cookies, &session);
+    Ok(CustomJsonResponse::new(
+        StatusCode::OK,
+        UserSession {
+            token: String::default(),
+            refresh_token: String::default(),
+            ..session
+        },
+    ))
+}
+
+/// Switches the signed in user to a session scoped to one of their
+/// organizations, created for the same device. The current session is revoked
+/// and the cookies replaced.
+///
+/// # Arguments
+/// - `org_id`: the id of the organization.
+async fn switch_organization(
+    Extension(state): Extension<Arc<ArkState>>,
+    cookies: Cookies,
+    AuthenticatedUser { user, session }: AuthenticatedUser,
+    Path(org_id): Path<String>,
+) -> Result<CustomJsonResponse<UserSession>, ErrorJsonResponse> {
+    switch_session(&state, &cookies, &user, &session, Some(&org_id))
+}
+
+/// Switches the signed in user back to a session outside of any organization,
+/// created for the same device. The current session is revoked and the
+/// cookies replaced.
+async fn leave_organization(
+    Extension(state): Extension<Arc<ArkState>>,
+    cookies: Cookies,
+    AuthenticatedUser { user, session }: AuthenticatedUser,
+) -> Result<CustomJsonResponse<UserSession>, ErrorJsonResponse> {
+    switch_session(&state, &cookies, &user, &session, None)
+}
+
+/// Replaces the current session by one scoped to the organization, or to none.
+fn switch_session(
+    state: &ArkState,
+    cookies: &Cookies,
+    user: &User,
+    current: &UserSession,
+    org_id: Option<&str>,
+) -> Result<CustomJsonResponse<UserSession>, ErrorJsonResponse> {
+    let session = SessionManager::switch_session(user, current, org_id).map_err(|er| match er {
+        TaskError::OrganizationMemberNotFound => ErrorJsonResponse::new(
+            StatusCode::FORBIDDEN,
+            "You are not a member of this organization.",
+        ),
+        TaskError::SessionLimitReached => ErrorJsonResponse::new(
+            StatusCode::FORBIDDEN,
+            "You have too many active sessions, sign out of another device first.",
+        ),
+        er => ErrorJsonResponse::new(StatusCode::INTERNAL_SERVER_ERROR, &er.to_string()),
+    })?;
+    set_session_cookie(state, cookies, &session);
+    Ok(CustomJsonResponse::new(
+        StatusCode::OK,
+        UserSession {
+            token: String::default(),
+            refresh_token: String::default(),
+            ..session
+        },
+    ))
+}
+
+/// Stores the session inside of the private `pl.session` cookie and its
+/// refresh token inside of the private `pl.refresh` cookie.
+///
+/// Both cookies are kept for as long as the session may live, the store
+/// expires an idle session on its own since validated requests push the idle
+/// deadline forward without writing the cookie again. The session cookie is
+/// not sent along cross-site requests other than top level navigations, the
+/// refresh cookie is only ever sent to `/auth/refresh` from the same site.
+fn set_session_cookie(state: &ArkState, cookies: &Cookies, session: &UserSession) {
+    let private = cookies.private(&state.key);
+    private.add(
+        Cookie::build((
+            SESSION_COOKIE_NAME,
+            serde_json::to_string(&UserSession {
+                refresh_token: String::default(),
+                ..session.clone()
+            })
+            .unwrap(),
+        ))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(session.remaining_lifetime))
+        .build(),
+    );
+    private.add(
+        Cookie::build((REFRESH_COOKIE_NAME, session.refresh_token.clone()))
+            .path("/auth/refresh")
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .max_age(Duration::seconds(session.remaining_lifetime))
+            .build(),
+    );
+}
+
+/// Reads the private `pl.session` cookie then removes it along with the
+/// `pl.refresh` cookie.
+fn take_session_cookie(
+    state: &ArkState,
+    cookies: &Cookies,
+) -> Result<UserSession, ErrorJsonResponse> {
+    let session = cookies
+        .private(&state.key)
+        .get(SESSION_COOKIE_NAME)
+        .and_then(|cookie| serde_json::from_str::<UserSession>(cookie.value()).ok());
+    // the refresh cookie outlives the session cookie, it goes either way.
+    remove_session_cookie(state, cookies);
+    session.ok_or_else(|| {
+        ErrorJsonResponse::new(StatusCode::UNAUTHORIZED, "You are not signed in.")
+    })
+}
+
+/// Removes the private `pl.session` and `pl.refresh` cookies.
+fn remove_session_cookie(state: &ArkState, cookies: &Cookies) {
+    let private = cookies.private(&state.key);
+    private.remove(Cookie::build((SESSION_COOKIE_NAME, "")).path("/").build());
+    private.remove(
+        Cookie::build((REFRESH_COOKIE_NAME, ""))
+            .path("/auth/refresh")
+            .build(),
+    );
+}
+
+/// Revokes the tokens every linked provider issued for the user.
+async fn revoke_provider_tokens(state: &ArkState, user: &User) {
+    for auth in &user.auth {
+        if let Ok(provider) = state.auth.get_from(&auth.oauth_provider) {
+            let _ =
+                UserManager::revoke_provider_tokens(&user.info.user_id, &auth.oauth_id, provider)
+                    .await;
+        }
+    }
+}
+
+/// Unlinks the identity of the specified provider from the signed in user
+/// and revokes the tokens that provider issued.
+///
+/// # Arguments
+/// - `provider`: the provider to unlink ex: github.
+async fn unlink(
+    Extension(state): Extension<Arc<ArkState>>,
+    AuthenticatedUser { user, session }: AuthenticatedUser,
+    Path(provider): Path<String>,
+) -> Result<Redirect, ErrorJsonResponse> {
+    let provider = state
+        .auth
+        .get_from(&provider)
+        .map_err(|er| ErrorJsonResponse::new(StatusCode::NOT_FOUND, &er.to_string()))?;
+    let auth = user
+        .auth
+        .iter()
+        .find(|auth| auth.oauth_provider.eq(&provider.name))
+        .ok_or_else(|| {
+            ErrorJsonResponse::new(StatusCode::NOT_FOUND, "This provider is not linked.")
+        })?;
+    // the tokens go away with the identity so they are read beforehand.
+    let identity =
+        UserManager::read_identity_tokens(&session.user_id, &provider.name, Some(&auth.oauth_id));
+    UserManager::unlink_identity(&session.user_id, &auth.oauth_id, &provider.name).map_err(
+        |_| ErrorJsonResponse::new(StatusCode::CONFLICT, "This identity cannot be unlinked."),
+    )?;
+    if let Some(tokens) = identity
+        .ok()
+        .and_then(|identity| {
+            OAuthTokens::open(
+                &identity.tokens,
+                &session.user_id,
+                &identity.oauth_provider,
+                &identity.oauth_id,
+            )
+            .ok()
+        })
+    {
+        let _ = provider.revoke_tokens(&tokens).await;
+    }
+    Ok(Redirect::to("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{header::COOKIE, header::SET_COOKIE, StatusCode},
+        Extension,
+    };
+    use tower_cookies::CookieManagerLayer;
+    use tower_service::Service;
+    use uuid::Uuid;
+
+    use super::routes;
+    use crate::app::{
+        ark::testing,
+        platform::iam::{
+            session::{
+                extractor::AuthenticatedUser,
+                model::{SessionMetadata, UserSession},
+                store::{RefreshRecord, SessionRecord},
+            },
+            user::model::User,
+        },
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn logout_everywhere_revokes_the_sessions_and_clears_the_cookies() {
+        let (_turn, sessions) = testing::tasks().await;
+        let user_id = Uuid::new_v4().to_string();
+        let metadata = SessionMetadata::new(None, None);
+        let session = SessionRecord {
+            digest: Uuid::new_v4().to_string(),
+            user_id: user_id.clone(),
+            security_stamp: String::from("stamp"),
+            metadata: metadata.clone(),
+        };
+        let refresh = RefreshRecord {
+            digest: Uuid::new_v4().to_string(),
+            user_id: user_id.clone(),
+            session_digest: session.digest.clone(),
+            security_stamp: session.security_stamp.clone(),
+            metadata: metadata.clone(),
+        };
+        sessions.create(&session, &refresh, 900, 3600).await.unwrap();
+        let mut user = User::default();
+        user.info.user_id = user_id.clone();
+        let authenticated = AuthenticatedUser {
+            user,
+            session: UserSession::new("token", 900, 3600, &user_id, metadata),
+        };
+
+        let mut router = routes()
+            .layer(Extension(authenticated))
+            .layer(Extension(testing::state()))
+            .layer(CookieManagerLayer::new());
+        let request = Request::builder()
+            .method("POST")
+            .uri("/auth/logout/all")
+            .header(COOKIE, "pl.session=session; pl.refresh=refresh")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let removed: Vec<&str> = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|cookie| cookie.to_str().unwrap())
+            .collect();
+        for name in ["pl.session=", "pl.refresh="] {
+            assert!(removed
+                .iter()
+                .any(|cookie| cookie.starts_with(name) && cookie.contains("Max-Age=0")));
+        }
+        assert!(sessions.list_by_user(&user_id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use std::{env, time::Duration};
+
+use once_cell::sync::Lazy;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+use crate::app::service::task::error::TaskResult;
+
+use super::{manager::UserManager, model::GrantLapsed};
+
+/// How often the sweeper runs by default, in seconds.
+static DEFAULT_SWEEP_INTERVAL: u64 = 60;
+
+/// Carries a `GrantLapsed` for every grant the sweeper deleted, events sent
+/// while nobody listens are dropped.
+static GRANT_EVENTS: Lazy<Sender<GrantLapsed>> = Lazy::new(|| broadcast::channel(256).0);
+
+/// Deletes time-bound role and permission grants once they lapse.
+///
+/// Lapsed grants are already ignored when evaluating what a user can do, the
+/// sweeper removes them from `iam_user_role`, `iam_user_permission` and the
+/// user cache and emits a `GrantLapsed` event for each. Sessions are left
+/// alone, a trial ending does not sign its user out.
+///
+/// # Examples
+/// ```
+/// GrantSweeper::start(GrantSweeper::interval_from_env());
+/// let mut events = GrantSweeper::subscribe();
+/// while let Ok(event) = events.recv().await { ... }
+/// ```
+pub struct GrantSweeper;
+
+impl GrantSweeper {
+    /// Starts sweeping in the background every `interval`.
+    ///
+    /// # Arguments
+    /// - `interval`: the time between two sweeps.
+    pub fn start(interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            println!(
+                "[ARK] grant sweeper initialized, sweeping every {:?}.",
+                interval
+            );
+            loop {
+                ticker.tick().await;
+                // tasks are waited on synchronously.
+                match tokio::task::spawn_blocking(Self::sweep).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(er)) => println!("[ARK] failed to sweep expired grants: {}", er),
+                    Err(er) => println!("[ARK] failed to sweep expired grants: {}", er),
+                }
+            }
+        });
+    }
+
+    /// Sweeps the lapsed grants once.
+    ///
+    /// # Examples
+    /// ```
+    /// let lapsed = GrantSweeper::sweep().unwrap();
+    /// ```
+    pub fn sweep() -> TaskResult<Vec<GrantLapsed>> {
+        let lapsed = UserManager::sweep_expired_grants()?;
+        if lapsed.is_empty() {
+            return Ok(lapsed);
+        }
+        for grant in &lapsed {
+            let _ = GRANT_EVENTS.send(grant.clone());
+        }
+        println!("[ARK] swept {} expired grant(s).", lapsed.len());
+        Ok(lapsed)
+    }
+
+    /// Listens to the grants lapsing from now on.
+    ///
+    /// # Examples
+    /// ```
+    /// let mut events = GrantSweeper::subscribe();
+    /// ```
+    pub fn subscribe() -> Receiver<GrantLapsed> {
+        GRANT_EVENTS.subscribe()
+    }
+
+    /// The interval named by `GRANT_SWEEP_INTERVAL` in seconds, a minute by default.
+    pub fn interval_from_env() -> Duration {
+        let seconds = env::var("GRANT_SWEEP_INTERVAL")
+            .ok()
+            .and_then(|seconds| seconds.parse::<u64>().ok())
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(DEFAULT_SWEEP_INTERVAL);
+        Duration::from_secs(seconds)
+    }
+}
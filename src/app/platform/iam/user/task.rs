@@ -4,11 +4,14 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use axum::async_trait;
+use bb8_postgres::tokio_postgres::Client;
 use serde::{Deserialize, Serialize};
 
 use crate::app::{
+    adapter::oauth_adapter::OAuthSealedTokens,
     database::postgres::PostgresDatabase,
     platform::iam::{
+        organization::task::is_member,
         permission::{cache::PermissionCache, model::Permission},
         role::{cache::RoleCache, model::Role},
     },
@@ -24,9 +27,17 @@ use crate::app::{
 
 use super::{
     manager::UserCacheManager,
-    model::{SecurityToken, User, UserSecurity},
+    model::{
+        Grant, GrantLapsed, ResourceScope, ScopedGrant, SecurityToken, User, UserAccessInfo,
+        UserAuthInfo, UserIdentityTokens, UserSecurity,
+    },
 };
 
+/// The scoped roles and permissions of the user `u`, one json array of
+/// `ScopedGrant` per table, shared by the read and preload queries.
+static SCOPED_GRANT_COLUMNS: &str = "(SELECT json_agg(json_build_object('grant_id', sr.role_id, 'scope', json_build_object('resource_type', sr.resource_type, 'resource_id', sr.resource_id)) ORDER BY sr.role_id, sr.resource_type, sr.resource_id)::text FROM iam_user_scoped_role sr WHERE sr.user_id = u.id) AS scoped_roles,
+    (SELECT json_agg(json_build_object('grant_id', sp.permission_id, 'scope', json_build_object('resource_type', sp.resource_type, 'resource_id', sp.resource_id)) ORDER BY sp.permission_id, sp.resource_type, sp.resource_id)::text FROM iam_user_scoped_permission sp WHERE sp.user_id = u.id) AS scoped_permissions";
+
 pub struct UserTaskHandler;
 
 #[async_trait]
@@ -134,6 +145,64 @@ impl TaskHandler<PostgresDatabase> for UserTaskHandler {
             return UserExchangeOAuthIdForId::run(pg, task_request, payload).await;
         }
 
+        if task_request.task_action.eq("user_link_identity") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<UserLinkIdentity>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return UserLinkIdentity::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("user_unlink_identity") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<UserUnlinkIdentity>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return UserUnlinkIdentity::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("user_store_identity_tokens") {
+            let payload = match TaskRequest::intepret_request_payload::<UserStoreIdentityTokens>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return UserStoreIdentityTokens::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("user_read_identity_tokens") {
+            let payload = match TaskRequest::intepret_request_payload::<UserReadIdentityTokens>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return UserReadIdentityTokens::run(pg, task_request, payload).await;
+        }
+
         if task_request.task_action.eq("user_add_role") {
             let payload =
                 match TaskRequest::intepret_request_payload::<UserAddRole>(&task_request) {
@@ -191,6 +260,93 @@ impl TaskHandler<PostgresDatabase> for UserTaskHandler {
             return UserDeletePermission::run(pg, task_request, payload).await;
         }
 
+        if task_request.task_action.eq("user_deny_permission") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<UserDenyPermission>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return UserDenyPermission::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("user_add_scoped_grant") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<UserAddScopedGrant>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return UserAddScopedGrant::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("user_delete_scoped_grant") {
+            let payload = match TaskRequest::intepret_request_payload::<UserDeleteScopedGrant>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return UserDeleteScopedGrant::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("user_sweep_expired_grants") {
+            let payload = match TaskRequest::intepret_request_payload::<UserSweepExpiredGrants>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return UserSweepExpiredGrants::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("user_sync_profile") {
+            let payload =
+                match TaskRequest::intepret_request_payload::<UserSyncProfile>(&task_request) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            task_request,
+                            vec![TaskError::FailedToInterpretPayload.to_string()],
+                        )
+                    }
+                };
+            return UserSyncProfile::run(pg, task_request, payload).await;
+        }
+
+        if task_request.task_action.eq("user_rotate_security_stamp") {
+            let payload = match TaskRequest::intepret_request_payload::<UserRotateSecurityStamp>(
+                &task_request,
+            ) {
+                Ok(p) => p,
+                Err(_) => {
+                    return TaskResponse::throw_failed_response(
+                        task_request,
+                        vec![TaskError::FailedToInterpretPayload.to_string()],
+                    )
+                }
+            };
+            return UserRotateSecurityStamp::run(pg, task_request, payload).await;
+        }
+
         if task_request.task_action.eq("user_preload_cache") {
             let payload =
                 match TaskRequest::intepret_request_payload::<UserPreloadCache>(&task_request) {
@@ -230,32 +386,37 @@ impl Task<PostgresDatabase, TaskRequest, UserCreateTask> for UserCreateTask {
         // dont include this as part of the transaction because if it fails the transaction fails.
 
         let transaction = pool.transaction().await.unwrap();
-        transaction.execute(
-            "INSERT INTO iam_users (id, verified, created_at, updated_at) VALUES ($1, $2, $3, $4)",
-            &[&param.user.info.user_id, &param.user.info.verified, &param.user.info.created_at, &param.user.info.updated_at]
-        ).await.unwrap();
-
         match transaction.execute(
-            "INSERT INTO iam_user_oauth (user_id, oauth_id, oauth_provider) VALUES ($1, $2, $3)",
-            &[&param.user.info.user_id, &param.user.auth.oauth_id, &param.user.auth.oauth_provider]
+            "INSERT INTO iam_users (id, username, email, verified, avatar_url, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[&param.user.info.user_id, &param.user.info.username, &param.user.info.email, &param.user.info.verified, &param.user.info.avatar_url, &param.user.info.created_at, &param.user.info.updated_at]
         ).await {
             Ok(_) => {},
             Err(_) => return TaskResponse::throw_failed_response(
                 request,
-                vec![TaskError::UserAlreadyExists.to_string()],
+                vec![TaskError::UserUniqueConstraint.to_string()],
             ),
         }
+
+        for auth in &param.user.auth {
+            match transaction.execute(
+                "INSERT INTO iam_user_oauth (user_id, oauth_id, oauth_provider) VALUES ($1, $2, $3)",
+                &[&param.user.info.user_id, &auth.oauth_id, &auth.oauth_provider]
+            ).await {
+                Ok(_) => {},
+                Err(_) => return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::UserAlreadyExists.to_string()],
+                ),
+            }
+        }
         if !param.user.access.role.is_empty() {
             for role_identifier in &param.user.access.role {
-                let role: Option<Role> = match RoleCache::get(role_identifier) {
-                    Ok(v) => Some(v),
-                    Err(_) => None,
-                };
-                if role != None {
+                let role: Option<Role> = RoleCache::get(role_identifier).ok();
+                if let Some(role) = role {
                     transaction
                         .execute(
                             "INSERT INTO iam_user_role (user_id, role_id) VALUES ($1, $2)",
-                            &[&param.user.info.user_id, &role.unwrap().role_id],
+                            &[&param.user.info.user_id, &role.role_id],
                         )
                         .await
                         .unwrap();
@@ -265,14 +426,24 @@ impl Task<PostgresDatabase, TaskRequest, UserCreateTask> for UserCreateTask {
         if !param.user.access.permission.is_empty() {
             for permission_identifier in &param.user.access.permission {
                 let permission: Option<Permission> =
-                    match PermissionCache::get(permission_identifier) {
-                        Ok(v) => Some(v),
-                        Err(_) => None,
-                    };
-                if permission != None {
+                    PermissionCache::get(permission_identifier).ok();
+                if let Some(permission) = permission {
                     transaction.execute(
                     "INSERT INTO iam_user_permission (user_id, permission_id) VALUES ($1, $2)",
-                    &[&param.user.info.user_id, &permission.unwrap().permission_id],
+                    &[&param.user.info.user_id, &permission.permission_id],
+                ).await.unwrap();
+                }
+            }
+        }
+        if !param.user.access.denied_permission.is_empty() {
+            for permission_identifier in &param.user.access.denied_permission {
+                let permission: Option<Permission> =
+                    PermissionCache::get(permission_identifier).ok();
+                if let Some(permission) = permission {
+                    transaction.execute(
+                    "INSERT INTO iam_user_permission (user_id, permission_id, denied) VALUES ($1, $2, TRUE)
+                    ON CONFLICT (user_id, permission_id) DO UPDATE SET denied = TRUE",
+                    &[&param.user.info.user_id, &permission.permission_id],
                 ).await.unwrap();
                 }
             }
@@ -328,7 +499,7 @@ impl Task<PostgresDatabase, TaskRequest, UserReadTask> for UserReadTask {
                 }
 
                 let fallback_stmt = pool
-                    .prepare(
+                    .prepare(&format!(
                         "SELECT 
                         u.id, 
                         u.username, 
@@ -337,32 +508,40 @@ impl Task<PostgresDatabase, TaskRequest, UserReadTask> for UserReadTask {
                         u.created_at, 
                         u.updated_at, 
                         array_agg(DISTINCT ur.role_id) FILTER (WHERE ur.role_id IS NOT NULL) AS roles, 
-                        array_agg(DISTINCT up.permission_id) FILTER (WHERE up.permission_id IS NOT NULL) AS permissions,
-                        o.oauth_id, 
-                        o.oauth_provider,
+                        array_agg(DISTINCT up.permission_id) FILTER (WHERE up.permission_id IS NOT NULL AND NOT up.denied) AS permissions,
+                        (SELECT array_agg(o.oauth_id ORDER BY o.oauth_provider, o.oauth_id) FROM iam_user_oauth o WHERE o.user_id = u.id) AS oauth_ids, 
+                        (SELECT array_agg(o.oauth_provider ORDER BY o.oauth_provider, o.oauth_id) FROM iam_user_oauth o WHERE o.user_id = u.id) AS oauth_providers,
                         u.security_token, 
-                        u.security_stamp
+                        u.security_stamp,
+                        u.avatar_url,
+                        array_agg(DISTINCT up.permission_id) FILTER (WHERE up.denied) AS denied_permissions,
+                        (SELECT array_agg(r.role_id ORDER BY r.role_id) FROM iam_user_role r WHERE r.user_id = u.id AND r.expires_at IS NOT NULL) AS expiring_roles,
+                        (SELECT array_agg(r.expires_at ORDER BY r.role_id) FROM iam_user_role r WHERE r.user_id = u.id AND r.expires_at IS NOT NULL) AS role_expiries,
+                        (SELECT array_agg(p.permission_id ORDER BY p.permission_id) FROM iam_user_permission p WHERE p.user_id = u.id AND p.expires_at IS NOT NULL) AS expiring_permissions,
+                        (SELECT array_agg(p.expires_at ORDER BY p.permission_id) FROM iam_user_permission p WHERE p.user_id = u.id AND p.expires_at IS NOT NULL) AS permission_expiries,
+                        {scoped_grants},
+                        (SELECT array_agg(m.org_id ORDER BY m.org_id) FROM iam_organization_member m WHERE m.user_id = u.id) AS organizations
                     FROM iam_users u
                     LEFT JOIN iam_user_role ur ON u.id = ur.user_id
                     LEFT JOIN iam_user_permission up ON u.id = up.user_id
-                    LEFT JOIN iam_user_oauth o ON u.id = o.user_id OR o.oauth_id = $1
-                    WHERE u.id = $1 OR o.oauth_id = $1
-                    GROUP BY u.id, o.oauth_id, o.oauth_provider;",
-                    )
+                    WHERE u.id = $1 OR u.id IN (SELECT user_id FROM iam_user_oauth WHERE oauth_id = $1)
+                    GROUP BY u.id;",
+                        scoped_grants = SCOPED_GRANT_COLUMNS,
+                    ))
                     .await
                     .unwrap();
                 let fallback_query = pool.query_one(&fallback_stmt, &[&param.identifier]).await;
                 match fallback_query {
                     Ok(row) => {
-                        let user = User::new(
+                        let mut user = User::new(
                             row.get(0),
                             row.get(1),
                             row.get(2),
                             row.get::<_, bool>(3),
+                            row.get(12),
                             row.get::<_, i64>(4),
                             row.get::<_, i64>(5),
-                            row.get::<_, String>(8),
-                            row.get::<_, String>(9),
+                            UserAuthInfo::from_columns(row.get(8), row.get(9)),
                             row.get::<_, Option<Vec<String>>>(6).unwrap_or_default(),
                             row.get::<_, Option<Vec<String>>>(7).unwrap_or_default(),
                             UserSecurity::new(
@@ -372,6 +551,16 @@ impl Task<PostgresDatabase, TaskRequest, UserReadTask> for UserReadTask {
                                 row.get(11),
                             ),
                         );
+                        user.access.denied_permission =
+                            row.get::<_, Option<Vec<String>>>(13).unwrap_or_default();
+                        user.access.role_expires_at =
+                            UserAccessInfo::expiries_from_columns(row.get(14), row.get(15));
+                        user.access.permission_expires_at =
+                            UserAccessInfo::expiries_from_columns(row.get(16), row.get(17));
+                        user.access.scoped_role = ScopedGrant::from_json(row.get(18));
+                        user.access.scoped_permission = ScopedGrant::from_json(row.get(19));
+                        user.access.organizations =
+                            row.get::<_, Option<Vec<String>>>(20).unwrap_or_default();
                         UserCacheManager::add_user_to_cache(user.clone()).unwrap();
                         notify_cache_miss("UserRead", "UserCache", &request.task_id);
                         return TaskResponse::compose_response(
@@ -447,7 +636,7 @@ impl Task<PostgresDatabase, TaskRequest, UserUpdateTask> for UserUpdateTask {
             .await
         {
             Ok(row) => {
-                if row.len() != 0 {
+                if !row.is_empty() {
                     // test
                     match UserCacheManager::read_user_from_cache(&row.get::<_, String>(0)) {
                         Ok(mut user) => {
@@ -461,6 +650,8 @@ impl Task<PostgresDatabase, TaskRequest, UserUpdateTask> for UserUpdateTask {
                                 user.info.username = Some(param.clone().value);
                             } else if param.update_for.eq_ignore_ascii_case("email") {
                                 user.info.email = Some(param.clone().value);
+                            } else if param.update_for.eq_ignore_ascii_case("avatar_url") {
+                                user.info.avatar_url = Some(param.clone().value);
                             }
                             UserCacheManager::add_user_to_cache(user).unwrap();
                         }
@@ -544,7 +735,7 @@ impl Task<PostgresDatabase, TaskRequest, UserUpdateAsBooleanTask> for UserUpdate
             .await
         {
             Ok(row) => {
-                if row.len() != 0 {
+                if !row.is_empty() {
                     match UserCacheManager::read_user_from_cache(&row.get::<_, String>(0)) {
                         Ok(mut user) => {
                             if param.update_for.eq_ignore_ascii_case("verified") {
@@ -632,7 +823,7 @@ impl Task<PostgresDatabase, TaskRequest, UserUpdateAsIntegerTask> for UserUpdate
             .await
         {
             Ok(row) => {
-                if row.len() != 0 {
+                if !row.is_empty() {
                     match UserCacheManager::read_user_from_cache(&row.get::<_, String>(0)) {
                         Ok(mut user) => {
                             if param.update_for.eq_ignore_ascii_case("created_at") {
@@ -687,15 +878,12 @@ impl Task<PostgresDatabase, TaskRequest, UserCreateSecurityToken> for UserCreate
         // updating security_stam
         let stmt_1 = pool
             .prepare(
-                format!(
-                    "UPDATE iam_users
+                "UPDATE iam_users
                 SET security_stamp = $1
                 WHERE id = $2
                    OR username = $2
                    OR email = $2
-                   RETURNING *;"
-                )
-                .as_str(),
+                   RETURNING *;",
             )
             .await
             .unwrap();
@@ -708,15 +896,12 @@ impl Task<PostgresDatabase, TaskRequest, UserCreateSecurityToken> for UserCreate
         // updating security_token
         let stmt_2 = pool
             .prepare(
-                format!(
-                    "UPDATE iam_users
+                "UPDATE iam_users
                 SET security_token = $1
                 WHERE id = $2
                    OR username = $2
                    OR email = $2
-                   RETURNING *;"
-                )
-                .as_str(),
+                   RETURNING *;",
             )
             .await
             .unwrap();
@@ -731,7 +916,7 @@ impl Task<PostgresDatabase, TaskRequest, UserCreateSecurityToken> for UserCreate
             .await
         {
             Ok(row) => {
-                if row.len() != 0 {
+                if !row.is_empty() {
                     match UserCacheManager::read_user_from_cache(&row.get::<_, String>(0)) {
                         Ok(mut user) => {
                             user.security = user_security.clone();
@@ -763,6 +948,61 @@ impl Task<PostgresDatabase, TaskRequest, UserCreateSecurityToken> for UserCreate
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserRotateSecurityStamp {
+    pub user_id: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserRotateSecurityStamp> for UserRotateSecurityStamp {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserRotateSecurityStamp,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        let security_stamp = UserSecurity::generate_security_stamp();
+        let stmt = pool
+            .prepare(
+                "UPDATE iam_users
+                SET security_stamp = $1
+                WHERE id = $2
+                RETURNING id;",
+            )
+            .await
+            .unwrap();
+        match pool
+            .query_opt(&stmt, &[&security_stamp, &param.user_id])
+            .await
+        {
+            Ok(Some(_)) => {
+                if let Ok(mut user) = UserCacheManager::read_user_from_cache(&param.user_id) {
+                    user.security.stamp = Some(security_stamp.clone());
+                    UserCacheManager::add_user_to_cache(user).unwrap();
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    security_stamp,
+                    Vec::default(),
+                );
+            }
+            Ok(None) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::UserNotFound.to_string()],
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub(super) struct UserExchangeOAuthIdForId {
     pub oauth_id: String,
@@ -807,95 +1047,361 @@ impl Task<PostgresDatabase, TaskRequest, UserExchangeOAuthIdForId> for UserExcha
 }
 
 #[derive(Serialize, Deserialize)]
-pub(super) struct UserAddPermission {
+pub(super) struct UserLinkIdentity {
     pub target_user_id: String,
-    pub permission_identifier: String,
+    pub oauth_id: String,
+    pub oauth_provider: String,
 }
 
 #[async_trait]
-impl Task<PostgresDatabase, TaskRequest, UserAddPermission> for UserAddPermission {
+impl Task<PostgresDatabase, TaskRequest, UserLinkIdentity> for UserLinkIdentity {
     async fn run(
         db: &PostgresDatabase,
         request: TaskRequest,
-        param: UserAddPermission,
+        param: UserLinkIdentity,
     ) -> TaskResponse {
         let pool = db.pool.get().await.unwrap();
         let stmt = pool
-            .prepare("INSERT INTO iam_user_permission (user_id, permission_id) VALUES ($1, $2)")
+            .prepare("INSERT INTO iam_user_oauth (user_id, oauth_id, oauth_provider) VALUES ($1, $2, $3)")
             .await
             .unwrap();
-
-        match PermissionCache::get(&param.permission_identifier) {
-            Ok(permission) => match UserCacheManager::read_user_from_cache(&param.target_user_id) {
-                Ok(mut cached_user) => match pool
-                    .execute(
-                        &stmt,
-                        &[&param.target_user_id, &permission.permission_id],
-                    )
-                    .await
-                {
-                    Ok(_) => {
-                        cached_user.access.permission.push(permission.permission_id);
+        match pool
+            .execute(
+                &stmt,
+                &[&param.target_user_id, &param.oauth_id, &param.oauth_provider],
+            )
+            .await
+        {
+            Ok(_) => {
+                match UserCacheManager::read_user_from_cache(&param.target_user_id) {
+                    Ok(mut cached_user) => {
+                        cached_user.auth.push(UserAuthInfo {
+                            oauth_id: param.oauth_id.clone(),
+                            oauth_provider: param.oauth_provider.clone(),
+                        });
                         UserCacheManager::add_user_to_cache(cached_user).unwrap();
-                        // user exists in the cache so we need to update
-                        return TaskResponse::compose_response(
-                            request,
-                            TaskStatus::Completed,
-                            String::default(),
-                            Vec::default(),
-                        );
                     }
                     Err(_) => {
-                        return TaskResponse::throw_failed_response(
-                            request,
-                            vec![TaskError::UserPermissionAlreadyExists.to_string()],
-                        );
-                    }
-                },
-                Err(_) => {
-                    // we don't need to directly check if a user exists here
-                    // because of the foreign keys, we cannot insert an empty
-                    // user because of that so if it throws out an error
-                    // that means the user does not exist.
-                    match pool
-                        .execute(
-                            &stmt,
-                            &[&param.target_user_id, &param.permission_identifier],
-                        )
-                        .await
-                    {
-                        Ok(_) => {
-                            return TaskResponse::compose_response(
-                                request,
-                                TaskStatus::Completed,
-                                String::default(),
-                                Vec::default(),
-                            );
-                        }
-                        Err(_) => {
-                            return TaskResponse::throw_failed_response(
-                                request,
-                                vec![TaskError::UserNotFound.to_string()],
-                            );
-                        }
+                        /* if not found in cache then it will just update the database. */
                     }
                 }
-            },
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
             Err(_) => {
+                // either the identity belongs to someone or the user does not exist.
                 return TaskResponse::throw_failed_response(
                     request,
-                    vec![TaskError::PermissionNotFound.to_string()],
-                )
+                    vec![TaskError::UserIdentityAlreadyLinked.to_string()],
+                );
             }
         }
     }
 }
 
-
 #[derive(Serialize, Deserialize)]
-pub(super) struct UserDeletePermission {
+pub(super) struct UserUnlinkIdentity {
     pub target_user_id: String,
-    pub permission_identifier: String,
+    pub oauth_id: String,
+    pub oauth_provider: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserUnlinkIdentity> for UserUnlinkIdentity {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserUnlinkIdentity,
+    ) -> TaskResponse {
+        let mut pool = db.pool.get().await.unwrap();
+        let transaction = pool.transaction().await.unwrap();
+        // lock every identity of the user so two unlinks can't remove the last two at once.
+        let identities = transaction
+            .query(
+                "SELECT oauth_id, oauth_provider FROM iam_user_oauth WHERE user_id = $1 FOR UPDATE",
+                &[&param.target_user_id],
+            )
+            .await
+            .unwrap();
+        if !identities.iter().any(|row| {
+            row.get::<_, String>(0).eq(&param.oauth_id)
+                && row.get::<_, String>(1).eq(&param.oauth_provider)
+        }) {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::UserIdentityNotFound.to_string()],
+            );
+        }
+        if identities.len() <= 1 {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::UserCannotUnlinkLastIdentity.to_string()],
+            );
+        }
+        transaction
+            .execute(
+                "DELETE FROM iam_user_oauth WHERE user_id = $1 AND oauth_id = $2 AND oauth_provider = $3",
+                &[&param.target_user_id, &param.oauth_id, &param.oauth_provider],
+            )
+            .await
+            .unwrap();
+        match transaction.commit().await {
+            Ok(_) => {
+                match UserCacheManager::read_user_from_cache(&param.target_user_id) {
+                    Ok(mut cached_user) => {
+                        cached_user.auth.retain(|auth| {
+                            !(auth.oauth_id.eq(&param.oauth_id)
+                                && auth.oauth_provider.eq(&param.oauth_provider))
+                        });
+                        UserCacheManager::add_user_to_cache(cached_user).unwrap();
+                    }
+                    Err(_) => {
+                        /* if not found in cache then it will just update the database. */
+                    }
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::UserIdentityNotFound.to_string()],
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserStoreIdentityTokens {
+    pub target_user_id: String,
+    pub oauth_id: String,
+    pub oauth_provider: String,
+    pub tokens: Option<OAuthSealedTokens>,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserStoreIdentityTokens> for UserStoreIdentityTokens {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserStoreIdentityTokens,
+    ) -> TaskResponse {
+        // tokens are never cached, they only live in the database.
+        let pool = db.pool.get().await.unwrap();
+        let stmt = pool
+            .prepare(
+                "UPDATE iam_user_oauth
+                SET access_token = $4, refresh_token = $5, token_expires_at = $6
+                WHERE user_id = $1 AND oauth_id = $2 AND oauth_provider = $3",
+            )
+            .await
+            .unwrap();
+        let tokens = param.tokens.clone().unwrap_or_default();
+        let access_token = param.tokens.as_ref().map(|_| tokens.access_token.clone());
+        match pool
+            .execute(
+                &stmt,
+                &[
+                    &param.target_user_id,
+                    &param.oauth_id,
+                    &param.oauth_provider,
+                    &access_token,
+                    &tokens.refresh_token,
+                    &tokens.expires_at,
+                ],
+            )
+            .await
+        {
+            Ok(1) => {
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
+            _ => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::UserIdentityNotFound.to_string()],
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserReadIdentityTokens {
+    pub target_user_id: String,
+    pub oauth_provider: String,
+    pub oauth_id: Option<String>,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserReadIdentityTokens> for UserReadIdentityTokens {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserReadIdentityTokens,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        // without an oauth id the most recently issued tokens of the provider are used.
+        let stmt = pool
+            .prepare(
+                "SELECT oauth_id, oauth_provider, access_token, refresh_token, token_expires_at
+                FROM iam_user_oauth
+                WHERE user_id = $1 AND oauth_provider = $2
+                  AND ($3::VARCHAR IS NULL OR oauth_id = $3)
+                  AND access_token IS NOT NULL
+                ORDER BY token_expires_at DESC NULLS FIRST
+                LIMIT 1",
+            )
+            .await
+            .unwrap();
+        match pool
+            .query_one(
+                &stmt,
+                &[&param.target_user_id, &param.oauth_provider, &param.oauth_id],
+            )
+            .await
+        {
+            Ok(row) => {
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    UserIdentityTokens {
+                        oauth_id: row.get(0),
+                        oauth_provider: row.get(1),
+                        tokens: OAuthSealedTokens {
+                            access_token: row.get(2),
+                            refresh_token: row.get(3),
+                            expires_at: row.get(4),
+                        },
+                    },
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::UserIdentityTokenNotFound.to_string()],
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserAddPermission {
+    pub target_user_id: String,
+    pub permission_identifier: String,
+    /// When the permission lapses (milliseconds), `None` keeps it until it is deleted.
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserAddPermission> for UserAddPermission {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserAddPermission,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        if !can_be_granted(&pool, &Grant::Permission(param.permission_identifier.clone()), &param.target_user_id).await {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::OrganizationMemberNotFound.to_string()],
+            );
+        }
+        let stmt = pool
+            .prepare("INSERT INTO iam_user_permission (user_id, permission_id, expires_at) VALUES ($1, $2, $3)")
+            .await
+            .unwrap();
+
+        match PermissionCache::get(&param.permission_identifier) {
+            Ok(permission) => match UserCacheManager::read_user_from_cache(&param.target_user_id) {
+                Ok(mut cached_user) => match pool
+                    .execute(
+                        &stmt,
+                        &[&param.target_user_id, &permission.permission_id, &param.expires_at],
+                    )
+                    .await
+                {
+                    Ok(_) => {
+                        if let Some(expires_at) = param.expires_at {
+                            cached_user.access.permission_expires_at.insert(permission.permission_id.clone(), expires_at);
+                        }
+                        cached_user.access.permission.push(permission.permission_id);
+                        UserCacheManager::add_user_to_cache(cached_user).unwrap();
+                        // user exists in the cache so we need to update
+                        return TaskResponse::compose_response(
+                            request,
+                            TaskStatus::Completed,
+                            String::default(),
+                            Vec::default(),
+                        );
+                    }
+                    Err(_) => {
+                        return TaskResponse::throw_failed_response(
+                            request,
+                            vec![TaskError::UserPermissionAlreadyExists.to_string()],
+                        );
+                    }
+                },
+                Err(_) => {
+                    // we don't need to directly check if a user exists here
+                    // because of the foreign keys, we cannot insert an empty
+                    // user because of that so if it throws out an error
+                    // that means the user does not exist.
+                    match pool
+                        .execute(
+                            &stmt,
+                            &[&param.target_user_id, &permission.permission_id, &param.expires_at],
+                        )
+                        .await
+                    {
+                        Ok(_) => {
+                            return TaskResponse::compose_response(
+                                request,
+                                TaskStatus::Completed,
+                                String::default(),
+                                Vec::default(),
+                            );
+                        }
+                        Err(_) => {
+                            return TaskResponse::throw_failed_response(
+                                request,
+                                vec![TaskError::UserNotFound.to_string()],
+                            );
+                        }
+                    }
+                }
+            },
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::PermissionNotFound.to_string()],
+                )
+            }
+        }
+    }
+}
+
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserDeletePermission {
+    pub target_user_id: String,
+    pub permission_identifier: String,
 }
 
 #[async_trait]
@@ -921,6 +1427,8 @@ impl Task<PostgresDatabase, TaskRequest, UserDeletePermission> for UserDeletePer
                 {
                     Ok(_) => {
                         cached_user.access.permission.retain(|perm| !perm.eq(&permission.permission_id) );
+                        cached_user.access.denied_permission.retain(|perm| !perm.eq(&permission.permission_id));
+                        cached_user.access.permission_expires_at.remove(&permission.permission_id);
                         UserCacheManager::add_user_to_cache(cached_user).unwrap();
                         // user exists in the cache so we need to update
                         return TaskResponse::compose_response(
@@ -976,10 +1484,359 @@ impl Task<PostgresDatabase, TaskRequest, UserDeletePermission> for UserDeletePer
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserDenyPermission {
+    pub target_user_id: String,
+    pub permission_identifier: String,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserDenyPermission> for UserDenyPermission {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserDenyPermission,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        // a granted link is turned into a denied one.
+        let stmt = pool
+            .prepare(
+                "INSERT INTO iam_user_permission (user_id, permission_id, denied) VALUES ($1, $2, TRUE)
+                ON CONFLICT (user_id, permission_id) DO UPDATE SET denied = TRUE, expires_at = NULL",
+            )
+            .await
+            .unwrap();
+
+        let permission = match PermissionCache::get(&param.permission_identifier) {
+            Ok(permission) => permission,
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::PermissionNotFound.to_string()],
+                )
+            }
+        };
+        // the foreign keys refuse links to a user that does not exist.
+        match pool
+            .execute(&stmt, &[&param.target_user_id, &permission.permission_id])
+            .await
+        {
+            Ok(_) => {
+                if let Ok(mut cached_user) =
+                    UserCacheManager::read_user_from_cache(&param.target_user_id)
+                {
+                    cached_user
+                        .access
+                        .permission
+                        .retain(|perm| !perm.eq(&permission.permission_id));
+                    cached_user
+                        .access
+                        .permission_expires_at
+                        .remove(&permission.permission_id);
+                    if !cached_user.access.denied_permission.contains(&permission.permission_id) {
+                        cached_user.access.denied_permission.push(permission.permission_id);
+                    }
+                    UserCacheManager::add_user_to_cache(cached_user).unwrap();
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::UserNotFound.to_string()],
+                );
+            }
+        }
+    }
+}
+
+/// Resolves the identifier of the role or permission of a grant to its id.
+fn resolve_grant(grant: &Grant) -> Result<Grant, TaskError> {
+    match grant {
+        Grant::Role(role_identifier) => RoleCache::get(role_identifier)
+            .map(|role| Grant::Role(role.role_id))
+            .map_err(|_| TaskError::RoleNotFound),
+        Grant::Permission(permission_identifier) => PermissionCache::get(permission_identifier)
+            .map(|permission| Grant::Permission(permission.permission_id))
+            .map_err(|_| TaskError::PermissionNotFound),
+    }
+}
+
+/// Whether a grant can be given to a user, the roles and permissions of an
+/// organization are only granted to its members.
+async fn can_be_granted(pool: &Client, grant: &Grant, user_id: &str) -> bool {
+    let owner = match grant {
+        Grant::Role(role_identifier) => {
+            RoleCache::get(role_identifier).ok().and_then(|role| role.org_id)
+        }
+        Grant::Permission(permission_identifier) => PermissionCache::get(permission_identifier)
+            .ok()
+            .and_then(|permission| permission.org_id),
+    };
+    match owner {
+        Some(org_id) => is_member(pool, &org_id, user_id).await,
+        None => true,
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserAddScopedGrant {
+    pub target_user_id: String,
+    /// The role or permission, by identifier.
+    pub grant: Grant,
+    pub scope: ResourceScope,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserAddScopedGrant> for UserAddScopedGrant {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserAddScopedGrant,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        let grant = match resolve_grant(&param.grant) {
+            Ok(grant) => grant,
+            Err(er) => return TaskResponse::throw_failed_response(request, vec![er.to_string()]),
+        };
+        if !can_be_granted(&pool, &grant, &param.target_user_id).await {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::OrganizationMemberNotFound.to_string()],
+            );
+        }
+        let (query, grant_id, already_exists) = match &grant {
+            Grant::Role(role_id) => (
+                "INSERT INTO iam_user_scoped_role (user_id, role_id, resource_type, resource_id) VALUES ($1, $2, $3, $4)",
+                role_id,
+                TaskError::UserRoleAlreadyExists,
+            ),
+            Grant::Permission(permission_id) => (
+                "INSERT INTO iam_user_scoped_permission (user_id, permission_id, resource_type, resource_id) VALUES ($1, $2, $3, $4)",
+                permission_id,
+                TaskError::UserPermissionAlreadyExists,
+            ),
+        };
+        let stmt = pool.prepare(query).await.unwrap();
+        let cached_user = UserCacheManager::read_user_from_cache(&param.target_user_id);
+        match pool
+            .execute(
+                &stmt,
+                &[
+                    &param.target_user_id,
+                    grant_id,
+                    &param.scope.resource_type,
+                    &param.scope.resource_id,
+                ],
+            )
+            .await
+        {
+            Ok(_) => {
+                if let Ok(mut cached_user) = cached_user {
+                    let scoped_grant = ScopedGrant {
+                        grant_id: grant_id.clone(),
+                        scope: param.scope.clone(),
+                    };
+                    match grant {
+                        Grant::Role(_) => cached_user.access.scoped_role.push(scoped_grant),
+                        Grant::Permission(_) => {
+                            cached_user.access.scoped_permission.push(scoped_grant)
+                        }
+                    }
+                    UserCacheManager::add_user_to_cache(cached_user).unwrap();
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
+            // the user exists, so it is the grant that already does.
+            Err(_) => {
+                let er = match cached_user {
+                    Ok(_) => already_exists,
+                    Err(_) => TaskError::UserNotFound,
+                };
+                return TaskResponse::throw_failed_response(request, vec![er.to_string()]);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserDeleteScopedGrant {
+    pub target_user_id: String,
+    /// The role or permission, by identifier.
+    pub grant: Grant,
+    pub scope: ResourceScope,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserDeleteScopedGrant> for UserDeleteScopedGrant {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserDeleteScopedGrant,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        let grant = match resolve_grant(&param.grant) {
+            Ok(grant) => grant,
+            Err(er) => return TaskResponse::throw_failed_response(request, vec![er.to_string()]),
+        };
+        let (query, grant_id) = match &grant {
+            Grant::Role(role_id) => (
+                "DELETE FROM iam_user_scoped_role
+                WHERE user_id = $1 AND role_id = $2 AND resource_type = $3 AND resource_id = $4",
+                role_id,
+            ),
+            Grant::Permission(permission_id) => (
+                "DELETE FROM iam_user_scoped_permission
+                WHERE user_id = $1 AND permission_id = $2 AND resource_type = $3 AND resource_id = $4",
+                permission_id,
+            ),
+        };
+        let stmt = pool.prepare(query).await.unwrap();
+        match pool
+            .execute(
+                &stmt,
+                &[
+                    &param.target_user_id,
+                    grant_id,
+                    &param.scope.resource_type,
+                    &param.scope.resource_id,
+                ],
+            )
+            .await
+        {
+            Ok(_) => {
+                if let Ok(mut cached_user) =
+                    UserCacheManager::read_user_from_cache(&param.target_user_id)
+                {
+                    let held = |scoped: &ScopedGrant| {
+                        !(scoped.grant_id.eq(grant_id) && scoped.scope.eq(&param.scope))
+                    };
+                    match grant {
+                        Grant::Role(_) => cached_user.access.scoped_role.retain(held),
+                        Grant::Permission(_) => cached_user.access.scoped_permission.retain(held),
+                    }
+                    UserCacheManager::add_user_to_cache(cached_user).unwrap();
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    String::default(),
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                );
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserSweepExpiredGrants {
+    /// Grants that lapsed at or before this time (milliseconds) are deleted.
+    pub now: i64,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserSweepExpiredGrants> for UserSweepExpiredGrants {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserSweepExpiredGrants,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        let mut lapsed: Vec<GrantLapsed> = Vec::new();
+        let role_stmt = pool
+            .prepare(
+                "DELETE FROM iam_user_role WHERE expires_at <= $1
+                RETURNING user_id, role_id, expires_at",
+            )
+            .await
+            .unwrap();
+        let permission_stmt = pool
+            .prepare(
+                "DELETE FROM iam_user_permission WHERE expires_at <= $1
+                RETURNING user_id, permission_id, expires_at",
+            )
+            .await
+            .unwrap();
+        match pool.query(&role_stmt, &[&param.now]).await {
+            Ok(rows) => lapsed.extend(rows.iter().map(|row| GrantLapsed {
+                user_id: row.get(0),
+                grant: Grant::Role(row.get(1)),
+                expired_at: row.get(2),
+            })),
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                )
+            }
+        }
+        match pool.query(&permission_stmt, &[&param.now]).await {
+            Ok(rows) => lapsed.extend(rows.iter().map(|row| GrantLapsed {
+                user_id: row.get(0),
+                grant: Grant::Permission(row.get(1)),
+                expired_at: row.get(2),
+            })),
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::TaskInternalError.to_string()],
+                )
+            }
+        }
+        // users that are not cached are read with their remaining grants.
+        for grant in &lapsed {
+            if let Ok(mut cached_user) = UserCacheManager::read_user_from_cache(&grant.user_id) {
+                match &grant.grant {
+                    Grant::Role(role_id) => {
+                        cached_user.access.role.retain(|role| !role.eq(role_id));
+                        cached_user.access.role_expires_at.remove(role_id);
+                    }
+                    Grant::Permission(permission_id) => {
+                        cached_user
+                            .access
+                            .permission
+                            .retain(|permission| !permission.eq(permission_id));
+                        cached_user.access.permission_expires_at.remove(permission_id);
+                    }
+                }
+                // lapsed grants are ignored anyway, a stale cache is not worth failing the sweep.
+                if let Err(er) = UserCacheManager::add_user_to_cache(cached_user) {
+                    println!("[CACHE] failed to cache user {}: {}", grant.user_id, er);
+                }
+            }
+        }
+        return TaskResponse::compose_response(
+            request,
+            TaskStatus::Completed,
+            lapsed,
+            Vec::default(),
+        );
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub(super) struct UserAddRole {
     pub target_user_id: String,
     pub role_identifier: String,
+    /// When the role lapses (milliseconds), `None` keeps it until it is deleted.
+    #[serde(default)]
+    pub expires_at: Option<i64>,
 }
 
 #[async_trait]
@@ -990,8 +1847,14 @@ impl Task<PostgresDatabase, TaskRequest, UserAddRole> for UserAddRole {
         param: UserAddRole,
     ) -> TaskResponse {
         let pool = db.pool.get().await.unwrap();
+        if !can_be_granted(&pool, &Grant::Role(param.role_identifier.clone()), &param.target_user_id).await {
+            return TaskResponse::throw_failed_response(
+                request,
+                vec![TaskError::OrganizationMemberNotFound.to_string()],
+            );
+        }
         let stmt = pool
-            .prepare("INSERT INTO iam_user_role (user_id, role_id) VALUES ($1, $2)")
+            .prepare("INSERT INTO iam_user_role (user_id, role_id, expires_at) VALUES ($1, $2, $3)")
             .await
             .unwrap();
 
@@ -1000,11 +1863,14 @@ impl Task<PostgresDatabase, TaskRequest, UserAddRole> for UserAddRole {
                 Ok(mut cached_user) => match pool
                     .execute(
                         &stmt,
-                        &[&param.target_user_id, &role.role_id],
+                        &[&param.target_user_id, &role.role_id, &param.expires_at],
                     )
                     .await
                 {
                     Ok(_) => {
+                        if let Some(expires_at) = param.expires_at {
+                            cached_user.access.role_expires_at.insert(role.role_id.clone(), expires_at);
+                        }
                         cached_user.access.role.push(role.role_id);
                         UserCacheManager::add_user_to_cache(cached_user).unwrap();
                         // user exists in the cache so we need to update
@@ -1026,7 +1892,7 @@ impl Task<PostgresDatabase, TaskRequest, UserAddRole> for UserAddRole {
                     match pool
                         .execute(
                             &stmt,
-                            &[&param.target_user_id, &role.role_id],
+                            &[&param.target_user_id, &role.role_id, &param.expires_at],
                         )
                         .await
                     {
@@ -1087,6 +1953,7 @@ impl Task<PostgresDatabase, TaskRequest, UserDeleteRole> for UserDeleteRole {
                 {
                     Ok(_) => {
                         cached_user.access.role.retain(|perm| !perm.eq(&role.role_id) );
+                        cached_user.access.role_expires_at.remove(&role.role_id);
                         UserCacheManager::add_user_to_cache(cached_user).unwrap();
                         // user exists in the cache so we need to update
                         return TaskResponse::compose_response(
@@ -1149,7 +2016,7 @@ impl Task<PostgresDatabase, TaskRequest, UserPreloadCache> for UserPreloadCache
         _param: UserPreloadCache,
     ) -> TaskResponse {
         let pool = db.pool.get().await.unwrap();
-        let stmt = pool.prepare(
+        let stmt = pool.prepare(&format!(
             "SELECT 
             u.id, 
             u.username, 
@@ -1158,33 +2025,41 @@ impl Task<PostgresDatabase, TaskRequest, UserPreloadCache> for UserPreloadCache
             u.created_at, 
             u.updated_at, 
             array_agg(DISTINCT ur.role_id) FILTER (WHERE ur.role_id IS NOT NULL) AS roles, 
-            array_agg(DISTINCT up.permission_id) FILTER (WHERE up.permission_id IS NOT NULL) AS permissions,
-            o.oauth_id, 
-            o.oauth_provider,
+            array_agg(DISTINCT up.permission_id) FILTER (WHERE up.permission_id IS NOT NULL AND NOT up.denied) AS permissions,
+            (SELECT array_agg(o.oauth_id ORDER BY o.oauth_provider, o.oauth_id) FROM iam_user_oauth o WHERE o.user_id = u.id) AS oauth_ids, 
+            (SELECT array_agg(o.oauth_provider ORDER BY o.oauth_provider, o.oauth_id) FROM iam_user_oauth o WHERE o.user_id = u.id) AS oauth_providers,
             u.security_token, 
-            u.security_stamp
+            u.security_stamp,
+            u.avatar_url,
+            array_agg(DISTINCT up.permission_id) FILTER (WHERE up.denied) AS denied_permissions,
+            (SELECT array_agg(r.role_id ORDER BY r.role_id) FROM iam_user_role r WHERE r.user_id = u.id AND r.expires_at IS NOT NULL) AS expiring_roles,
+            (SELECT array_agg(r.expires_at ORDER BY r.role_id) FROM iam_user_role r WHERE r.user_id = u.id AND r.expires_at IS NOT NULL) AS role_expiries,
+            (SELECT array_agg(p.permission_id ORDER BY p.permission_id) FROM iam_user_permission p WHERE p.user_id = u.id AND p.expires_at IS NOT NULL) AS expiring_permissions,
+            (SELECT array_agg(p.expires_at ORDER BY p.permission_id) FROM iam_user_permission p WHERE p.user_id = u.id AND p.expires_at IS NOT NULL) AS permission_expiries,
+            {scoped_grants},
+            (SELECT array_agg(m.org_id ORDER BY m.org_id) FROM iam_organization_member m WHERE m.user_id = u.id) AS organizations
         FROM iam_users u
         LEFT JOIN iam_user_role ur ON u.id = ur.user_id
         LEFT JOIN iam_user_permission up ON u.id = up.user_id
-        LEFT JOIN iam_user_oauth o ON u.id = o.user_id
         WHERE updated_at >= EXTRACT(EPOCH FROM NOW()) - 604800
-        GROUP BY u.id, o.oauth_id, o.oauth_provider;",
-        ).await.unwrap();
+        GROUP BY u.id;",
+            scoped_grants = SCOPED_GRANT_COLUMNS,
+        )).await.unwrap();
 
         match pool.query(&stmt, &[]).await {
             Ok(rows) => {
                 let mut amt_items = 0;
                 for row in rows {
                     amt_items += 1;
-                    let user = User::new(
+                    let mut user = User::new(
                         row.get(0),
                         row.get(1),
                         row.get(2),
                         row.get::<_, bool>(3),
+                        row.get(12),
                         row.get::<_, i64>(4),
                         row.get::<_, i64>(5),
-                        row.get::<_, String>(8),
-                        row.get::<_, String>(9),
+                        UserAuthInfo::from_columns(row.get(8), row.get(9)),
                         row.get::<_, Option<Vec<String>>>(6).unwrap_or_default(),
                         row.get::<_, Option<Vec<String>>>(7).unwrap_or_default(),
                         UserSecurity::new(
@@ -1194,6 +2069,16 @@ impl Task<PostgresDatabase, TaskRequest, UserPreloadCache> for UserPreloadCache
                             row.get(11),
                         ),
                     );
+                    user.access.denied_permission =
+                        row.get::<_, Option<Vec<String>>>(13).unwrap_or_default();
+                    user.access.role_expires_at =
+                        UserAccessInfo::expiries_from_columns(row.get(14), row.get(15));
+                    user.access.permission_expires_at =
+                        UserAccessInfo::expiries_from_columns(row.get(16), row.get(17));
+                    user.access.scoped_role = ScopedGrant::from_json(row.get(18));
+                    user.access.scoped_permission = ScopedGrant::from_json(row.get(19));
+                    user.access.organizations =
+                        row.get::<_, Option<Vec<String>>>(20).unwrap_or_default();
                     UserCacheManager::add_user_to_cache(user.clone()).unwrap();
                 }
                 println!("[ARK] cached {} user(s) cache.", amt_items);
@@ -1213,3 +2098,89 @@ impl Task<PostgresDatabase, TaskRequest, UserPreloadCache> for UserPreloadCache
         }
     }
 }
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct UserSyncProfile {
+    pub user_id: String,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub verified: Option<bool>,
+    pub avatar_url: Option<String>,
+}
+
+#[async_trait]
+impl Task<PostgresDatabase, TaskRequest, UserSyncProfile> for UserSyncProfile {
+    async fn run(
+        db: &PostgresDatabase,
+        request: TaskRequest,
+        param: UserSyncProfile,
+    ) -> TaskResponse {
+        let pool = db.pool.get().await.unwrap();
+        // the provider only refreshes what it knows about, a username or email
+        // that already belongs to another user is kept as is.
+        let stmt = pool
+            .prepare(
+                "UPDATE iam_users
+                SET username = CASE
+                        WHEN $2::VARCHAR IS NULL
+                          OR EXISTS (SELECT 1 FROM iam_users WHERE username = $2 AND id <> $1)
+                        THEN username ELSE $2 END,
+                    email = CASE
+                        WHEN $3::VARCHAR IS NULL
+                          OR EXISTS (SELECT 1 FROM iam_users WHERE email = $3 AND id <> $1)
+                        THEN email ELSE $3 END,
+                    verified = COALESCE($4, verified),
+                    avatar_url = COALESCE($5, avatar_url),
+                    updated_at = $6
+                WHERE id = $1
+                RETURNING username, email, verified, avatar_url, updated_at;",
+            )
+            .await
+            .unwrap();
+        match pool
+            .query_one(
+                &stmt,
+                &[
+                    &param.user_id,
+                    &param.username,
+                    &param.email,
+                    &param.verified,
+                    &param.avatar_url,
+                    &(SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_millis() as i64),
+                ],
+            )
+            .await
+        {
+            Ok(row) => {
+                match UserCacheManager::read_user_from_cache(&param.user_id) {
+                    Ok(mut user) => {
+                        user.info.username = row.get(0);
+                        user.info.email = row.get(1);
+                        user.info.verified = row.get(2);
+                        user.info.avatar_url = row.get(3);
+                        user.info.updated_at = row.get(4);
+                        UserCacheManager::add_user_to_cache(user).unwrap();
+                    }
+                    Err(_) => {
+                        /* if not found in cache then it will just update the database. */
+                    }
+                }
+                return TaskResponse::compose_response(
+                    request,
+                    TaskStatus::Completed,
+                    param,
+                    Vec::default(),
+                );
+            }
+            Err(_) => {
+                return TaskResponse::throw_failed_response(
+                    request,
+                    vec![TaskError::UserNotFound.to_string()],
+                )
+            }
+        }
+    }
+}
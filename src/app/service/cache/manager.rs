@@ -104,8 +104,7 @@ impl CacheManager {
         let cache_response = Self::send(request);
         match cache_response.cache_status {
             CacheStatus::Completed => {
-                let response = CacheResponse::intepret_response_result::<T>(&cache_response);
-                response
+                CacheResponse::intepret_response_result::<T>(&cache_response)
             },
             CacheStatus::Failed => Err(CacheError::FailedToCompleteCache),
         }
@@ -39,9 +39,9 @@ pub mod manager;
 pub mod message;
 
 static INBOUND_CACHE: Lazy<(Sender<CacheRequest>, Receiver<CacheRequest>)> =
-   Lazy::new(|| unbounded());
+   Lazy::new(unbounded);
 static OUTBOUND_CACHE: Lazy<(Sender<CacheResponse>, Receiver<CacheResponse>)> =
-    Lazy::new(|| unbounded());
+    Lazy::new(unbounded);
 
 pub trait LocalizedCache<T> {
     fn add(item: T);
@@ -67,7 +67,7 @@ pub fn notify_cache_hit(source: &str, action: &str, task_id: &str) {
         "[CACHE] HIT Successfully retrieved the requested item from the cache\n - Task Id: {}\n - Cache Key: {}\n - Timestamp: {}\n - Source: {}",
         task_id,
         action,
-        Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        Utc::now().format("%Y-%m-%d %H:%M:%S"),
         source
     );
 }
@@ -78,15 +78,15 @@ pub fn notify_cache_miss(source: &str, cache_key: &str, task_id: &str) {
         "[CACHE] MISS The requested item was not found in the cache.\n - Task Id: {}\n - Cache Key: {}\n - Timestamp: {}\n - Source: {}",
         task_id,
         cache_key,
-        Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        Utc::now().format("%Y-%m-%d %H:%M:%S"),
         source
     );
 }
 
 //static INBOUND_CACHE: Lazy<(Sender<CacheRequest>, Receiver<CacheRequest>)> =
-//    Lazy::new(|| unbounded());
+//    Lazy::new(unbounded);
 //static OUTBOUND_CACHE: Lazy<(Sender<CacheResponse>, Receiver<CacheResponse>)> =
-//    Lazy::new(|| unbounded());
+//    Lazy::new(unbounded);
 
 
 // Cache
@@ -21,6 +21,8 @@ pub enum TaskError {
     PermissionFailedToPreload,
     #[error("PermissionLinkAlreadyExist")]
     PermissionLinkAlreadyExist,
+    #[error("PermissionKeyInvalid")]
+    PermissionKeyInvalid,
     #[error("RoleDuplication")]
     RoleDuplication,
     #[error("RoleFieldNotFound")]
@@ -31,6 +33,27 @@ pub enum TaskError {
     RoleFailedToPreload,
     #[error("RoleLinkFailedToLink")]
     RoleLinkFailedToLink,
+    #[error("RoleCycleDetected")]
+    RoleCycleDetected,
+    #[error("RoleParentNotFound")]
+    RoleParentNotFound,
+    #[error("RoleSessionPolicyInvalid")]
+    RoleSessionPolicyInvalid,
+    // Organization
+    #[error("OrganizationDuplication")]
+    OrganizationDuplication,
+    #[error("OrganizationFieldNotFound")]
+    OrganizationFieldNotFound,
+    #[error("OrganizationNotFound")]
+    OrganizationNotFound,
+    #[error("OrganizationFailedToPreload")]
+    OrganizationFailedToPreload,
+    #[error("OrganizationMemberAlreadyExists")]
+    OrganizationMemberAlreadyExists,
+    #[error("OrganizationMemberNotFound")]
+    OrganizationMemberNotFound,
+    #[error("OrganizationMismatch")]
+    OrganizationMismatch,
     // Task
     #[error("TaskInternalError")]
     TaskInternalError,
@@ -59,13 +82,27 @@ pub enum TaskError {
     UserPermissionAlreadyExists,
     #[error("UserRoleAlreadyExists")]
     UserRoleAlreadyExists,
+    #[error("UserIdentityAlreadyLinked")]
+    UserIdentityAlreadyLinked,
+    #[error("UserIdentityNotFound")]
+    UserIdentityNotFound,
+    #[error("UserCannotUnlinkLastIdentity")]
+    UserCannotUnlinkLastIdentity,
+    #[error("UserIdentityTokenNotFound")]
+    UserIdentityTokenNotFound,
+    #[error("UserIdentityTokenExpired")]
+    UserIdentityTokenExpired,
     // Session
     #[error("SessionCreationFailed")]
     SessionCreationFailed,
     #[error("SessionDeletionFailed")]
     SessionDeletionFailed,
     #[error("SessionNotFound")]
-    SessionNotFound
+    SessionNotFound,
+    #[error("SessionRefreshTokenReused")]
+    SessionRefreshTokenReused,
+    #[error("SessionLimitReached")]
+    SessionLimitReached
 }
 
 pub type TaskResult<T> = Result<T, TaskError>;
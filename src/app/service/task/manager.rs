@@ -6,8 +6,8 @@ use serde::{Deserialize, Serialize};
 use tokio::task::{self, JoinHandle};
 
 use crate::app::{
-    database::{postgres::PostgresDatabase, redis::RedisDatabase},
-    platform::iam::{permission::task::PermissionTaskHandler, role::task::RoleTaskHandler, session::task::SessionTaskHandler, user::task::UserTaskHandler},
+    database::postgres::PostgresDatabase,
+    platform::iam::{organization::task::OrganizationTaskHandler, permission::task::PermissionTaskHandler, role::task::RoleTaskHandler, session::{store::SharedSessionStore, task::SessionTaskHandler}, user::task::UserTaskHandler},
     service::task::{
         message::{TaskStatus, TaskType},
         TaskHandler,
@@ -22,12 +22,12 @@ use super::{
 /// A structure for handling tasks within the system.
 pub struct TaskManager {
     pg: PostgresDatabase,
-    redis: RedisDatabase
+    sessions: SharedSessionStore
 }
 
 impl TaskManager {
-    pub fn new(pg: PostgresDatabase, redis: RedisDatabase) -> Self {
-        Self { pg, redis }
+    pub fn new(pg: PostgresDatabase, sessions: SharedSessionStore) -> Self {
+        Self { pg, sessions }
     }
 
     /// Starts the listening process for task requests.
@@ -39,8 +39,8 @@ impl TaskManager {
     /// ```
     pub fn listen(self) {
         let pg_clone = self.pg.clone();
-        let redis_clone = self.redis.clone();
-        Self::initialize_listener(pg_clone, redis_clone);
+        let sessions_clone = self.sessions.clone();
+        Self::initialize_listener(pg_clone, sessions_clone);
     }
 
     /// Sends a task request and waits for its completion.
@@ -68,12 +68,12 @@ impl TaskManager {
     /// // Assuming `task_request` is a valid TaskRequest object
     /// let task_response = send_async(task_request).await;
     /// ```
+    #[allow(dead_code)]
     fn send_async(task_request: TaskRequest) -> JoinHandle<TaskResponse> {
-        let task_handle = task::spawn(async move {
+        task::spawn(async move {
             Self::send_task_request(&task_request);
             Self::wait_for_async_task_completion(&task_request).await.unwrap()
-        });
-        task_handle
+        })
     }
 
     /// Process task.
@@ -108,8 +108,7 @@ impl TaskManager {
         let task_response = Self::send(request);
         match task_response.task_status {
             TaskStatus::Completed => {
-                let response = TaskResponse::intepret_response_result::<T>(&task_response);
-                response
+                TaskResponse::intepret_response_result::<T>(&task_response)
             },
             TaskStatus::Failed => Err(TaskError::FailedToCompleteTask),
         }
@@ -119,18 +118,19 @@ impl TaskManager {
     ///
     /// # Arguments
     /// - `pg_clone`: A cloned instance of `PostgresDatabase` used for handling database operations within tasks.
+    /// - `sessions_clone`: The session store the session tasks run against.
     ///
     /// # Examples
     /// ```
     /// // Assume `pg_clone` is a cloned instance of PostgresDatabase
-    /// self.initialize_listener(pg_clone);
+    /// self.initialize_listener(pg_clone, sessions_clone);
     /// ```
-    fn initialize_listener(pg_clone: PostgresDatabase, redis_clone: RedisDatabase) {
+    fn initialize_listener(pg_clone: PostgresDatabase, sessions_clone: SharedSessionStore) {
         tokio::spawn(async move {
             let inbound_receiver = &INBOUND.1;
             println!("[ARK] Task initialized, now listening to incoming requests.");
             while let Ok(task_request) = inbound_receiver.recv() {
-                Self::process_incoming_request(&pg_clone, &redis_clone, task_request).await;
+                Self::process_incoming_request(&pg_clone, &sessions_clone, task_request).await;
             }
         });
     }
@@ -148,20 +148,21 @@ impl TaskManager {
     /// ```
     async fn process_incoming_request(
         pg_clone: &PostgresDatabase,
-        redis_clone: &RedisDatabase,
+        sessions_clone: &SharedSessionStore,
         task_request: TaskRequest,
     ) {
         println!(
             "[TASK] Successfully received a task from {}. Task type: {:?}.",
             task_request.task_id, task_request.task_type
         );
-        Self::handle_task_request(pg_clone, redis_clone, task_request).await;
+        Self::handle_task_request(pg_clone, sessions_clone, task_request).await;
     }
 
     /// Handles a given task request based on its type.
     ///
     /// # Arguments
     /// - `pg`: A reference to the `PostgresDatabase` used for database operations.
+    /// - `sessions`: The session store used for session tasks.
     /// - `task_request`: The `TaskRequest` object containing details about the task to be handled.
     ///
     /// # Examples
@@ -169,7 +170,7 @@ impl TaskManager {
     /// // Assume `pg` is a reference to a PostgresDatabase and `task_request` is a valid TaskRequest
     /// self.handle_task_request(&pg, task_request).await;
     /// ```
-    async fn handle_task_request(pg: &PostgresDatabase, redis: &RedisDatabase, task_request: TaskRequest) {
+    async fn handle_task_request(pg: &PostgresDatabase, sessions: &SharedSessionStore, task_request: TaskRequest) {
         match task_request.task_type {
             TaskType::Permission => {
                 let task_response = PermissionTaskHandler::handle(pg, task_request).await;
@@ -184,11 +185,15 @@ impl TaskManager {
                 Self::send_task_response(task_response)
             },
             TaskType::Session => {
-                let task_response = SessionTaskHandler::handle(redis, task_request).await;
+                let task_response = SessionTaskHandler::handle(sessions, task_request).await;
                 Self::send_task_response(task_response)
                 // do session...
                 // when session is executed should return the session token and expiration....
             },
+            TaskType::Organization => {
+                let task_response = OrganizationTaskHandler::handle(pg, task_request).await;
+                Self::send_task_response(task_response)
+            },
         }
     }
 
@@ -247,6 +252,7 @@ impl TaskManager {
     /// // Assuming `task_request` is a reference to a valid TaskRequest
     /// let task_response = wait_for_task_completion(&task_request);
     /// ```
+    #[allow(dead_code)]
     fn wait_for_async_task_completion(task_request: &TaskRequest) -> JoinHandle<TaskResponse> {
         let task_request_clone = task_request.clone(); // Clone the TaskRequest
         task::spawn(async move {
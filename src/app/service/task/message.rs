@@ -25,7 +25,8 @@ pub enum TaskType {
     Permission,
     Role,
     User,
-    Session
+    Session,
+    Organization
 }
 
 /// A request structure for a task.
@@ -38,10 +38,10 @@ pub mod manager;
 pub mod message;
 
 /// Receives tasks and processing them.
-static INBOUND: Lazy<(Sender<TaskRequest>, Receiver<TaskRequest>)> = Lazy::new(|| unbounded());
+static INBOUND: Lazy<(Sender<TaskRequest>, Receiver<TaskRequest>)> = Lazy::new(unbounded);
 
 /// Receives results from tasks and sends them back to the origin point.
-static OUTBOUND: Lazy<(Sender<TaskResponse>, Receiver<TaskResponse>)> = Lazy::new(|| unbounded());
+static OUTBOUND: Lazy<(Sender<TaskResponse>, Receiver<TaskResponse>)> = Lazy::new(unbounded);
 
 /// Used to create specific tasks.
 #[async_trait]
@@ -0,0 +1,11 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ValidationError {
+    #[error("ValidationFieldFailure")]
+    ValidationFieldFailure,
+    #[error("PermissionKeyInvalid")]
+    PermissionKeyInvalid,
+}
+
+pub type ValidationResult<T> = Result<T, ValidationError>;
@@ -0,0 +1,8 @@
+use self::error::ValidationResult;
+
+pub mod error;
+
+/// Checks an item before it is built, returning it untouched when it is valid.
+pub trait Validator<T> {
+    fn validate(item: T) -> ValidationResult<T>;
+}
@@ -8,6 +8,12 @@ use app::{
 
 pub mod app;
 
+// the attributes of `ark-macros` expand to `::ark::__guard`, wherever the
+// guard module lives.
+extern crate self as ark;
+#[doc(hidden)]
+pub use app::platform::iam::guard as __guard;
+
 #[tokio::main]
 async fn main() {
     let ark = ArkServer::default().await;